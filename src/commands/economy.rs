@@ -0,0 +1,451 @@
+use super::*;
+use crate::core::economy::{self, EconomyError, Item, Settings, Transaction, TransactionKind};
+
+const PAGE_SIZE: usize = 10;
+
+pub fn commands<'a>() -> Vec<Command<'a>> {
+	let category = "Economy".to_string();
+	vec![
+		// balance
+		Command {
+			names: svec!["balance", "bal"],
+			desc: "See how much money you or someone else has".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(false)
+					.description("User whose balance you want to see")
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let user = params.options.get_user("user").map(|v| v.0.clone()).unwrap_or(params.author.clone());
+				let (settings, balance) = {
+					let db = params.db.read().unwrap();
+					(economy::settings(&CHLOE.config, &db, guild_id), economy::balance(&db, guild_id, user.id))
+				};
+				Ok(CommRes::Text(match user.id == params.author.id {
+					true => format!("You have {}", settings.format(balance)),
+					false => format!("**{}** has {}", user.tag(), settings.format(balance))
+				}))
+			}),
+			..Command::new()
+		},
+		// daily
+		Command {
+			names: svec!["daily"],
+			desc: "Claim your daily reward".to_string(),
+			options: Vec::new(),
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let mut db = params.db.write().unwrap();
+				let settings = economy::settings(&CHLOE.config, &db, guild_id);
+				let transaction = match economy::claim_daily(&CHLOE.config, &mut db, guild_id, params.author.id) {
+					Ok(v) => v,
+					Err(e) => return Err(economy_error(&params, &settings, e))
+				};
+				Ok(CommRes::Text(format!(
+					"You got {}, so you have {} now. Come back {} for more",
+					settings.format(transaction.amount as u64), settings.format(transaction.balance),
+					time::discord_timestamp(transaction.created + economy::DAILY_COOLDOWN, "R")
+				)))
+			}),
+			..Command::new()
+		},
+		// pay
+		Command {
+			names: svec!["pay"],
+			desc: "Give some of your money to someone".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(true)
+					.description("Who you want to pay")
+			}, |option| {
+				option.name("amount").kind(CommandOptionType::Integer).required(true)
+					.description("How much")
+					.min_int_value(1)
+					.max_int_value(economy::MAX_BALANCE)
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let (user, _) = handle_syntax_opt!(params.options.get_user("user"));
+				let amount = handle_syntax_opt!(params.options.get_int("amount"));
+				if amount < 1 {
+					return Err(error!("You can only pay more than 0"));
+				}
+				if user.id == params.author.id {
+					return Err(error!("You can't pay yourself"));
+				}
+				if user.bot {
+					return Err(error!("Bots can't have money"));
+				}
+				let mut db = params.db.write().unwrap();
+				let settings = economy::settings(&CHLOE.config, &db, guild_id);
+				let transaction = match economy::pay(&CHLOE.config, &mut db, guild_id, params.author.id, user.id, amount as u64) {
+					Ok(v) => v,
+					Err(e) => return Err(economy_error(&params, &settings, e))
+				};
+				Ok(CommRes::Text(format!("Paid {} to **{}**. You have {} left", settings.format(amount as u64), user.tag(), settings.format(transaction.balance))))
+			}),
+			..Command::new()
+		},
+		// richest
+		Command {
+			names: svec!["richest", "baltop"],
+			desc: "See who has the most money in the server".to_string(),
+			options: vec![|option| {
+				option.name("page").kind(CommandOptionType::Integer).required(false)
+					.description("Page to start at")
+					.min_int_value(1)
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let (settings, members) = {
+					let db = params.db.read().unwrap();
+					(economy::settings(&CHLOE.config, &db, guild_id), economy::leaderboard(&db, guild_id))
+				};
+				if members.is_empty() {
+					return Ok(CommRes::Text("Nobody has money in this server yet".to_string()));
+				}
+				let lines: Vec<String> = members.iter().enumerate().map(|(i, (user_id, balance))| {
+					format!("**{}.** <@{}>: {}", i + 1, user_id, settings.format(*balance))
+				}).collect();
+				let page = params.options.get_int("page").unwrap_or(1).max(1) as usize - 1;
+				paginate(&params, "Richest members", &lines, PAGE_SIZE, page).await
+			}),
+			..Command::new()
+		},
+		// shop
+		Command {
+			names: svec!["shop"],
+			desc: "See what the server's shop sells".to_string(),
+			options: Vec::new(),
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let (settings, items) = {
+					let db = params.db.read().unwrap();
+					(economy::settings(&CHLOE.config, &db, guild_id), economy::items(&db, guild_id))
+				};
+				if items.is_empty() {
+					return Ok(CommRes::Text("The shop has nothing for sale".to_string()));
+				}
+				let lines: Vec<String> = items.iter().map(|item| {
+					let mut line = format!("**{}**: {}", item.name, settings.format(item.price));
+					if let Some(role_id) = item.role_id {
+						line += &format!(", gives <@&{}>", role_id);
+					}
+					if let Some(stock) = item.stock {
+						line += &match stock {
+							0 => ", sold out".to_string(),
+							v => format!(", {} left", v)
+						};
+					}
+					if let Some(description) = &item.description {
+						line += &format!("\n{}", description);
+					}
+					line
+				}).collect();
+				paginate(&params, "Shop", &lines, PAGE_SIZE, 0).await
+			}),
+			..Command::new()
+		},
+		// buy
+		Command {
+			names: svec!["buy"],
+			desc: "Buy something from the server's shop".to_string(),
+			options: vec![|option| {
+				option.name("item").kind(CommandOptionType::String).required(true)
+					.description("Name of the item")
+					.set_autocomplete(true)
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let name = handle_syntax_opt!(params.options.get_string("item"));
+				let (settings, transaction, item) = {
+					let mut db = params.db.write().unwrap();
+					let settings = economy::settings(&CHLOE.config, &db, guild_id);
+					let item = handle_opt!(economy::find_item(&db, guild_id, &name), format!("The shop has no item called \"{}\"", name));
+					if item.role_id.is_some_and(|v| params.member.as_ref().is_some_and(|m| m.roles.contains(&v))) {
+						return Err(error!("You already have that role"));
+					}
+					match economy::buy(&CHLOE.config, &mut db, guild_id, params.author.id, &item) {
+						Ok(v) => (settings, v, item),
+						Err(e) => return Err(economy_error(&params, &settings, e))
+					}
+				};
+				if let Some(role_id) = item.role_id {
+					let added = params.ctx.http.add_member_role(guild_id.0, params.author.id.0, role_id.0, Some("Bought in the shop")).await;
+					if let Err(e) = added {
+						let refunded = economy::refund(&CHLOE.config, &mut params.db.write().unwrap(), guild_id, params.author.id, &item);
+						return Err(match refunded {
+							Ok(..) => error!("I couldn't give you the role, so you got your money back. My highest role must be above it", e),
+							Err(..) => error!("I couldn't give you the role, nor give your money back. Tell an admin about it", e)
+						});
+					}
+				}
+				Ok(CommRes::Text(format!("You bought **{}** for {}. You have {} left", item.name, settings.format(item.price), settings.format(transaction.balance))))
+			}),
+			autocomplete: Some(|params: CommandParams| func!({
+				item_names(&params)
+			})),
+			..Command::new()
+		},
+		// inventory
+		Command {
+			names: svec!["inventory", "inv"],
+			desc: "See what you or someone else bought".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(false)
+					.description("User whose inventory you want to see")
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let user = params.options.get_user("user").map(|v| v.0.clone()).unwrap_or(params.author.clone());
+				let items = economy::inventory(&params.db.read().unwrap(), guild_id, user.id);
+				if items.is_empty() {
+					return Ok(CommRes::Text(match user.id == params.author.id {
+						true => "You haven't bought anything".to_string(),
+						false => format!("**{}** hasn't bought anything", user.tag())
+					}));
+				}
+				let lines: Vec<String> = items.iter().map(|(name, count)| format!("**{}** ×{}", name, count)).collect();
+				paginate(&params, &format!("{}'s inventory", user.name), &lines, PAGE_SIZE, 0).await
+			}),
+			..Command::new()
+		},
+		// economy
+		Command {
+			names: svec!["economy"],
+			desc: "Set up the server's economy and shop".to_string(),
+			options: vec![|option| {
+				option.name("currency").kind(CommandOptionType::SubCommand)
+					.description("Change the name of the currency")
+					.create_sub_option(|o| {
+						o.name("name").kind(CommandOptionType::String).required(true)
+							.description("Name, like coins")
+					})
+			}, |option| {
+				option.name("daily").kind(CommandOptionType::SubCommand)
+					.description("Change how much the daily reward is")
+					.create_sub_option(|o| {
+						o.name("amount").kind(CommandOptionType::Integer).required(true)
+							.description("How much")
+							.min_int_value(1)
+							.max_int_value(economy::MAX_BALANCE)
+					})
+			}, |option| {
+				option.name("give").kind(CommandOptionType::SubCommand)
+					.description("Give money to a member")
+					.create_sub_option(|o| {
+						o.name("user").kind(CommandOptionType::User).required(true)
+							.description("Member")
+					})
+					.create_sub_option(|o| {
+						o.name("amount").kind(CommandOptionType::Integer).required(true)
+							.description("How much")
+							.min_int_value(1)
+							.max_int_value(economy::MAX_BALANCE)
+					})
+					.create_sub_option(|o| {
+						o.name("reason").kind(CommandOptionType::String).required(false)
+							.description("Why, for the transaction log")
+					})
+			}, |option| {
+				option.name("take").kind(CommandOptionType::SubCommand)
+					.description("Take money from a member")
+					.create_sub_option(|o| {
+						o.name("user").kind(CommandOptionType::User).required(true)
+							.description("Member")
+					})
+					.create_sub_option(|o| {
+						o.name("amount").kind(CommandOptionType::Integer).required(true)
+							.description("How much")
+							.min_int_value(1)
+							.max_int_value(economy::MAX_BALANCE)
+					})
+					.create_sub_option(|o| {
+						o.name("reason").kind(CommandOptionType::String).required(false)
+							.description("Why, for the transaction log")
+					})
+			}, |option| {
+				option.name("additem").kind(CommandOptionType::SubCommand)
+					.description("Put something up for sale in the shop")
+					.create_sub_option(|o| {
+						o.name("name").kind(CommandOptionType::String).required(true)
+							.description("Name of the item")
+					})
+					.create_sub_option(|o| {
+						o.name("price").kind(CommandOptionType::Integer).required(true)
+							.description("Price")
+							.min_int_value(0)
+							.max_int_value(economy::MAX_BALANCE)
+					})
+					.create_sub_option(|o| {
+						o.name("role").kind(CommandOptionType::Role).required(false)
+							.description("Role that buyers get, if it's a role")
+					})
+					.create_sub_option(|o| {
+						o.name("stock").kind(CommandOptionType::Integer).required(false)
+							.description("How many can be bought (no limit if left out)")
+							.min_int_value(1)
+					})
+					.create_sub_option(|o| {
+						o.name("description").kind(CommandOptionType::String).required(false)
+							.description("Text shown below it in the shop")
+					})
+			}, |option| {
+				option.name("removeitem").kind(CommandOptionType::SubCommand)
+					.description("Take something off the shop")
+					.create_sub_option(|o| {
+						o.name("item").kind(CommandOptionType::String).required(true)
+							.description("Name of the item")
+							.set_autocomplete(true)
+					})
+			}, |option| {
+				option.name("transactions").kind(CommandOptionType::SubCommand)
+					.description("See the latest changes to balances")
+					.create_sub_option(|o| {
+						o.name("user").kind(CommandOptionType::User).required(false)
+							.description("Only show this member's")
+					})
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_GUILD,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				if subcommand == "transactions" {
+					let user_id = options.get_user("user").map(|v| v.0.id);
+					let (settings, transactions) = {
+						let db = params.db.read().unwrap();
+						(economy::settings(&CHLOE.config, &db, guild_id), economy::transactions(&db, guild_id, user_id))
+					};
+					if transactions.is_empty() {
+						return Ok(CommRes::Text("There are no transactions".to_string()));
+					}
+					let lines: Vec<String> = transactions.iter().map(|v| transaction_line(&settings, v)).collect();
+					return paginate(&params, "Transactions", &lines, PAGE_SIZE, 0).await;
+				}
+				if let ("additem", Some(role)) = (subcommand, options.get_role("role")) {
+					check_role(&params, role).await?;
+				}
+				let mut db = params.db.write().unwrap();
+				let mut settings = economy::settings(&CHLOE.config, &db, guild_id);
+				let text = match subcommand {
+					"currency" => {
+						let name = handle_syntax_opt!(options.get_string("name")).trim().to_string();
+						if !(1..=32).contains(&name.chars().count()) {
+							return Err(error!("The name must be up to 32 characters long"));
+						}
+						settings.currency = name;
+						handle!(economy::set_settings(&mut db, guild_id, &settings));
+						format!("Amounts will now look like {}", settings.format(100))
+					},
+					"daily" => {
+						settings.daily = handle_syntax_opt!(options.get_int("amount")).clamp(1, economy::MAX_BALANCE as i64) as u64;
+						handle!(economy::set_settings(&mut db, guild_id, &settings));
+						format!("The daily reward is now {}", settings.format(settings.daily))
+					},
+					"give" | "take" => {
+						let (user, _) = handle_syntax_opt!(options.get_user("user"));
+						if user.bot {
+							return Err(error!("Bots can't have money"));
+						}
+						let amount = handle_syntax_opt!(options.get_int("amount")).clamp(1, economy::MAX_BALANCE as i64);
+						let amount = if subcommand == "take" { -amount } else { amount };
+						let mut transaction = [Transaction::new(TransactionKind::Admin, user.id, amount, Some(params.author.id), options.get_string("reason"))];
+						if let Err(e) = economy::apply(&CHLOE.config, &mut db, guild_id, &mut transaction, &[]) {
+							return Err(economy_error(&params, &settings, e));
+						}
+						match amount > 0 {
+							true => format!("Gave {} to **{}**, who has {} now", settings.format(amount as u64), user.tag(), settings.format(transaction[0].balance)),
+							false => format!("Took {} from **{}**, who has {} left", settings.format(amount.unsigned_abs()), user.tag(), settings.format(transaction[0].balance))
+						}
+					},
+					"additem" => {
+						let name = handle_syntax_opt!(options.get_string("name")).trim().to_string();
+						if !(1..=50).contains(&name.chars().count()) {
+							return Err(error!("The name must be up to 50 characters long"));
+						}
+						if economy::find_item(&db, guild_id, &name).is_some() {
+							return Err(error!(format!("The shop already has an item called \"{}\"", name)));
+						}
+						if economy::items(&db, guild_id).len() >= economy::MAX_ITEMS {
+							return Err(error!(format!("Shops can only have up to {} items", economy::MAX_ITEMS)));
+						}
+						let mut item = Item::new(name, handle_syntax_opt!(options.get_int("price")).clamp(0, economy::MAX_BALANCE as i64) as u64);
+						item.role_id = options.get_role("role").map(|v| v.id);
+						item.stock = options.get_int("stock").map(|v| v.max(1) as u64);
+						item.description = options.get_string("description").map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+						if item.description.as_ref().is_some_and(|v| v.chars().count() > 200) {
+							return Err(error!("The description must be up to 200 characters long"));
+						}
+						handle!(economy::save_item(&mut db, guild_id, &item));
+						match item.role_id {
+							Some(role_id) => format!("**{}** is now for sale for {}, and gives <@&{}>", item.name, settings.format(item.price), role_id),
+							None => format!("**{}** is now for sale for {}", item.name, settings.format(item.price))
+						}
+					},
+					"removeitem" => {
+						let name = handle_syntax_opt!(options.get_string("item"));
+						let item = handle_opt!(economy::find_item(&db, guild_id, &name), format!("The shop has no item called \"{}\"", name));
+						handle!(economy::remove_item(&mut db, guild_id, &item.id));
+						format!("**{}** isn't for sale anymore. Members that bought it still have it", item.name)
+					},
+					_ => return Err(syntax_error!())
+				};
+				Ok(CommRes::Text(text))
+			}),
+			autocomplete: Some(|params: CommandParams| func!({
+				item_names(&params)
+			})),
+			..Command::new()
+		},
+	]
+}
+
+/// Tells the user why money couldn't change hands
+fn economy_error(params: &CommandParams, settings: &Settings, e: EconomyError) -> CommErr {
+	match e {
+		EconomyError::NotEnough(user_id, balance) if user_id == params.author.id => error!(format!("You only have {}", settings.format(balance))),
+		EconomyError::NotEnough(user_id, balance) => error!(format!("<@{}> only has {}", user_id, settings.format(balance))),
+		EconomyError::TooMuch(user_id) => error!(format!("<@{}> can't have more than {}", user_id, settings.format(economy::MAX_BALANCE))),
+		EconomyError::Cooldown(at) => error!(format!("You already claimed your daily reward. You can claim it again {}", time::discord_timestamp(at, "R"))),
+		EconomyError::SoldOut => error!("That item is sold out"),
+		EconomyError::Database(e) => error!(, e)
+	}
+}
+
+/// Describes a transaction in a line, like "+100 <@user> Daily reward → 250 coins"
+fn transaction_line(settings: &Settings, transaction: &Transaction) -> String {
+	let mut line = format!("{} **{:+}** <@{}> {}", time::discord_timestamp(transaction.created, "R"), transaction.amount, transaction.user_id, transaction.kind.name());
+	if let Some(other_id) = transaction.other_id {
+		line += &match transaction.kind {
+			TransactionKind::Admin => format!(" by <@{}>", other_id),
+			_ => format!(" with <@{}>", other_id)
+		};
+	}
+	if let Some(note) = &transaction.note {
+		line += &format!(": {}", note);
+	}
+	line + &format!(" → {}", settings.format(transaction.balance))
+}
+
+/// Names of the items in the shop that match what's being typed
+fn item_names(params: &CommandParams) -> Vec<String> {
+	let typed = match params.options.focused().and_then(|o| o.value.clone()) {
+		Some(Value::String(v)) => v.to_lowercase(),
+		_ => String::new()
+	};
+	let guild_id = match params.guild_id {
+		Some(v) => v,
+		None => return Vec::new()
+	};
+	economy::items(&params.db.read().unwrap(), guild_id).into_iter()
+		.map(|item| item.name)
+		.filter(|name| name.to_lowercase().contains(typed.as_str()))
+		.collect()
+}
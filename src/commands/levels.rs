@@ -0,0 +1,343 @@
+use super::*;
+use crate::core::levels::{self, Announce};
+
+const LEADERBOARD_PAGE_SIZE: usize = 10;
+/// Highest XP a message can give
+const MAX_MESSAGE_XP: u64 = 1000;
+
+pub fn commands<'a>() -> Vec<Command<'a>> {
+	let category = "Levels".to_string();
+	vec![
+		// rank
+		Command {
+			names: svec!["rank", "level"],
+			desc: "See your level or someone else's".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(false)
+					.description("User whose level you want to see")
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let user = params.options.get_user("user").map(|v| v.0.clone()).unwrap_or(params.author.clone());
+				if user.bot {
+					return Err(error!("Bots don't get XP"));
+				}
+				let (xp, (rank, total)) = {
+					let db = params.db.read().unwrap();
+					(levels::xp(&db, guild_id, user.id), levels::rank(&db, guild_id, user.id))
+				};
+				let level = levels::level(xp);
+				let into_level = xp - levels::total_xp(level);
+				let needed = levels::level_xp(level);
+				let filled = (into_level * 20 / needed) as usize;
+				Ok(CommRes::Msg(make_message(|m| {
+					m.add_embed(|e| {
+						e.author(|a| a.name(user.tag()).icon_url(user.face()))
+						.field("Level", level, true)
+						.field("XP", xp, true)
+						.field("Rank", match rank {
+							Some(rank) => format!("#{} of {}", rank, total),
+							None => "Unranked".to_string()
+						}, true)
+						.field(format!("Progress to level {}", level + 1), format!("`{}{}` {}/{} XP", "█".repeat(filled), "░".repeat(20 - filled), into_level, needed), false)
+						.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+					})
+				})))
+			}),
+			..Command::new()
+		},
+		// leaderboard
+		Command {
+			names: svec!["leaderboard", "lb", "top"],
+			desc: "See who has the most XP in the server".to_string(),
+			options: vec![|option| {
+				option.name("page").kind(CommandOptionType::Integer).required(false)
+					.description("Page to start at")
+					.min_int_value(1)
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let members = levels::leaderboard(&params.db.read().unwrap(), guild_id);
+				if members.is_empty() {
+					return Ok(CommRes::Text("Nobody has XP in this server yet".to_string()));
+				}
+				let lines: Vec<String> = members.iter().enumerate().map(|(i, (user_id, xp))| {
+					format!("**{}.** <@{}>: level {} ({} XP)", i + 1, user_id, levels::level(*xp), xp)
+				}).collect();
+				let page = params.options.get_int("page").unwrap_or(1).max(1) as usize - 1;
+				paginate(&params, "Leaderboard", &lines, LEADERBOARD_PAGE_SIZE, page).await
+			}),
+			..Command::new()
+		},
+		// levels
+		Command {
+			names: svec!["levels"],
+			desc: "Set up how members earn XP and what they get for it".to_string(),
+			options: vec![|option| {
+				option.name("status").kind(CommandOptionType::SubCommand)
+					.description("See how leveling is set up")
+			}, |option| {
+				option.name("enable").kind(CommandOptionType::SubCommand)
+					.description("Turn leveling on or off")
+					.create_sub_option(|o| {
+						o.name("enabled").kind(CommandOptionType::Boolean).required(true)
+							.description("Whether members earn XP")
+					})
+			}, |option| {
+				option.name("xp").kind(CommandOptionType::SubCommand)
+					.description("Choose how much XP a message gives")
+					.create_sub_option(|o| {
+						o.name("min").kind(CommandOptionType::Integer).required(true)
+							.description("Least XP")
+							.min_int_value(1)
+							.max_int_value(MAX_MESSAGE_XP)
+					})
+					.create_sub_option(|o| {
+						o.name("max").kind(CommandOptionType::Integer).required(true)
+							.description("Most XP")
+							.min_int_value(1)
+							.max_int_value(MAX_MESSAGE_XP)
+					})
+			}, |option| {
+				option.name("cooldown").kind(CommandOptionType::SubCommand)
+					.description("Choose how long members wait before their messages give XP again")
+					.create_sub_option(|o| {
+						o.name("duration").kind(CommandOptionType::String).required(true)
+							.description("Like 30s or 1m")
+					})
+			}, |option| {
+				option.name("announce").kind(CommandOptionType::SubCommand)
+					.description("Choose where level-ups are announced")
+					.create_sub_option(|o| {
+						o.name("where").kind(CommandOptionType::String).required(true)
+							.description("Where")
+							.add_string_choice("Nowhere", "off")
+							.add_string_choice("Where the member talked", "here")
+							.add_string_choice("In a channel", "channel")
+					})
+					.create_sub_option(|o| {
+						o.name("channel").kind(CommandOptionType::Channel).required(false)
+							.description("Channel, when they're announced in one")
+					})
+			}, |option| {
+				option.name("message").kind(CommandOptionType::SubCommand)
+					.description("Change the text of level-up announcements")
+					.create_sub_option(|o| {
+						o.name("text").kind(CommandOptionType::String).required(false)
+							.description("It can have {user}, {mention} and {level} (the default if left out)")
+					})
+			}, |option| {
+				option.name("reward").kind(CommandOptionType::SubCommand)
+					.description("Choose a role that members get when they reach a level")
+					.create_sub_option(|o| {
+						o.name("level").kind(CommandOptionType::Integer).required(true)
+							.description("Level")
+							.min_int_value(1)
+					})
+					.create_sub_option(|o| {
+						o.name("role").kind(CommandOptionType::Role).required(false)
+							.description("Role (the level's reward is removed if left out)")
+					})
+			}, |option| {
+				option.name("multiplier").kind(CommandOptionType::SubCommand)
+					.description("Make a channel or role give more or less XP")
+					.create_sub_option(|o| {
+						o.name("value").kind(CommandOptionType::Number).required(true)
+							.description("What XP is multiplied by, like 1.5 (1 to remove it)")
+							.min_number_value(0.0)
+							.max_number_value(levels::MAX_MULTIPLIER)
+					})
+					.create_sub_option(|o| {
+						o.name("channel").kind(CommandOptionType::Channel).required(false)
+							.description("Channel")
+					})
+					.create_sub_option(|o| {
+						o.name("role").kind(CommandOptionType::Role).required(false)
+							.description("Role")
+					})
+			}, |option| {
+				option.name("ignore").kind(CommandOptionType::SubCommand)
+					.description("Choose whether messages in a channel give XP")
+					.create_sub_option(|o| {
+						o.name("channel").kind(CommandOptionType::Channel).required(true)
+							.description("Channel")
+					})
+					.create_sub_option(|o| {
+						o.name("ignored").kind(CommandOptionType::Boolean).required(true)
+							.description("Whether messages in it don't give XP")
+					})
+			}, |option| {
+				option.name("setxp").kind(CommandOptionType::SubCommand)
+					.description("Change how much XP a member has")
+					.create_sub_option(|o| {
+						o.name("user").kind(CommandOptionType::User).required(true)
+							.description("Member")
+					})
+					.create_sub_option(|o| {
+						o.name("xp").kind(CommandOptionType::Integer).required(true)
+							.description("XP")
+							.min_int_value(0)
+					})
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_GUILD,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let mut settings = levels::settings(&CHLOE.config, &params.db.read().unwrap(), guild_id);
+				let text = match subcommand {
+					"status" => {
+						let mut rewards: Vec<String> = settings.rewards.iter().map(|(level, role_id)| format!("Level {}: <@&{}>", level, role_id)).collect();
+						if rewards.is_empty() {
+							rewards.push("None".to_string());
+						}
+						let mut multipliers: Vec<String> = settings.channel_multipliers.iter().map(|(id, m)| format!("<#{}>: ×{}", id, m))
+							.chain(settings.role_multipliers.iter().map(|(id, m)| format!("<@&{}>: ×{}", id, m)))
+							.collect();
+						if multipliers.is_empty() {
+							multipliers.push("None".to_string());
+						}
+						let ignored = match settings.ignored_channels.is_empty() {
+							true => "None".to_string(),
+							false => settings.ignored_channels.iter().map(|v| format!("<#{}>", v)).collect::<Vec<String>>().join(" ")
+						};
+						return Ok(CommRes::Msg(make_message(|m| {
+							m.add_embed(|e| {
+								e.title("Leveling")
+								.description(format!(
+									"{}\nMessages give {} to {} XP, once every {}",
+									if settings.enabled { "✅ On" } else { "❌ Off" },
+									settings.xp.0, settings.xp.1, time::format_duration(Duration::from_secs(settings.cooldown))
+								))
+								.field("Level-ups are announced", match settings.announce {
+									Announce::Off => "Nowhere".to_string(),
+									Announce::Here => "Where the member talked".to_string(),
+									Announce::Channel(channel_id) => format!("In <#{}>", channel_id)
+								}, false)
+								.field("Announcement", settings.message(), false)
+								.field("Rewards", rewards.join("\n"), false)
+								.field("Multipliers", multipliers.join("\n"), false)
+								.field("Ignored channels", ignored, false)
+								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+							})
+						})));
+					},
+					"enable" => {
+						settings.enabled = handle_syntax_opt!(options.get_bool("enabled"));
+						match settings.enabled {
+							true => "Members will now earn XP by talking".to_string(),
+							false => "Members won't earn XP anymore".to_string()
+						}
+					},
+					"xp" => {
+						let min = handle_syntax_opt!(options.get_int("min")).clamp(1, MAX_MESSAGE_XP as i64) as u64;
+						let max = handle_syntax_opt!(options.get_int("max")).clamp(1, MAX_MESSAGE_XP as i64) as u64;
+						if min > max {
+							return Err(error!("The least XP can't be more than the most XP"));
+						}
+						settings.xp = (min, max);
+						format!("Messages will now give {} to {} XP", min, max)
+					},
+					"cooldown" => {
+						let duration = handle_syntax_opt!(options.get_string("duration"));
+						let seconds = handle_opt!(duration.parse().ok().or(time::parse_duration(&duration).map(|v| v.as_secs())), "That isn't a valid duration, like 30s or 1m");
+						if seconds > levels::MAX_COOLDOWN {
+							return Err(error!("The cooldown can't be longer than an hour"));
+						}
+						settings.cooldown = seconds;
+						format!("Members' messages will now give XP once every {}", time::format_duration(Duration::from_secs(seconds)))
+					},
+					"announce" => {
+						settings.announce = match handle_syntax_opt!(options.get_string("where")).as_str() {
+							"off" => Announce::Off,
+							"here" => Announce::Here,
+							"channel" => Announce::Channel(handle_opt!(options.get_channel("channel"), "Choose the channel to announce them in").id),
+							_ => return Err(syntax_error!())
+						};
+						match settings.announce {
+							Announce::Off => "Level-ups won't be announced anymore".to_string(),
+							Announce::Here => "Level-ups will now be announced where the member talked".to_string(),
+							Announce::Channel(channel_id) => format!("Level-ups will now be announced in <#{}>", channel_id)
+						}
+					},
+					"message" => {
+						settings.message = options.get_string("text").map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+						if settings.message.as_ref().is_some_and(|v| v.chars().count() > 1500) {
+							return Err(error!("The text must be up to 1500 characters long"));
+						}
+						match settings.message {
+							Some(..) => "Changed the text of level-up announcements".to_string(),
+							None => "Level-up announcements are back to the default text".to_string()
+						}
+					},
+					"reward" => {
+						let level = handle_syntax_opt!(options.get_int("level")).max(1) as u64;
+						settings.rewards.retain(|(v, _)| *v != level);
+						match options.get_role("role") {
+							Some(role) => {
+								check_role(&params, role).await?;
+								settings.rewards.push((level, role.id));
+								settings.rewards.sort();
+								format!("Members will now get <@&{}> at level {}", role.id, level)
+							},
+							None => format!("Members won't get a role at level {} anymore", level)
+						}
+					},
+					"multiplier" => {
+						let value = handle_syntax_opt!(options.get_number("value")).clamp(0.0, levels::MAX_MULTIPLIER);
+						let (target, previous) = match (options.get_channel("channel"), options.get_role("role")) {
+							(Some(channel), None) => (format!("<#{}>", channel.id), match value == 1.0 {
+								true => settings.channel_multipliers.remove(&channel.id),
+								false => settings.channel_multipliers.insert(channel.id, value)
+							}),
+							(None, Some(role)) => (format!("<@&{}>", role.id), match value == 1.0 {
+								true => settings.role_multipliers.remove(&role.id),
+								false => settings.role_multipliers.insert(role.id, value)
+							}),
+							_ => return Err(error!("Choose either a channel or a role"))
+						};
+						match (value == 1.0, previous) {
+							(true, None) => return Err(error!(format!("{} has no multiplier", target))),
+							(true, Some(..)) => format!("{} doesn't have a multiplier anymore", target),
+							(false, _) => format!("XP is now multiplied by {} for {}", value, target)
+						}
+					},
+					"ignore" => {
+						let channel = handle_syntax_opt!(options.get_channel("channel"));
+						let ignored = handle_syntax_opt!(options.get_bool("ignored"));
+						settings.ignored_channels.retain(|v| *v != channel.id);
+						if ignored {
+							settings.ignored_channels.push(channel.id);
+						}
+						match ignored {
+							true => format!("Messages in <#{}> won't give XP anymore", channel.id),
+							false => format!("Messages in <#{}> will now give XP", channel.id)
+						}
+					},
+					"setxp" => {
+						let (user, _) = handle_syntax_opt!(options.get_user("user"));
+						if user.bot {
+							return Err(error!("Bots don't get XP"));
+						}
+						let xp = handle_syntax_opt!(options.get_int("xp")).max(0) as u64;
+						let old = {
+							let mut db = params.db.write().unwrap();
+							let old = levels::xp(&db, guild_id, user.id);
+							handle!(levels::set_xp(&mut db, guild_id, user.id, xp));
+							old
+						};
+						let (old, new) = (levels::level(old), levels::level(xp));
+						levels::give_rewards(&params.ctx, guild_id, user.id, &settings.rewards_between(old, new)).await;
+						return Ok(CommRes::Text(format!("**{}** has {} XP now, so they're level {}", user.tag(), xp, new)));
+					},
+					_ => return Err(syntax_error!())
+				};
+				handle!(levels::set_settings(&mut params.db.write().unwrap(), guild_id, &settings));
+				Ok(CommRes::Text(text))
+			}),
+			..Command::new()
+		},
+	]
+}
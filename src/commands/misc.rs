@@ -12,6 +12,7 @@ pub fn commands<'a>() -> Vec<Command<'a>> {
 			func: |_params: CommandParams| func!({
 				Ok(CommRes::Text("pong".to_string()))
 			}),
+			rerun_on_edit: true,
 			..Command::new()
 		},
 		// pong
@@ -25,7 +26,7 @@ pub fn commands<'a>() -> Vec<Command<'a>> {
 					let db = &mut params.db.write().unwrap();
 					let count = match db.get(&["pings"]) {
 						Value::Number(num) => handle_opt!(num.as_u64()),
-						Value::Null | _ => 0
+						_ => 0
 					} + 1;
 					db.set(&["pings"], count.into()).unwrap();
 					count
@@ -38,50 +39,66 @@ pub fn commands<'a>() -> Vec<Command<'a>> {
 		Command {
 			names: svec!["help"],
 			desc: "Find out what commands I have".to_string(),
-			options: Vec::new(),
+			options: vec![|option| {
+				option.name("command").kind(CommandOptionType::String).required(false)
+					.description("Command you want to know more about")
+					.set_autocomplete(true)
+			}],
 			cat: category.clone(),
 			func: |params: CommandParams| func!({
-				let mut row = CreateActionRow::default();
-				for category in CHLOE.config["categories"].as_array().unwrap().iter() {
-					let category = match category {
-						Value::String(s) => s.clone(),
-						_ => String::new()
-					};
-					let mut button = CreateButton::default();
-					button.custom_id(category.clone())
-						.label(category)
-						.style(ButtonStyle::Secondary);
-					row.add_button(button);
+				if let Some(name) = params.options.get_string("command") {
+					let name = name.strip_prefix(params.prefix.as_str()).unwrap_or(name.as_str());
+					let command = handle_opt!(CHLOE.command(name), format!("I don't have a command called \"{}\"", name));
+					return Ok(CommRes::Msg(make_message(|m| {
+						m.add_embed(|e| command_embed(e, command, params.prefix.as_str()))
+					})));
 				}
-				let mut components = CreateComponents::default();
-				components.add_action_row(row);
+				let categories = CHLOE.categories();
+				let mut category = String::new();
+				let mut page = 0;
+				let mut components = help_components(&categories, &category, page, 0);
 				let mut m = handle!(params.follow_up(|m| {
 					m.content("Choose a category")
 					.set_components(components.clone())
 				}).await);
-				let mut mci = m.await_component_interactions(&params.ctx).timeout(Duration::from_secs(10)).build();
-				while let Some(mci) = mci.next().await {
+				let mut mci = m.await_component_interactions(&params.ctx).timeout(Duration::from_secs(60)).build();
+				while let Some(Some(mci)) = CHLOE.shutdown.unless_requested(mci.next()).await {
+					match mci.data.custom_id.as_str() {
+						"help_prev" => page = page.saturating_sub(1),
+						"help_next" => page += 1,
+						id if id.starts_with("help_menu_") => {
+							category = mci.data.values.first().cloned().unwrap_or_default();
+							page = 0;
+						},
+						id => {
+							category = id.strip_prefix("help_category_").unwrap_or(id).to_string();
+							page = 0;
+						}
+					}
+					let commands: Vec<&Command> = CHLOE.commands.iter().filter(|c| c.cat == category).collect();
+					let pages = commands.chunks(HELP_PAGE_SIZE).count().max(1);
+					page = page.min(pages - 1);
+					components = help_components(&categories, &category, page, pages);
 					handle!(mci.create_interaction_response(&params.ctx, |r| {
-						r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(
-							|d| {
+						r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| {
+							d.content("")
+							.embed(|e| {
 								let mut fields: Vec<(String, &str, bool)> = Vec::new();
-								for command in CHLOE.commands.iter() {
-									if command.cat == mci.data.custom_id {
-										fields.push((
-											format!("{}{} {}", params.prefix, command.names[0], command.args),
-											if command.desc.is_empty() { "." } else { command.desc.as_str() },
-											false
-										));
-									}
+								for command in commands.iter().skip(page * HELP_PAGE_SIZE).take(HELP_PAGE_SIZE) {
+									fields.push((
+										command.syntax(params.prefix.as_str()),
+										if command.desc.is_empty() { "." } else { command.desc.as_str() },
+										false
+									));
 								}
-								d.embed(|e| {
-									e.title(format!("{} commands", mci.data.custom_id))
-									.description("<> = Required field\n[] = Optional field")
-									.fields(fields)
-									.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
-								})
-							}
-						)
+								e.title(format!("{} commands", category))
+								.description(format!("<> = Required field\n[] = Optional field\nUse `{}help <command>` to learn more about a command", params.prefix))
+								.fields(fields)
+								.footer(|f| f.text(format!("Page {}/{}", page + 1, pages)))
+								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+							})
+							.set_components(components.clone())
+						})
 					})
 					.await);
 				}
@@ -91,6 +108,16 @@ pub fn commands<'a>() -> Vec<Command<'a>> {
 				}).await);
 				Ok(CommRes::None)
 			}),
+			autocomplete: Some(|params: CommandParams| func!({
+				let typed = match params.options.focused().and_then(|o| o.value.clone()) {
+					Some(Value::String(v)) => v.to_lowercase(),
+					_ => String::new()
+				};
+				CHLOE.commands.iter()
+					.map(|c| c.names[0].clone())
+					.filter(|name| name.contains(typed.as_str()))
+					.collect()
+			})),
 			..Command::new()
 		},
 		// invite
@@ -106,6 +133,65 @@ pub fn commands<'a>() -> Vec<Command<'a>> {
 					_ => Err(error!(, "The entry \"invite\" in the config must be a string"))
 				}
 			}),
+			rerun_on_edit: true,
+			..Command::new()
+		},
+		// stats
+		Command {
+			names: svec!["stats", "statistics"],
+			desc: "See how I'm doing and which commands are used the most".to_string(),
+			options: vec![|option| {
+				option.name("server").kind(CommandOptionType::Boolean).required(false)
+					.description("Only count commands used in this server (for server managers)")
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let color = CHLOE.config["embed_color"].as_i64().unwrap() as i32;
+				if params.options.get_bool("server").unwrap_or(false) {
+					let (guild_id, member) = match (params.guild_id, &params.member) {
+						(Some(guild_id), Some(member)) => (guild_id, member),
+						_ => return Err(error!("Server stats can only be seen in servers"))
+					};
+					let perms = handle!(member_permissions(&params.ctx, guild_id, member).await);
+					if !perms.manage_guild() {
+						return Err(error!("You need the Manage Server permission to see this server's stats"));
+					}
+					let usage = stats::command_usage(&params.db.read().unwrap(), Some(guild_id.to_string().as_str()));
+					let total = stats::total(&usage);
+					return Ok(CommRes::Msg(make_message(|m| {
+						m.add_embed(|e| {
+							e.title("Server stats")
+							.field("Commands run", total.runs, true)
+							.field("Success rate", format!("{:.1}%", total.success_rate()), true)
+							.field("Average response time", format!("{} ms", total.avg_ms()), true)
+							.field("Most used commands", most_used(&usage), false)
+							.color(color)
+						})
+					})));
+				}
+				let (usage, today, week, db_size) = {
+					let db = params.db.read().unwrap();
+					(stats::command_usage(&db, None), stats::recent_usage(&db, 1), stats::recent_usage(&db, 7), db.size())
+				};
+				let total = stats::total(&usage);
+				let shards = CHLOE.shards().await;
+				Ok(CommRes::Msg(make_message(|m| {
+					m.add_embed(|e| {
+						e.title("Stats")
+						.field("Uptime", time::format_duration(CHLOE.uptime()), true)
+						.field("Servers", CHLOE.guild_count(), true)
+						.field("Database size", stats::format_bytes(db_size), true)
+						.field("Commands run", total.runs, true)
+						.field("Today", today.runs, true)
+						.field("Last 7 days", week.runs, true)
+						.field("Success rate", format!("{:.1}%", total.success_rate()), true)
+						.field("Average response time", format!("{} ms", total.avg_ms()), true)
+						.field("Most used commands", most_used(&usage), false)
+						.field(format!("Shards ({} in total)", CHLOE.shard_count()), shard_list(&shards, params.ctx.shard_id), false)
+						.color(color)
+					})
+				})))
+			}),
 			..Command::new()
 		},
 		// say
@@ -121,6 +207,7 @@ pub fn commands<'a>() -> Vec<Command<'a>> {
 				let text = handle_syntax_opt!(params.options.get_string("text"));
 				Ok(CommRes::Text(text))
 			}),
+			rerun_on_edit: true,
 			..Command::new()
 		},
 		// error
@@ -136,6 +223,28 @@ pub fn commands<'a>() -> Vec<Command<'a>> {
 				let text = handle_syntax_opt!(params.options.get_string("text"));
 				Err(error!(text))
 			}),
+			rerun_on_edit: true,
+			..Command::new()
+		},
+		// unknowncommands
+		Command {
+			names: svec!["unknowncommands"],
+			desc: "Choose whether I reply to commands that don't exist in this server".to_string(),
+			options: vec![|option| {
+				option.name("reply").kind(CommandOptionType::Boolean).required(true)
+					.description("Whether I should reply to unknown commands")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_GUILD,
+			func: |params: CommandParams| func!({
+				let reply = handle_syntax_opt!(params.options.get_bool("reply"));
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers").to_string();
+				handle!(params.db.write().unwrap().set(&["guilds", guild_id.as_str(), "settings", "unknown_commands"], reply.into()));
+				Ok(CommRes::Text(match reply {
+					true => "I will now reply to unknown commands".to_string(),
+					false => "I will now ignore unknown commands".to_string()
+				}))
+			}),
 			..Command::new()
 		},
 		// love
@@ -151,7 +260,161 @@ pub fn commands<'a>() -> Vec<Command<'a>> {
 				let who = handle_syntax_opt!(params.options.get_user("who"));
 				Ok(CommRes::Text(format!("{} loves {} :two_hearts:", params.author.name, who.0.name)))
 			}),
+			rerun_on_edit: true,
 			..Command::new()
 		},
 	]
-}
\ No newline at end of file
+}
+const HELP_PAGE_SIZE: usize = 10;
+
+/// Buttons to choose a category (or select menus, when there are too many
+/// categories to fit in a row), plus buttons to move between pages
+fn help_components(categories: &[&str], current: &str, page: usize, pages: usize) -> CreateComponents {
+	let mut components = CreateComponents::default();
+	match categories.len() {
+		// Discord rejects empty rows
+		0 => (),
+		1..=5 => {
+			let mut row = CreateActionRow::default();
+			for category in categories.iter() {
+				let mut button = CreateButton::default();
+				button.custom_id(format!("help_category_{}", category))
+					.label(category)
+					.style(if *category == current { ButtonStyle::Primary } else { ButtonStyle::Secondary });
+				row.add_button(button);
+			}
+			components.add_action_row(row);
+		},
+		_ => {
+			// Menus have up to 25 options, so there's one for each 25
+			// categories, leaving the last of the 5 rows for the page buttons
+			let menus = categories.len().div_ceil(25).min(4);
+			for (i, chunk) in categories.chunks(25).take(menus).enumerate() {
+				let mut menu = CreateSelectMenu::default();
+				menu.custom_id(format!("help_menu_{}", i))
+					.placeholder(match menus {
+						1 => "Choose a category".to_string(),
+						_ => format!("Choose a category ({}/{})", i + 1, menus)
+					})
+					.options(|o| {
+						for category in chunk.iter() {
+							o.create_option(|o| {
+								o.label(category).value(category).default_selection(*category == current)
+							});
+						}
+						o
+					});
+				components.create_action_row(|r| r.add_select_menu(menu));
+			}
+		}
+	}
+	if pages > 1 {
+		components.create_action_row(|r| {
+			r.create_button(|b| {
+				b.custom_id("help_prev").label("Previous").style(ButtonStyle::Secondary).disabled(page == 0)
+			})
+			.create_button(|b| {
+				b.custom_id("help_next").label("Next").style(ButtonStyle::Secondary).disabled(page + 1 >= pages)
+			})
+		});
+	}
+	components
+}
+
+fn option_kind_name(kind: u64) -> &'static str {
+	match kind {
+		1 => "subcommand",
+		2 => "subcommand group",
+		3 => "text",
+		4 => "integer",
+		5 => "yes/no",
+		6 => "user",
+		7 => "channel",
+		8 => "role",
+		9 => "user or role",
+		10 => "number",
+		11 => "attachment",
+		_ => "unknown"
+	}
+}
+
+/// Detailed description of a command, for `help <command>`
+fn command_embed<'a>(e: &'a mut CreateEmbed, command: &Command, prefix: &str) -> &'a mut CreateEmbed {
+	let mut fields: Vec<(String, String, bool)> = vec![("Syntax".to_string(), format!("`{}`", command.syntax(prefix)), false)];
+	if command.names.len() > 1 {
+		let aliases: Vec<String> = command.names[1..].iter().map(|name| format!("`{}{}`", prefix, name)).collect();
+		fields.push(("Aliases".to_string(), aliases.join(", "), false));
+	}
+	let options = command.option_data();
+	if !options.is_empty() {
+		let mut lines = Vec::new();
+		for option in options.iter() {
+			let kind = option["type"].as_u64().unwrap_or(0);
+			let name = option["name"].as_str().unwrap_or_default();
+			let desc = option["description"].as_str().unwrap_or_default();
+			if kind == 1 || kind == 2 {
+				let sub_options = match &option["options"] {
+					Value::Array(v) => v.as_slice(),
+					_ => &[]
+				};
+				let syntax = format!("{} {}", name, args_string(sub_options));
+				lines.push(format!("`{}` - {}", syntax.trim_end(), desc));
+			}
+			else {
+				let required = if option["required"].as_bool().unwrap_or(false) { "required" } else { "optional" };
+				lines.push(format!("`{}` ({}, {}) - {}", name, option_kind_name(kind), required, desc));
+			}
+		}
+		fields.push(("Options".to_string(), lines.join("\n"), false));
+	}
+	if !command.cat.is_empty() {
+		fields.push(("Category".to_string(), command.cat.clone(), true));
+	}
+	if command.owner_only {
+		fields.push(("Permissions".to_string(), "Owner only".to_string(), true));
+	}
+	else if !command.perms.is_empty() {
+		fields.push(("Permissions".to_string(), command.perms.get_permission_names().join(", "), true));
+	}
+	if !command.cooldown.is_zero() {
+		fields.push(("Cooldown".to_string(), format!("{} seconds", command.cooldown.as_secs()), true));
+	}
+	e.title(format!("{}{}", prefix, command.names[0]))
+		.description(if command.desc.is_empty() { "." } else { command.desc.as_str() })
+		.fields(fields)
+		.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+}
+
+/// The 10 most used commands, one per line
+fn most_used(usage: &[(String, stats::Usage)]) -> String {
+	if usage.is_empty() {
+		return "None yet".to_string();
+	}
+	let lines: Vec<String> = usage.iter().take(10).map(|(name, usage)| {
+		format!("`{}` - {} runs, {:.1}% successful, {} ms on average", name, usage.runs, usage.success_rate(), usage.avg_ms())
+	}).collect();
+	lines.join("\n")
+}
+
+/// The status of the shards this process runs, one per line. `current` is the
+/// shard the command came through
+fn shard_list(shards: &[shards::ShardStatus], current: u64) -> String {
+	if shards.is_empty() {
+		return "None running".to_string();
+	}
+	let mut lines: Vec<String> = shards.iter().take(20).map(|shard| {
+		let mut line = format!("`{}` - {}", shard.id, shard.stage);
+		if let Some(latency) = shard.latency {
+			line += &format!(", {} ms", latency.as_millis());
+		}
+		line += &format!(", {} servers", CHLOE.shard_guild_count(shard.id));
+		if shard.id == current {
+			line += " (this one)";
+		}
+		line
+	}).collect();
+	if shards.len() > lines.len() {
+		lines.push(format!("...and {} more", shards.len() - lines.len()));
+	}
+	lines.join("\n")
+}
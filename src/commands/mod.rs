@@ -4,11 +4,12 @@ use serde_json::Value;
 use std::string::String;
 use std::time::Duration;
 use futures::StreamExt;
-use serenity::builder::{CreateComponents, CreateActionRow, CreateButton};
+use serenity::builder::{CreateComponents, CreateActionRow, CreateButton, CreateSelectMenu, CreateEmbed};
 use serenity::model::application::interaction::InteractionResponseType;
-use serenity::builder::CreateApplicationCommandOption;
 use serenity::model::application::command::CommandOptionType;
 use serenity::model::application::component::ButtonStyle;
+use serenity::model::Permissions;
+use serenity::model::guild::{Member, PartialGuild, Role};
 
 macro_rules! func {
 	($a:block) => {
@@ -85,26 +86,206 @@ macro_rules! svec {
 	};
 }
 
+mod economy;
+mod levels;
 mod misc;
+mod moderation;
+mod owner;
+mod polls;
+mod reminders;
+mod rolepanels;
+mod tags;
+mod welcome;
 
 pub fn commands<'a>() -> Vec<Command<'a>> {
 	let mut commands: Vec<Command> = Vec::new();
 	commands.append(&mut misc::commands());
+	commands.append(&mut moderation::commands());
+	commands.append(&mut reminders::commands());
+	commands.append(&mut welcome::commands());
+	commands.append(&mut rolepanels::commands());
+	commands.append(&mut tags::commands());
+	commands.append(&mut levels::commands());
+	commands.append(&mut economy::commands());
+	commands.append(&mut polls::commands());
+	commands.append(&mut owner::commands());
 	for command in commands.iter_mut() {
-		let mut args = Vec::new();
-		if !command.options.is_empty() {
-			for option_fn in command.options.iter() {
-				let mut option = CreateApplicationCommandOption::default();
-				option_fn(&mut option);
-				let option_name = option.0.get("name").unwrap().as_str().unwrap();
-				let option_string = match option.0.get("required").unwrap().as_bool().unwrap() {
-					true => format!("<{}>", option_name),
-					false => format!("[{}]", option_name)
-				};
-				args.push(option_string);
-			}
-		}
-		command.args = args.join(" ");
+		command.args = args_string(&command.option_data());
 	}
 	commands
 }
+
+/// Handlers of the scheduled jobs that commands create, by kind
+pub fn jobs() -> Vec<(&'static str, scheduler::JobFn)> {
+	vec![
+		(reminders::JOB, reminders::send_reminder),
+		(infractions::UNBAN_JOB, moderation::lift_ban),
+		(antiraid::END_JOB, moderation::end_raid),
+		(crate::core::polls::END_JOB, polls::end_poll)
+	]
+}
+
+/// Describes the arguments of a list of options, like "<text> [user]", or
+/// "<add|remove>" for subcommands
+pub fn args_string(options: &[Value]) -> String {
+	if options.iter().any(|o| matches!(o["type"].as_u64(), Some(1) | Some(2))) {
+		let names: Vec<&str> = options.iter().filter_map(|o| o["name"].as_str()).collect();
+		return format!("<{}>", names.join("|"));
+	}
+	let mut args = Vec::new();
+	for option in options.iter() {
+		let option_name = option["name"].as_str().unwrap();
+		let option_string = match option["required"].as_bool().unwrap_or(false) {
+			true => format!("<{}>", option_name),
+			false => format!("[{}]", option_name)
+		};
+		args.push(option_string);
+	}
+	args.join(" ")
+}
+
+/// Reads the optional "duration" and "reason" options. Prefix commands can't
+/// tell a left out duration apart from the first word of the reason, so when
+/// the duration isn't valid there it becomes part of the reason instead
+pub fn duration_and_reason(params: &CommandParams, options: &CommOptions) -> Result<(Option<Duration>, Option<String>), CommErr> {
+	let reason = options.get_string("reason");
+	match options.get_string("duration") {
+		Some(duration) => match time::parse_duration(&duration) {
+			Some(v) => Ok((Some(v), reason)),
+			None if params.inter.is_some() => Err(error!(format!("\"{}\" is not a valid duration. Try something like 30m, 12h or 7d", duration))),
+			None => Ok((None, Some(match reason {
+				Some(reason) => format!("{} {}", duration, reason),
+				None => duration
+			})))
+		},
+		None => Ok((None, reason))
+	}
+}
+
+/// Asks the author to confirm a destructive action with buttons. Returns
+/// whether they did within 30 seconds
+pub async fn confirm(params: &CommandParams, prompt: &str) -> Result<bool, CommErr> {
+	let mut components = CreateComponents::default();
+	components.create_action_row(|r| {
+		r.create_button(|b| b.custom_id("confirm").label("Confirm").style(ButtonStyle::Danger))
+		.create_button(|b| b.custom_id("cancel").label("Cancel").style(ButtonStyle::Secondary))
+	});
+	let mut m = handle!(params.follow_up(|m| {
+		m.content(prompt)
+		.set_components(components.clone())
+	}).await);
+	let mci = CHLOE.shutdown.unless_requested(m.await_component_interaction(&params.ctx)
+		.author_id(params.author.id)
+		.timeout(Duration::from_secs(30))).await.flatten();
+	let confirmed = matches!(&mci, Some(mci) if mci.data.custom_id == "confirm");
+	let content = match (&mci, confirmed) {
+		(None, _) if CHLOE.shutdown.is_requested() => "Cancelled, since I'm shutting down",
+		(None, _) => "Cancelled, since it wasn't confirmed in time",
+		(Some(..), false) => "Cancelled",
+		(Some(..), true) => prompt
+	};
+	disable_all_components(&mut components);
+	match mci {
+		Some(mci) => handle!(mci.create_interaction_response(&params.ctx, |r| {
+			r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| {
+				d.content(content).set_components(components)
+			})
+		}).await),
+		None => handle!(m.edit(&params.ctx.http, |m| m.content(content).set_components(components)).await)
+	}
+	Ok(confirmed)
+}
+
+/// Shows lines in an embed a page at a time, with buttons to go through the
+/// pages for 60 seconds. `page` is the one it starts at, counting from 0
+pub async fn paginate<'a>(params: &CommandParams, title: &str, lines: &[String], page_size: usize, page: usize) -> Result<CommRes<'a>, CommErr> {
+	let pages = lines.chunks(page_size).count().max(1);
+	let mut page = page.min(pages - 1);
+	let embed = |page: usize| {
+		let mut e = CreateEmbed::default();
+		e.title(title)
+			.description(lines.iter().skip(page * page_size).take(page_size).cloned().collect::<Vec<String>>().join("\n"))
+			.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32);
+		if pages > 1 {
+			e.footer(|f| f.text(format!("Page {}/{}", page + 1, pages)));
+		}
+		e
+	};
+	if pages == 1 {
+		return Ok(CommRes::Msg(make_message(|m| m.set_embed(embed(page)))));
+	}
+	let mut components = page_components(page, pages);
+	let mut m = handle!(params.follow_up(|m| {
+		m.add_embed(embed(page))
+		.set_components(components.clone())
+	}).await);
+	let mut mci = m.await_component_interactions(&params.ctx).timeout(Duration::from_secs(60)).build();
+	while let Some(Some(mci)) = CHLOE.shutdown.unless_requested(mci.next()).await {
+		match mci.data.custom_id.as_str() {
+			"page_prev" => page = page.saturating_sub(1),
+			_ => page = (page + 1).min(pages - 1)
+		}
+		components = page_components(page, pages);
+		handle!(mci.create_interaction_response(&params.ctx, |r| {
+			r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| {
+				d.set_embed(embed(page))
+				.set_components(components.clone())
+			})
+		}).await);
+	}
+	handle!(m.edit(&params.ctx.http, |m| {
+		disable_all_components(&mut components);
+		m.set_components(components)
+	}).await);
+	Ok(CommRes::None)
+}
+
+fn page_components(page: usize, pages: usize) -> CreateComponents {
+	let mut components = CreateComponents::default();
+	components.create_action_row(|r| {
+		r.create_button(|b| {
+			b.custom_id("page_prev").label("Previous").style(ButtonStyle::Secondary).disabled(page == 0)
+		})
+		.create_button(|b| {
+			b.custom_id("page_next").label("Next").style(ButtonStyle::Secondary).disabled(page + 1 >= pages)
+		})
+	});
+	components
+}
+
+/// Reason of a moderation action for the audit log, which says who it was
+/// done by since the audit log only shows me
+pub fn audit_reason(params: &CommandParams, reason: Option<&str>) -> String {
+	format!("{} ({}): {}", params.author.tag(), params.author.id, reason.unwrap_or("No reason given"))
+		.chars().take(512).collect()
+}
+
+/// Position of the member's highest role. Members without roles are at 0,
+/// like @everyone
+pub fn top_role(guild: &PartialGuild, member: &Member) -> i64 {
+	member.roles.iter()
+		.filter_map(|id| guild.roles.get(id))
+		.map(|role| role.position)
+		.max()
+		.unwrap_or(0)
+}
+
+/// Makes sure that the role can be given to members by me, and that the
+/// author isn't handing out a role higher than their own. Both of our highest
+/// roles must be above it, unless the author owns the server
+pub async fn check_role(params: &CommandParams, role: &Role) -> Result<(), CommErr> {
+	let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+	let author = handle_opt!(params.member.as_ref(), "This command can only be used in servers");
+	if role.managed || role.id.0 == guild_id.0 {
+		return Err(error!("That role can't be given to members"));
+	}
+	let me = moderation::check_my_permissions(params, guild_id, Permissions::MANAGE_ROLES).await?;
+	let guild = handle!(guild_id.to_partial_guild(&params.ctx.http).await);
+	if author.user.id != guild.owner_id && top_role(&guild, author) <= role.position {
+		return Err(error!("You can only choose roles below your highest role"));
+	}
+	if top_role(&guild, &me) <= role.position {
+		return Err(error!("I can only give roles below my highest role"));
+	}
+	Ok(())
+}
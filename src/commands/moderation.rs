@@ -0,0 +1,1150 @@
+use super::*;
+use std::pin::Pin;
+use std::future::Future;
+use serenity::builder::{CreateApplicationCommandOption, EditChannel};
+use serenity::model::guild::Member;
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use serenity::model::user::User;
+use crate::core::antiraid::{self, JoinAction};
+use crate::core::automod::{self, AutomodAction, Rule, RuleKind};
+use crate::core::infractions::{self, Escalation, EscalationAction, Infraction, InfractionKind};
+use crate::core::modlog::{self, LogEvent};
+use crate::core::scheduler::JobContext;
+
+/// Longest slowmode Discord allows
+const MAX_SLOWMODE: u64 = 6 * 60 * 60;
+/// How many messages purge looks through at most to find the ones to delete
+const MAX_PURGE_SCAN: usize = 500;
+/// How many words and regexes servers can ban with automod
+const MAX_AUTOMOD_WORDS: usize = 200;
+const MAX_AUTOMOD_REGEXES: usize = 20;
+
+pub fn commands<'a>() -> Vec<Command<'a>> {
+	let category = "Moderation".to_string();
+	vec![
+		// kick
+		Command {
+			names: svec!["kick"],
+			desc: "Kick a member out of the server".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(true)
+					.description("Member to kick")
+			}, |option| {
+				option.name("reason").kind(CommandOptionType::String).required(false)
+					.description("Why they're being kicked")
+			}],
+			cat: category.clone(),
+			perms: Permissions::KICK_MEMBERS,
+			func: |params: CommandParams| func!({
+				let (user, _) = handle_syntax_opt!(params.options.get_user("user"));
+				let user = user.clone();
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let member = check_target(&params, user.id, Permissions::KICK_MEMBERS, "kick").await?;
+				handle_opt!(member, "That user isn't in this server");
+				if !confirm(&params, &format!("Kick **{}**?", user.tag())).await? {
+					return Ok(CommRes::None);
+				}
+				let reason = params.options.get_string("reason");
+				handle!(guild_id.kick_with_reason(&params.ctx.http, user.id, &audit_reason(&params, reason.as_deref())).await);
+				handle!(infractions::add(&mut params.db.write().unwrap(), guild_id, &Infraction::new(InfractionKind::Kick, user.id, params.author.id, reason, None)));
+				Ok(CommRes::Text(format!("Kicked **{}**", user.tag())))
+			}),
+			..Command::new()
+		},
+		// ban
+		Command {
+			names: svec!["ban"],
+			desc: "Ban a user from the server, for a while or forever".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(true)
+					.description("User to ban")
+			}, |option| {
+				option.name("duration").kind(CommandOptionType::String).required(false)
+					.description("How long to ban them for, up to a year, like 12h or 7d (forever if left out)")
+			}, |option| {
+				option.name("reason").kind(CommandOptionType::String).required(false)
+					.description("Why they're being banned")
+			}],
+			cat: category.clone(),
+			perms: Permissions::BAN_MEMBERS,
+			func: |params: CommandParams| func!({
+				let (user, _) = handle_syntax_opt!(params.options.get_user("user"));
+				let user = user.clone();
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let (duration, reason) = duration_and_reason(&params, &params.options)?;
+				if duration.is_some_and(|v| v.as_secs() > infractions::MAX_BAN) {
+					return Err(error!("Bans can't be longer than a year. Leave out the duration to ban them forever"));
+				}
+				check_target(&params, user.id, Permissions::BAN_MEMBERS, "ban").await?;
+				let prompt = match duration {
+					Some(duration) => format!("Ban **{}** for {}?", user.tag(), time::format_duration(duration)),
+					None => format!("Ban **{}**?", user.tag())
+				};
+				if !confirm(&params, &prompt).await? {
+					return Ok(CommRes::None);
+				}
+				let duration = duration.map(|v| v.as_secs());
+				handle!(guild_id.ban_with_reason(&params.ctx.http, user.id, 0, &audit_reason(&params, reason.as_deref())).await);
+				let mut db = params.db.write().unwrap();
+				let until = handle!(infractions::schedule_unban(&mut db, guild_id, user.id, duration));
+				handle!(infractions::add(&mut db, guild_id, &Infraction::new(InfractionKind::Ban, user.id, params.author.id, reason, duration)));
+				Ok(CommRes::Text(match until {
+					Some(until) => format!("Banned **{}** until {}", user.tag(), time::discord_timestamp(until, "f")),
+					None => format!("Banned **{}**", user.tag())
+				}))
+			}),
+			..Command::new()
+		},
+		// unban
+		Command {
+			names: svec!["unban"],
+			desc: "Lift a user's ban".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(true)
+					.description("User to unban, or their ID")
+			}, |option| {
+				option.name("reason").kind(CommandOptionType::String).required(false)
+					.description("Why they're being unbanned")
+			}],
+			cat: category.clone(),
+			perms: Permissions::BAN_MEMBERS,
+			func: |params: CommandParams| func!({
+				let (user, _) = handle_syntax_opt!(params.options.get_user("user"));
+				let user = user.clone();
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				check_my_permissions(&params, guild_id, Permissions::BAN_MEMBERS).await?;
+				let reason = params.options.get_string("reason");
+				match params.ctx.http.remove_ban(guild_id.0, user.id.0, Some(&audit_reason(&params, reason.as_deref()))).await {
+					Ok(..) => (),
+					Err(serenity::Error::Http(e)) if e.status_code().map(|v| v.as_u16()) == Some(404) => {
+						return Err(error!(format!("**{}** isn't banned", user.tag())));
+					},
+					Err(e) => return Err(error!(, format!("{e}")))
+				}
+				let mut db = params.db.write().unwrap();
+				handle!(infractions::schedule_unban(&mut db, guild_id, user.id, None));
+				handle!(infractions::add(&mut db, guild_id, &Infraction::new(InfractionKind::Unban, user.id, params.author.id, reason, None)));
+				Ok(CommRes::Text(format!("Unbanned **{}**", user.tag())))
+			}),
+			..Command::new()
+		},
+		// timeout
+		Command {
+			names: svec!["timeout", "mute"],
+			desc: "Stop a member from talking for a while, or let them talk again".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(true)
+					.description("Member to time out")
+			}, |option| {
+				option.name("duration").kind(CommandOptionType::String).required(true)
+					.description("How long to time them out for, up to 28d, or \"off\" to remove their timeout")
+			}, |option| {
+				option.name("reason").kind(CommandOptionType::String).required(false)
+					.description("Why they're being timed out")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MODERATE_MEMBERS,
+			func: |params: CommandParams| func!({
+				let (user, _) = handle_syntax_opt!(params.options.get_user("user"));
+				let user = user.clone();
+				let duration = handle_syntax_opt!(params.options.get_string("duration"));
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let duration = match duration.to_lowercase().as_str() {
+					"off" | "none" | "remove" | "0" => None,
+					_ => {
+						let duration = handle_opt!(time::parse_duration(&duration), format!("\"{}\" is not a valid duration. Try something like 10m, 2h or 7d", duration));
+						if duration.as_secs() > infractions::MAX_TIMEOUT {
+							return Err(error!("Timeouts can't be longer than 28 days"));
+						}
+						Some(duration.as_secs())
+					}
+				};
+				let member = check_target(&params, user.id, Permissions::MODERATE_MEMBERS, "time out").await?;
+				handle_opt!(member, "That user isn't in this server");
+				let reason = params.options.get_string("reason");
+				let until = duration.map(|v| time::now() + v);
+				handle!(infractions::timeout_member(&params.ctx, guild_id, user.id, until, &audit_reason(&params, reason.as_deref())).await);
+				let kind = match duration {
+					Some(..) => InfractionKind::Timeout,
+					None => InfractionKind::Untimeout
+				};
+				handle!(infractions::add(&mut params.db.write().unwrap(), guild_id, &Infraction::new(kind, user.id, params.author.id, reason, duration)));
+				Ok(CommRes::Text(match until {
+					Some(until) => format!("Timed out **{}** until {}", user.tag(), time::discord_timestamp(until, "f")),
+					None => format!("Removed **{}**'s timeout", user.tag())
+				}))
+			}),
+			..Command::new()
+		},
+		// purge
+		Command {
+			names: svec!["purge", "clear"],
+			desc: "Delete the latest messages of the channel, optionally only the ones that match some filters".to_string(),
+			options: vec![|option| {
+				option.name("count").kind(CommandOptionType::Integer).required(true)
+					.description("How many messages to delete")
+					.min_int_value(1)
+					.max_int_value(100)
+			}, |option| {
+				option.name("user").kind(CommandOptionType::User).required(false)
+					.description("Only delete this user's messages")
+			}, |option| {
+				option.name("filter").kind(CommandOptionType::String).required(false)
+					.description("Only delete messages of some kind")
+					.add_string_choice("Sent by bots", "bots")
+					.add_string_choice("With attachments", "attachments")
+			}, |option| {
+				option.name("contains").kind(CommandOptionType::String).required(false)
+					.description("Only delete messages with this text")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_MESSAGES,
+			cooldown: Duration::from_secs(5),
+			func: |params: CommandParams| func!({
+				let count = handle_syntax_opt!(params.options.get_int("count"));
+				if !(1..=100).contains(&count) {
+					return Err(error!("I can only delete between 1 and 100 messages at once"));
+				}
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				check_my_permissions(&params, guild_id, Permissions::MANAGE_MESSAGES | Permissions::READ_MESSAGE_HISTORY).await?;
+				let user_id = params.options.get_user("user").map(|(user, _)| user.id);
+				let mut contains = params.options.get_string("contains");
+				let filter = match params.options.get_string("filter") {
+					Some(filter) if filter == "bots" || filter == "attachments" => Some(filter),
+					// Prefix commands can't tell a left out filter apart from the
+					// first word of the text, so it becomes part of the text
+					Some(filter) if params.inter.is_none() => {
+						contains = Some(match contains {
+							Some(contains) => format!("{} {}", filter, contains),
+							None => filter
+						});
+						None
+					},
+					Some(..) => return Err(syntax_error!()),
+					None => None
+				};
+				let contains = contains.map(|v| v.to_lowercase());
+				// Discord only deletes messages in bulk if they're newer than 2 weeks
+				let oldest = time::now() as i64 - 14 * 24 * 60 * 60 + 60;
+				let interaction_id = params.inter.as_ref().map(|i| i.id);
+				let mut before = params.msg.as_ref().map(|m| m.id);
+				let mut ids = Vec::new();
+				let mut scanned = 0;
+				'scan: while scanned < MAX_PURGE_SCAN {
+					let messages = handle!(params.channel_id.messages(&params.ctx.http, |b| match before {
+						Some(id) => b.before(id).limit(100),
+						None => b.limit(100)
+					}).await);
+					if messages.is_empty() {
+						break;
+					}
+					scanned += messages.len();
+					before = messages.last().map(|m| m.id);
+					for message in messages {
+						if message.timestamp.unix_timestamp() < oldest {
+							break 'scan;
+						}
+						// The response to this slash command
+						if interaction_id.is_some() && message.interaction.as_ref().map(|i| i.id) == interaction_id {
+							continue;
+						}
+						let matches = user_id.is_none_or(|id| message.author.id == id)
+							&& match filter.as_deref() {
+								Some("bots") => message.author.bot,
+								Some("attachments") => !message.attachments.is_empty(),
+								_ => true
+							}
+							&& contains.as_ref().is_none_or(|text| message.content.to_lowercase().contains(text.as_str()));
+						if matches {
+							ids.push(message.id);
+							if ids.len() as i64 >= count {
+								break 'scan;
+							}
+						}
+					}
+				}
+				if ids.is_empty() {
+					return Err(error!("There are no messages from the last 2 weeks that match"));
+				}
+				let prompt = match ids.len() {
+					1 => "Delete 1 message?".to_string(),
+					n => format!("Delete {} messages?", n)
+				};
+				if !confirm(&params, &prompt).await? {
+					return Ok(CommRes::None);
+				}
+				match ids.as_slice() {
+					[id] => handle!(params.channel_id.delete_message(&params.ctx.http, id).await),
+					ids => handle!(params.channel_id.delete_messages(&params.ctx.http, ids).await)
+				}
+				Ok(CommRes::Text(match ids.len() {
+					1 => "Deleted 1 message".to_string(),
+					n => format!("Deleted {} messages", n)
+				}))
+			}),
+			..Command::new()
+		},
+		// slowmode
+		Command {
+			names: svec!["slowmode"],
+			desc: "Make members wait between their messages in a channel".to_string(),
+			options: vec![|option| {
+				option.name("duration").kind(CommandOptionType::String).required(true)
+					.description("How long members have to wait, like 10s or 5m, up to 6h, or \"off\"")
+			}, |option| {
+				option.name("channel").kind(CommandOptionType::Channel).required(false)
+					.description("Channel to change (this one if left out)")
+			}, |option| {
+				option.name("reason").kind(CommandOptionType::String).required(false)
+					.description("Why the slowmode is being changed")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_CHANNELS,
+			func: |params: CommandParams| func!({
+				let duration = handle_syntax_opt!(params.options.get_string("duration"));
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let secs = match duration.to_lowercase().as_str() {
+					"off" | "none" | "0" => 0,
+					_ => handle_opt!(time::parse_duration(&duration), format!("\"{}\" is not a valid duration. Try something like 10s, 5m or 1h", duration)).as_secs()
+				};
+				if secs > MAX_SLOWMODE {
+					return Err(error!("Slowmode can't be longer than 6 hours"));
+				}
+				check_my_permissions(&params, guild_id, Permissions::MANAGE_CHANNELS).await?;
+				let channel_id = params.options.get_channel("channel").map(|c| c.id).unwrap_or(params.channel_id);
+				let mut edit = EditChannel::default();
+				edit.rate_limit_per_user(secs);
+				let reason = audit_reason(&params, params.options.get_string("reason").as_deref());
+				let map = serenity::json::hashmap_to_json_map(edit.0);
+				handle!(params.ctx.http.edit_channel(channel_id.0, &map, Some(&reason)).await);
+				Ok(CommRes::Text(match secs {
+					0 => format!("Turned off slowmode in <#{}>", channel_id),
+					_ => format!("Members now have to wait {} between their messages in <#{}>", time::format_duration(Duration::from_secs(secs)), channel_id)
+				}))
+			}),
+			..Command::new()
+		},
+		// warn
+		Command {
+			names: svec!["warn"],
+			desc: "Warn a member. Enough warnings get them punished automatically".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(true)
+					.description("Member to warn")
+			}, |option| {
+				option.name("reason").kind(CommandOptionType::String).required(true)
+					.description("Why they're being warned")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MODERATE_MEMBERS,
+			func: |params: CommandParams| func!({
+				let (user, _) = handle_syntax_opt!(params.options.get_user("user"));
+				let user = user.clone();
+				let reason = handle_syntax_opt!(params.options.get_string("reason"));
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				if user.bot {
+					return Err(error!("Bots can't be warned"));
+				}
+				check_target(&params, user.id, Permissions::empty(), "warn").await?;
+				if reason.chars().count() > 500 {
+					return Err(error!("The reason can't be longer than 500 characters"));
+				}
+				let (count, escalation) = {
+					let mut db = params.db.write().unwrap();
+					handle!(infractions::add(&mut db, guild_id, &Infraction::new(InfractionKind::Warn, user.id, params.author.id, Some(reason), None)));
+					let count = infractions::warnings(&db, guild_id, user.id);
+					let escalation = infractions::escalations(&CHLOE.config, &db, guild_id).into_iter().find(|v| v.warnings == count as u64);
+					(count, escalation)
+				};
+				let mut reply = match count {
+					1 => format!("Warned **{}**. This is their first warning", user.tag()),
+					n => format!("Warned **{}**. They have {} warnings now", user.tag(), n)
+				};
+				if let Some(escalation) = escalation {
+					reply += &match escalate(&params, guild_id, &user, &escalation).await {
+						Ok(text) => format!(", so they were {}", text),
+						Err(CommErr::Error(e, _)) if !e.is_empty() => format!(", but I couldn't give them {}: {}", escalation.describe(), e),
+						Err(..) => format!(", but I couldn't give them {}", escalation.describe())
+					};
+				}
+				Ok(CommRes::Text(reply))
+			}),
+			..Command::new()
+		},
+		// warnings
+		Command {
+			names: svec!["warnings", "infractions"],
+			desc: "See a member's warnings and the moderation actions taken against them".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(true)
+					.description("Member whose history you want to see")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MODERATE_MEMBERS,
+			func: |params: CommandParams| func!({
+				let (user, _) = handle_syntax_opt!(params.options.get_user("user"));
+				let user = user.clone();
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let list = infractions::list(&params.db.read().unwrap(), guild_id, user.id);
+				if list.is_empty() {
+					return Ok(CommRes::Text(format!("**{}** has a clean history", user.tag())));
+				}
+				let warnings = list.iter().filter(|v| v.kind == InfractionKind::Warn).count();
+				// The latest ones are the most relevant
+				let mut lines: Vec<String> = list.iter().rev().take(15).map(infraction_line).collect();
+				if list.len() > lines.len() {
+					lines.push(format!("...and {} older ones", list.len() - lines.len()));
+				}
+				Ok(CommRes::Msg(make_message(|m| {
+					m.add_embed(|e| {
+						e.title(format!("History of {}", user.tag()))
+						.description(lines.join("\n"))
+						.footer(|f| f.text(format!("{} warnings, {} entries in total", warnings, list.len())))
+						.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+					})
+				})))
+			}),
+			..Command::new()
+		},
+		// delwarn
+		Command {
+			names: svec!["delwarn"],
+			desc: "Delete a warning, or any other entry of a member's history".to_string(),
+			options: vec![|option| {
+				option.name("id").kind(CommandOptionType::String).required(true)
+					.description("ID of the entry, as shown by the warnings command")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MODERATE_MEMBERS,
+			func: |params: CommandParams| func!({
+				let id = handle_syntax_opt!(params.options.get_string("id")).trim().to_uppercase();
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let mut db = params.db.write().unwrap();
+				let infraction = handle_opt!(infractions::get(&db, guild_id, &id), format!("There is no entry with the ID \"{}\" in this server", id));
+				handle!(infractions::remove(&mut db, guild_id, &infraction));
+				Ok(CommRes::Text(format!("Deleted the {} `{}` of <@{}>", infraction.kind.name().to_lowercase(), id, infraction.user_id)))
+			}),
+			..Command::new()
+		},
+		// clearwarns
+		Command {
+			names: svec!["clearwarns"],
+			desc: "Delete all of a member's warnings".to_string(),
+			options: vec![|option| {
+				option.name("user").kind(CommandOptionType::User).required(true)
+					.description("Member whose warnings you want to delete")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MODERATE_MEMBERS,
+			func: |params: CommandParams| func!({
+				let (user, _) = handle_syntax_opt!(params.options.get_user("user"));
+				let user = user.clone();
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let count = infractions::warnings(&params.db.read().unwrap(), guild_id, user.id);
+				if count == 0 {
+					return Err(error!(format!("**{}** doesn't have any warnings", user.tag())));
+				}
+				if !confirm(&params, &format!("Delete the {} warnings of **{}**?", count, user.tag())).await? {
+					return Ok(CommRes::None);
+				}
+				let count = handle!(infractions::clear_warnings(&mut params.db.write().unwrap(), guild_id, user.id));
+				Ok(CommRes::Text(format!("Deleted the {} warnings of **{}**", count, user.tag())))
+			}),
+			..Command::new()
+		},
+		// escalation
+		Command {
+			names: svec!["escalation"],
+			desc: "Choose what happens to members when they reach a number of warnings".to_string(),
+			options: vec![|option| {
+				option.name("list").kind(CommandOptionType::SubCommand)
+					.description("See what happens at each number of warnings")
+			}, |option| {
+				option.name("set").kind(CommandOptionType::SubCommand)
+					.description("Choose what happens at a number of warnings")
+					.create_sub_option(|o| {
+						o.name("warnings").kind(CommandOptionType::Integer).required(true)
+							.description("Number of warnings")
+							.min_int_value(1)
+					})
+					.create_sub_option(|o| {
+						o.name("action").kind(CommandOptionType::String).required(true)
+							.description("What happens")
+							.add_string_choice("Timeout", "timeout")
+							.add_string_choice("Kick", "kick")
+							.add_string_choice("Ban", "ban")
+					})
+					.create_sub_option(|o| {
+						o.name("duration").kind(CommandOptionType::String).required(false)
+							.description("How long the timeout or ban lasts, like 1h or 7d (bans are forever if left out)")
+					})
+			}, |option| {
+				option.name("remove").kind(CommandOptionType::SubCommand)
+					.description("Make nothing happen at a number of warnings")
+					.create_sub_option(|o| {
+						o.name("warnings").kind(CommandOptionType::Integer).required(true)
+							.description("Number of warnings")
+							.min_int_value(1)
+					})
+			}, |option| {
+				option.name("reset").kind(CommandOptionType::SubCommand)
+					.description("Go back to the default escalations")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_GUILD,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let mut db = params.db.write().unwrap();
+				let mut escalations = infractions::escalations(&CHLOE.config, &db, guild_id);
+				let text = match subcommand {
+					"list" => {
+						if escalations.is_empty() {
+							return Ok(CommRes::Text("Nothing happens automatically when members get warnings".to_string()));
+						}
+						let lines: Vec<String> = escalations.iter().map(|v| format!("**{} warnings**: {}", v.warnings, v.describe())).collect();
+						return Ok(CommRes::Msg(make_message(|m| {
+							m.add_embed(|e| {
+								e.title("Warning escalations")
+								.description(lines.join("\n"))
+								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+							})
+						})));
+					},
+					"set" => {
+						let warnings = handle_syntax_opt!(options.get_int("warnings"));
+						let action = handle_syntax_opt!(options.get_string("action"));
+						let escalation = handle_opt!(Escalation::from_value(&serde_json::json!({
+							"warnings": warnings,
+							"action": action,
+							"duration": options.get_string("duration")
+						})), "Timeouts need a valid duration, like 1h or 7d, and the action must be timeout, kick or ban");
+						if matches!(escalation.action, EscalationAction::Timeout(duration) if duration > infractions::MAX_TIMEOUT) {
+							return Err(error!("Timeouts can't be longer than 28 days"));
+						}
+						if matches!(escalation.action, EscalationAction::Ban(Some(duration)) if duration > infractions::MAX_BAN) {
+							return Err(error!("Bans can't be longer than a year. Leave out the duration to ban them forever"));
+						}
+						escalations.retain(|v| v.warnings != escalation.warnings);
+						escalations.push(escalation);
+						escalations.sort_by_key(|v| v.warnings);
+						format!("Members will now get {} when they reach {} warnings", escalation.describe(), escalation.warnings)
+					},
+					"remove" => {
+						let warnings = handle_syntax_opt!(options.get_int("warnings"));
+						let count = escalations.len();
+						escalations.retain(|v| v.warnings as i64 != warnings);
+						if escalations.len() == count {
+							return Err(error!(format!("Nothing happens at {} warnings already", warnings)));
+						}
+						format!("Nothing will happen anymore when members reach {} warnings", warnings)
+					},
+					"reset" => {
+						handle!(infractions::set_escalations(&mut db, guild_id, None));
+						return Ok(CommRes::Text("Went back to the default escalations".to_string()));
+					},
+					_ => return Err(syntax_error!())
+				};
+				handle!(infractions::set_escalations(&mut db, guild_id, Some(&escalations)));
+				Ok(CommRes::Text(text))
+			}),
+			..Command::new()
+		},
+		// modlog
+		Command {
+			names: svec!["modlog"],
+			desc: "Choose where and what I log about what happens in the server".to_string(),
+			options: vec![|option| {
+				option.name("channel").kind(CommandOptionType::SubCommand)
+					.description("Choose the channel I log in, or stop logging")
+					.create_sub_option(|o| {
+						o.name("channel").kind(CommandOptionType::Channel).required(false)
+							.description("Channel to log in (logging stops if left out)")
+					})
+			}, |option| {
+				option.name("toggle").kind(CommandOptionType::SubCommand)
+					.description("Choose whether I log something")
+					.create_sub_option(|o| {
+						o.name("event").kind(CommandOptionType::String).required(true)
+							.description("What to log or not")
+							.add_string_choice("Message edits", "message_edit")
+							.add_string_choice("Message deletions", "message_delete")
+							.add_string_choice("Members joining", "member_join")
+							.add_string_choice("Members leaving", "member_leave")
+							.add_string_choice("Role changes", "role_change")
+							.add_string_choice("Nickname changes", "nickname_change")
+							.add_string_choice("Bans", "ban")
+							.add_string_choice("Unbans", "unban")
+							.add_string_choice("Automod actions", "automod")
+							.add_string_choice("Raid alerts", "raid")
+					})
+					.create_sub_option(|o| {
+						o.name("enabled").kind(CommandOptionType::Boolean).required(true)
+							.description("Whether I log it")
+					})
+			}, |option| {
+				option.name("status").kind(CommandOptionType::SubCommand)
+					.description("See where and what I log")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_GUILD,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				match subcommand {
+					"channel" => {
+						let channel_id = match options.get_channel("channel") {
+							Some(channel) => channel.id,
+							None => {
+								handle!(modlog::set_channel(&mut params.db.write().unwrap(), guild_id, None));
+								return Ok(CommRes::Text("I won't log anything anymore".to_string()));
+							}
+						};
+						let sent = channel_id.send_message(&params.ctx.http, |m| {
+							m.add_embed(|e| {
+								e.title("Moderation log")
+								.description(format!("I will log what happens in the server here. Use `{}modlog toggle` to choose what", params.prefix))
+								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+							})
+						}).await;
+						if sent.is_err() {
+							return Err(error!(format!("I can't send messages in <#{}>", channel_id)));
+						}
+						handle!(modlog::set_channel(&mut params.db.write().unwrap(), guild_id, Some(channel_id)));
+						Ok(CommRes::Text(format!("I will now log in <#{}>", channel_id)))
+					},
+					"toggle" => {
+						let event = handle_syntax_opt!(options.get_string("event").and_then(|v| LogEvent::from_key(&v)));
+						let enabled = handle_syntax_opt!(options.get_bool("enabled"));
+						handle!(modlog::set_enabled(&mut params.db.write().unwrap(), guild_id, event, enabled));
+						Ok(CommRes::Text(match enabled {
+							true => format!("{} will now be logged", event.name()),
+							false => format!("{} won't be logged anymore", event.name())
+						}))
+					},
+					"status" => {
+						let db = params.db.read().unwrap();
+						let channel = match modlog::channel(&db, guild_id) {
+							Some(v) => format!("<#{}>", v),
+							None => "None, so nothing is logged".to_string()
+						};
+						let events: Vec<String> = LogEvent::ALL.iter().map(|event| {
+							let enabled = modlog::is_enabled(&db, guild_id, *event);
+							format!("{} {} (`{}`)", if enabled { "✅" } else { "❌" }, event.name(), event.key())
+						}).collect();
+						Ok(CommRes::Msg(make_message(|m| {
+							m.add_embed(|e| {
+								e.title("Moderation log")
+								.field("Channel", channel, false)
+								.field("Events", events.join("\n"), false)
+								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+							})
+						})))
+					},
+					_ => Err(syntax_error!())
+				}
+			}),
+			..Command::new()
+		},
+		// automod
+		Command {
+			names: svec!["automod"],
+			desc: "Set up rules that act on messages automatically".to_string(),
+			options: vec![|option| {
+				option.name("status").kind(CommandOptionType::SubCommand)
+					.description("See the rules and how they're set up")
+			}, |option| {
+				option.name("enable").kind(CommandOptionType::SubCommand)
+					.description("Turn a rule on, or change what it does")
+					.create_sub_option(|o| rule_choices(o.name("rule").description("Rule to turn on"), false))
+					.create_sub_option(|o| {
+						o.name("action").kind(CommandOptionType::String).required(true)
+							.description("What happens to messages that break it")
+							.add_string_choice("Delete", "delete")
+							.add_string_choice("Delete and warn", "warn")
+							.add_string_choice("Delete and time out", "timeout")
+							.add_string_choice("Only log", "log")
+					})
+					.create_sub_option(|o| {
+						o.name("duration").kind(CommandOptionType::String).required(false)
+							.description("How long timeouts last, like 10m or 1h")
+					})
+			}, |option| {
+				option.name("disable").kind(CommandOptionType::SubCommand)
+					.description("Turn a rule off")
+					.create_sub_option(|o| rule_choices(o.name("rule").description("Rule to turn off"), false))
+			}, |option| {
+				option.name("set").kind(CommandOptionType::SubCommand)
+					.description("Change when a rule is broken")
+					.create_sub_option(|o| {
+						o.name("rule").kind(CommandOptionType::String).required(true)
+							.description("Rule to change")
+							.add_string_choice("Mass mentions", "mentions")
+							.add_string_choice("Repeated messages", "spam")
+							.add_string_choice("Excessive caps", "caps")
+							.add_string_choice("Attachment floods", "attachments")
+					})
+					.create_sub_option(|o| {
+						o.name("setting").kind(CommandOptionType::String).required(true)
+							.description("max for mentions, count or seconds for spam and attachments, percent or min_length for caps")
+					})
+					.create_sub_option(|o| {
+						o.name("value").kind(CommandOptionType::Integer).required(true)
+							.description("New value")
+							.min_int_value(1)
+					})
+			}, |option| {
+				option.name("word").kind(CommandOptionType::SubCommand)
+					.description("Ban a word or phrase, or unban it")
+					.create_sub_option(|o| mode_choices(o.description("Whether to ban or unban it")))
+					.create_sub_option(|o| {
+						o.name("word").kind(CommandOptionType::String).required(true)
+							.description("Word or phrase")
+					})
+			}, |option| {
+				option.name("regex").kind(CommandOptionType::SubCommand)
+					.description("Ban messages that match a regex, or unban it")
+					.create_sub_option(|o| mode_choices(o.description("Whether to ban or unban it")))
+					.create_sub_option(|o| {
+						o.name("regex").kind(CommandOptionType::String).required(true)
+							.description("Regex, which doesn't care about case")
+					})
+			}, |option| {
+				option.name("exemptrole").kind(CommandOptionType::SubCommand)
+					.description("Let members with a role break a rule, or stop letting them")
+					.create_sub_option(|o| rule_choices(o.name("rule").description("Rule, or all of them"), true))
+					.create_sub_option(|o| mode_choices(o.description("Whether to exempt the role or stop exempting it")))
+					.create_sub_option(|o| {
+						o.name("role").kind(CommandOptionType::Role).required(true)
+							.description("Role")
+					})
+			}, |option| {
+				option.name("exemptchannel").kind(CommandOptionType::SubCommand)
+					.description("Let messages in a channel break a rule, or stop letting them")
+					.create_sub_option(|o| rule_choices(o.name("rule").description("Rule, or all of them"), true))
+					.create_sub_option(|o| mode_choices(o.description("Whether to exempt the channel or stop exempting it")))
+					.create_sub_option(|o| {
+						o.name("channel").kind(CommandOptionType::Channel).required(true)
+							.description("Channel")
+					})
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_GUILD,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				if subcommand == "status" {
+					let rules = automod::rules(&params.db.read().unwrap(), guild_id);
+					return Ok(CommRes::Msg(make_message(|m| {
+						m.add_embed(|e| {
+							for rule in &rules {
+								let mut lines = vec![match rule.enabled {
+									true => format!("✅ On, {}", rule.action.describe()),
+									false => "❌ Off".to_string()
+								}];
+								if !rule.settings.is_empty() {
+									let mut settings: Vec<String> = rule.settings.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+									settings.sort();
+									lines.push(format!("Settings: {}", settings.join(", ")));
+								}
+								if rule.kind == RuleKind::Words {
+									lines.push(format!("{} banned words, {} banned regexes", rule.words.len(), rule.regexes.len()));
+								}
+								if !rule.exempt_roles.is_empty() {
+									lines.push(format!("Exempt roles: {}", rule.exempt_roles.iter().map(|v| format!("<@&{}>", v)).collect::<Vec<String>>().join(" ")));
+								}
+								if !rule.exempt_channels.is_empty() {
+									lines.push(format!("Exempt channels: {}", rule.exempt_channels.iter().map(|v| format!("<#{}>", v)).collect::<Vec<String>>().join(" ")));
+								}
+								e.field(format!("{} (`{}`)", rule.kind.name(), rule.kind.key()), lines.join("\n"), false);
+							}
+							e.title("Automod")
+							.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+						})
+					})));
+				}
+				let kinds: Vec<RuleKind> = match subcommand {
+					"word" | "regex" => vec![RuleKind::Words],
+					_ => match options.get_string("rule").as_deref() {
+						Some("all") if subcommand.starts_with("exempt") => RuleKind::ALL.to_vec(),
+						Some(key) => vec![handle_syntax_opt!(RuleKind::from_key(key))],
+						None => return Err(syntax_error!())
+					}
+				};
+				let add = options.get_string("mode").map(|v| v == "add");
+				let mut db = params.db.write().unwrap();
+				let mut rules: Vec<Rule> = kinds.iter().map(|kind| automod::get(&db, guild_id, *kind)).collect();
+				let text = match subcommand {
+					"enable" => {
+						let action = handle_syntax_opt!(options.get_string("action"));
+						let duration = options.get_string("duration").and_then(|v| time::parse_duration(&v)).map(|v| v.as_secs());
+						let action = handle_opt!(AutomodAction::from_name(&action, duration), "Timeouts need a valid duration, like 10m or 1h");
+						if matches!(action, AutomodAction::Timeout(duration) if duration > infractions::MAX_TIMEOUT) {
+							return Err(error!("Timeouts can't be longer than 28 days"));
+						}
+						let rule = &mut rules[0];
+						rule.enabled = true;
+						rule.action = action;
+						format!("{} will now {} messages that break it", rule.kind.name(), action.describe())
+					},
+					"disable" => {
+						rules[0].enabled = false;
+						format!("{} is off now", rules[0].kind.name())
+					},
+					"set" => {
+						let setting = handle_syntax_opt!(options.get_string("setting"));
+						let value = handle_syntax_opt!(options.get_int("value")) as u64;
+						let rule = &mut rules[0];
+						let (_, _, min, max) = handle_opt!(rule.kind.limits().iter().find(|v| v.0 == setting), format!(
+							"{} only has the settings {}", rule.kind.name(),
+							rule.kind.limits().iter().map(|v| format!("`{}`", v.0)).collect::<Vec<String>>().join(", ")
+						));
+						if value < *min || value > *max {
+							return Err(error!(format!("`{}` must be between {} and {}", setting, min, max)));
+						}
+						rule.settings.insert(setting.clone(), value);
+						format!("Set `{}` of {} to {}", setting, rule.kind.name(), value)
+					},
+					"word" | "regex" => {
+						let add = handle_syntax_opt!(add);
+						let rule = &mut rules[0];
+						let (list, max, value) = match subcommand {
+							"word" => (&mut rule.words, MAX_AUTOMOD_WORDS, handle_syntax_opt!(options.get_string("word")).trim().to_lowercase()),
+							_ => (&mut rule.regexes, MAX_AUTOMOD_REGEXES, handle_syntax_opt!(options.get_string("regex")).trim().to_string())
+						};
+						if value.is_empty() || value.chars().count() > 100 {
+							return Err(error!(format!("The {} must be up to 100 characters long", subcommand)));
+						}
+						match add {
+							true => {
+								if list.contains(&value) {
+									return Err(error!(format!("That {} is already banned", subcommand)));
+								}
+								if list.len() >= max {
+									return Err(error!(format!("You can only ban up to {} of them", max)));
+								}
+								if subcommand == "regex" {
+									if let Err(e) = automod::compile(&value) {
+										return Err(error!("That regex isn't valid", e));
+									}
+								}
+								list.push(value);
+								format!("Banned that {}. Turn the words rule on with `{}automod enable words` if it isn't", subcommand, params.prefix)
+							},
+							false => {
+								let count = list.len();
+								list.retain(|v| *v != value);
+								if list.len() == count {
+									return Err(error!(format!("That {} isn't banned", subcommand)));
+								}
+								format!("That {} isn't banned anymore", subcommand)
+							}
+						}
+					},
+					"exemptrole" | "exemptchannel" => {
+						let add = handle_syntax_opt!(add);
+						let (mention, id) = match subcommand {
+							"exemptrole" => {
+								let role = handle_syntax_opt!(options.get_role("role"));
+								(format!("<@&{}>", role.id), role.id.0)
+							},
+							_ => {
+								let channel = handle_syntax_opt!(options.get_channel("channel"));
+								(format!("<#{}>", channel.id), channel.id.0)
+							}
+						};
+						for rule in rules.iter_mut() {
+							let list: Vec<u64> = match subcommand {
+								"exemptrole" => rule.exempt_roles.iter().map(|v| v.0).collect(),
+								_ => rule.exempt_channels.iter().map(|v| v.0).collect()
+							};
+							let mut list: Vec<u64> = list.into_iter().filter(|v| *v != id).collect();
+							if add {
+								list.push(id);
+							}
+							match subcommand {
+								"exemptrole" => rule.exempt_roles = list.into_iter().map(RoleId).collect(),
+								_ => rule.exempt_channels = list.into_iter().map(ChannelId).collect()
+							}
+						}
+						let which = match rules.len() {
+							1 => rules[0].kind.name().to_string(),
+							_ => "every rule".to_string()
+						};
+						match add {
+							true => format!("{} is now exempt from {}", mention, which),
+							false => format!("{} isn't exempt from {} anymore", mention, which)
+						}
+					},
+					_ => return Err(syntax_error!())
+				};
+				for rule in &rules {
+					handle!(automod::set(&mut db, guild_id, rule));
+				}
+				Ok(CommRes::Text(text))
+			}),
+			..Command::new()
+		},
+		// raidmode
+		Command {
+			names: svec!["raidmode"],
+			desc: "Put the server in raid mode, or take it out of it".to_string(),
+			options: vec![|option| {
+				option.name("on").kind(CommandOptionType::SubCommand)
+					.description("Start raid mode until you turn it off")
+					.create_sub_option(|o| {
+						o.name("reason").kind(CommandOptionType::String).required(false)
+							.description("Why")
+					})
+			}, |option| {
+				option.name("off").kind(CommandOptionType::SubCommand)
+					.description("End raid mode")
+			}, |option| {
+				option.name("status").kind(CommandOptionType::SubCommand)
+					.description("See whether the server is in raid mode")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_GUILD,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				match subcommand {
+					"on" => {
+						let reason = format!("Turned on by <@{}>{}", params.author.id, match options.get_string("reason") {
+							Some(reason) => format!(": {}", reason),
+							None => String::new()
+						});
+						let action = antiraid::policy(&CHLOE.config, &params.db.read().unwrap(), guild_id).action;
+						if !handle!(antiraid::start(&CHLOE, &params.ctx, &params.db, guild_id, true, &reason).await) {
+							return Ok(CommRes::Text(format!("The server is already in raid mode. It will stay in it until you use `{}raidmode off`", params.prefix)));
+						}
+						Ok(CommRes::Text(format!("The server is in raid mode now{}. Use `{}raidmode off` to end it", match action {
+							JoinAction::Nothing => "",
+							JoinAction::Timeout => ", so new members get timed out",
+							JoinAction::Kick => ", so new members get kicked"
+						}, params.prefix)))
+					},
+					"off" => {
+						let reason = format!("Turned off by <@{}>", params.author.id);
+						match handle!(antiraid::end(&CHLOE, &params.ctx, &params.db, guild_id, &reason).await) {
+							true => Ok(CommRes::Text("The server isn't in raid mode anymore".to_string())),
+							false => Err(error!("The server isn't in raid mode"))
+						}
+					},
+					"status" => {
+						let raid = antiraid::raid(&params.db.read().unwrap(), guild_id);
+						Ok(CommRes::Text(match raid {
+							Some(raid) => format!("The server has been in raid mode since {}{}: {}", time::discord_timestamp(raid.since, "R"), match raid.manual {
+								true => ", until someone turns it off",
+								false => ", until joins calm down"
+							}, raid.reason),
+							None => "The server isn't in raid mode".to_string()
+						}))
+					},
+					_ => Err(syntax_error!())
+				}
+			}),
+			..Command::new()
+		},
+		// antiraid
+		Command {
+			names: svec!["antiraid"],
+			desc: "Choose how I detect raids and react to them".to_string(),
+			options: vec![|option| {
+				option.name("status").kind(CommandOptionType::SubCommand)
+					.description("See how raids are detected and what happens during them")
+			}, |option| {
+				option.name("set").kind(CommandOptionType::SubCommand)
+					.description("Change a setting")
+					.create_sub_option(|o| {
+						o.name("setting").kind(CommandOptionType::String).required(true)
+							.description("Setting to change")
+							.add_string_choice("Detect raids (on or off)", "enabled")
+							.add_string_choice("Joins that start raid mode", "joins")
+							.add_string_choice("Seconds those joins happen within", "seconds")
+							.add_string_choice("Youngest allowed account (or off)", "account_age")
+							.add_string_choice("What happens to new members (none, timeout or kick)", "action")
+							.add_string_choice("How long their timeouts last", "timeout")
+							.add_string_choice("Raise the verification level (on or off)", "verification")
+							.add_string_choice("How long raid mode lasts after the last join", "duration")
+							.add_string_choice("Role to ping when a raid starts (or off)", "alert_role")
+					})
+					.create_sub_option(|o| {
+						o.name("value").kind(CommandOptionType::String).required(true)
+							.description("New value")
+					})
+			}, |option| {
+				option.name("reset").kind(CommandOptionType::SubCommand)
+					.description("Go back to the default settings")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_GUILD,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				match subcommand {
+					"status" => {
+						let policy = antiraid::policy(&CHLOE.config, &params.db.read().unwrap(), guild_id);
+						let format = |seconds: u64| time::format_duration(Duration::from_secs(seconds));
+						let on_off = |v: bool| if v { "on" } else { "off" };
+						let lines = [
+							format!("**Detect raids** (`enabled`): {}", on_off(policy.enabled)),
+							format!("**Raid mode starts at** (`joins`, `seconds`): {} joins in {} seconds", policy.joins, policy.seconds),
+							format!("**Youngest allowed account** (`account_age`): {}", policy.account_age.map(format).unwrap_or("off".to_string())),
+							format!("**What happens to new members** (`action`): {}", policy.action.name()),
+							format!("**Timeouts last** (`timeout`): {}", format(policy.timeout)),
+							format!("**Raise the verification level** (`verification`): {}", on_off(policy.verification)),
+							format!("**Raid mode lasts after the last join** (`duration`): {}", format(policy.duration)),
+							format!("**Role to ping** (`alert_role`): {}", policy.alert_role.map(|v| format!("<@&{}>", v)).unwrap_or("none".to_string()))
+						];
+						Ok(CommRes::Msg(make_message(|m| {
+							m.add_embed(|e| {
+								e.title("Anti-raid")
+								.description(lines.join("\n"))
+								.footer(|f| f.text("Alerts are posted in the moderation log, if raid alerts are logged"))
+								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+							})
+						})))
+					},
+					"set" => {
+						let setting = handle_syntax_opt!(options.get_string("setting"));
+						let value = handle_syntax_opt!(options.get_string("value"));
+						let value = match antiraid::parse_setting(&setting, &value) {
+							Ok(v) => v,
+							Err(e) => return Err(error!(e))
+						};
+						handle!(antiraid::set_setting(&mut params.db.write().unwrap(), guild_id, &setting, value));
+						Ok(CommRes::Text(format!("Changed `{}`. Use `{}antiraid status` to see every setting", setting, params.prefix)))
+					},
+					"reset" => {
+						handle!(antiraid::reset(&mut params.db.write().unwrap(), guild_id));
+						Ok(CommRes::Text("Went back to the default settings".to_string()))
+					},
+					_ => Err(syntax_error!())
+				}
+			}),
+			..Command::new()
+		},
+	]
+}
+
+/// Adds the automod rules as choices to an option
+fn rule_choices(option: &mut CreateApplicationCommandOption, all: bool) -> &mut CreateApplicationCommandOption {
+	option.kind(CommandOptionType::String).required(true);
+	for kind in RuleKind::ALL {
+		option.add_string_choice(kind.name(), kind.key());
+	}
+	if all {
+		option.add_string_choice("All rules", "all");
+	}
+	option
+}
+
+/// Adds the mode option, for adding something to a list or removing it
+fn mode_choices(option: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+	option.name("mode").kind(CommandOptionType::String).required(true)
+		.add_string_choice("Add", "add")
+		.add_string_choice("Remove", "remove")
+}
+
+/// Lifts a temporary ban once it ends
+pub fn lift_ban(job: JobContext) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> {
+	func!({
+		let id_of = |key: &str| job.payload[key].as_str().and_then(|v| v.parse().ok());
+		let guild_id: u64 = id_of("guild_id").ok_or("The ban has no server")?;
+		let user_id: u64 = id_of("user_id").ok_or("The ban has no user")?;
+		let reason = "Temporary ban ended";
+		match job.ctx.http.remove_ban(guild_id, user_id, Some(reason)).await {
+			Ok(..) => (),
+			// Someone already unbanned them, or I'm not in the server anymore
+			Err(serenity::Error::Http(e)) if matches!(e.status_code().map(|v| v.as_u16()), Some(403 | 404)) => return Ok(()),
+			Err(e) => return Err(format!("{e}"))
+		}
+		let me = job.ctx.http.get_current_user().await.map_err(|e| format!("{e}"))?.id;
+		let infraction = Infraction::new(InfractionKind::Unban, UserId(user_id), me, Some(reason.to_string()), None);
+		infractions::add(&mut job.db.write().unwrap(), GuildId(guild_id), &infraction)
+	})
+}
+
+/// Takes a server out of raid mode once joins calm down
+pub fn end_raid(job: JobContext) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> {
+	func!({
+		let guild_id: u64 = job.payload["guild_id"].as_str().and_then(|v| v.parse().ok()).ok_or("The raid has no server")?;
+		let reason = "Nobody joined for a while";
+		antiraid::end(&CHLOE, &job.ctx, &job.db, GuildId(guild_id), reason).await.map(|_| ())
+	})
+}
+
+/// Gives the member the punishment of the escalation they reached, after
+/// making sure I'm allowed to. Returns what happened to them
+async fn escalate(params: &CommandParams, guild_id: GuildId, user: &User, escalation: &Escalation) -> Result<String, CommErr> {
+	let (perms, action) = match escalation.action {
+		EscalationAction::Timeout(..) => (Permissions::MODERATE_MEMBERS, "time out"),
+		EscalationAction::Kick => (Permissions::KICK_MEMBERS, "kick"),
+		EscalationAction::Ban(..) => (Permissions::BAN_MEMBERS, "ban")
+	};
+	let member = check_target(params, user.id, perms, action).await?;
+	if member.is_none() && !matches!(escalation.action, EscalationAction::Ban(..)) {
+		return Err(error!("That user isn't in this server"));
+	}
+	let audit_reason = audit_reason(params, Some(&format!("Reached {} warnings", escalation.warnings)));
+	Ok(handle!(infractions::escalate(&params.ctx, &params.db, guild_id, user.id, escalation, &audit_reason).await))
+}
+
+/// Describes an infraction for a list, like "`ID` **Warning** <time> by @mod: reason"
+fn infraction_line(infraction: &Infraction) -> String {
+	let mut line = format!("`{}` **{}** {} by <@{}>", infraction.id, infraction.kind.name(), time::discord_timestamp(infraction.created, "R"), infraction.moderator_id);
+	if let Some(duration) = infraction.duration {
+		line += &format!(" for {}", time::format_duration(Duration::from_secs(duration)));
+	}
+	if let Some(reason) = &infraction.reason {
+		line += &format!(": {}", reason.chars().take(100).collect::<String>());
+	}
+	line
+}
+
+/// Makes sure I have the permissions in the server. Returns my member
+pub async fn check_my_permissions(params: &CommandParams, guild_id: GuildId, perms: Permissions) -> Result<Member, CommErr> {
+	let me = handle!(params.ctx.http.get_current_user().await).id;
+	let member = handle!(guild_id.member(&params.ctx, me).await);
+	let my_perms = handle!(member_permissions(&params.ctx, guild_id, &member).await);
+	if !my_perms.contains(perms) {
+		let missing = perms - my_perms;
+		return Err(error!(format!("I need the following permissions to do that: {}", missing.get_permission_names().join(", "))));
+	}
+	Ok(member)
+}
+
+/// Makes sure that both the author and I can `action` the user, which needs
+/// me to have the permissions and both of us to have a higher role than them.
+/// Returns their member, or None if they aren't in the server
+pub async fn check_target(params: &CommandParams, user_id: UserId, perms: Permissions, action: &str) -> Result<Option<Member>, CommErr> {
+	let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+	let author = handle_opt!(params.member.as_ref(), "This command can only be used in servers");
+	if user_id == params.author.id {
+		return Err(error!(format!("You can't {} yourself", action)));
+	}
+	let me = check_my_permissions(params, guild_id, perms).await?;
+	if user_id == me.user.id {
+		return Err(error!(format!("I can't {} myself", action)));
+	}
+	let guild = handle!(guild_id.to_partial_guild(&params.ctx.http).await);
+	if user_id == guild.owner_id {
+		return Err(error!(format!("Nobody can {} the owner of the server", action)));
+	}
+	let member = match guild_id.member(&params.ctx, user_id).await {
+		Ok(v) => v,
+		// Unknown Member, while anything else is a real error
+		Err(serenity::Error::Http(e)) if e.status_code().map(|v| v.as_u16()) == Some(404) => return Ok(None),
+		Err(e) => return Err(error!(, format!("{e}")))
+	};
+	let position = top_role(&guild, &member);
+	if author.user.id != guild.owner_id && top_role(&guild, author) <= position {
+		return Err(error!(format!("You can't {} someone whose highest role is the same as or higher than yours", action)));
+	}
+	// Without permissions to check I'm not the one doing anything to them
+	if !perms.is_empty() && top_role(&guild, &me) <= position {
+		return Err(error!(format!("I can't {} someone whose highest role is the same as or higher than mine", action)));
+	}
+	Ok(Some(member))
+}
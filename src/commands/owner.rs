@@ -0,0 +1,285 @@
+use super::*;
+use crate::core::blocklist::{self, Block, BlockKind};
+use crate::core::errors;
+use crate::core::shutdown::ShutdownKind;
+use crate::core::presence;
+use crate::core::scheduler::{self, Schedule};
+
+pub fn commands<'a>() -> Vec<Command<'a>> {
+	let category = "Owner".to_string();
+	vec![
+		// blocklist
+		Command {
+			names: svec!["blocklist", "block"],
+			desc: "Stop users, servers or channels from using my commands".to_string(),
+			options: vec![|option| {
+				option.name("add").kind(CommandOptionType::SubCommand)
+					.description("Block a user, server or channel")
+					.create_sub_option(|o| {
+						o.name("type").kind(CommandOptionType::String).required(true)
+							.description("What to block")
+							.add_string_choice("User", "user")
+							.add_string_choice("Server", "server")
+							.add_string_choice("Channel", "channel")
+					})
+					.create_sub_option(|o| {
+						o.name("id").kind(CommandOptionType::String).required(true)
+							.description("ID or mention of what to block")
+					})
+					.create_sub_option(|o| {
+						o.name("duration").kind(CommandOptionType::String).required(false)
+							.description("How long to block it for, like 12h or 7d (forever if left out)")
+					})
+					.create_sub_option(|o| {
+						o.name("reason").kind(CommandOptionType::String).required(false)
+							.description("Why it's being blocked")
+					})
+			}, |option| {
+				option.name("remove").kind(CommandOptionType::SubCommand)
+					.description("Unblock a user, server or channel")
+					.create_sub_option(|o| {
+						o.name("type").kind(CommandOptionType::String).required(true)
+							.description("What to unblock")
+							.add_string_choice("User", "user")
+							.add_string_choice("Server", "server")
+							.add_string_choice("Channel", "channel")
+					})
+					.create_sub_option(|o| {
+						o.name("id").kind(CommandOptionType::String).required(true)
+							.description("ID or mention of what to unblock")
+					})
+			}, |option| {
+				option.name("list").kind(CommandOptionType::SubCommand)
+					.description("See everything that is blocked")
+			}],
+			cat: category.clone(),
+			owner_only: true,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				if subcommand == "list" {
+					let blocks = handle!(blocklist::list(&mut params.db.write().unwrap()));
+					if blocks.is_empty() {
+						return Ok(CommRes::Text("Nothing is blocked".to_string()));
+					}
+					let mut lines = Vec::new();
+					for block in blocks.iter().take(30) {
+						let mut line = format!("**{}** `{}`", block.kind.name(), block.id);
+						if let Some(reason) = &block.reason {
+							line += &format!(" - {}", reason);
+						}
+						if let Some(expires) = block.expires {
+							line += &format!(" (ends {})", time::discord_timestamp(expires, "R"));
+						}
+						lines.push(line);
+					}
+					if blocks.len() > lines.len() {
+						lines.push(format!("...and {} more", blocks.len() - lines.len()));
+					}
+					return Ok(CommRes::Msg(make_message(|m| {
+						m.add_embed(|e| {
+							e.title("Blocklist")
+							.description(lines.join("\n"))
+							.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+						})
+					})));
+				}
+				let kind = handle_syntax_opt!(options.get_string("type").and_then(|v| BlockKind::from_name(&v)));
+				let id = handle_syntax_opt!(options.get_string("id"));
+				let sigils: &[&str] = match kind {
+					BlockKind::User => &["@!", "@"],
+					BlockKind::Guild => &[],
+					BlockKind::Channel => &["#"]
+				};
+				let id = handle_opt!(args::parse_mention(&id, sigils), format!("\"{}\" is not a valid {} ID", id, kind.name()));
+				match subcommand {
+					"add" => {
+						let (duration, reason) = duration_and_reason(&params, options)?;
+						let expires = match duration {
+							Some(duration) => Some(handle_opt!(time::now().checked_add(duration.as_secs()), "That duration is too long")),
+							None => None
+						};
+						let block = Block {
+							kind,
+							id,
+							reason,
+							expires
+						};
+						handle!(blocklist::add(&mut params.db.write().unwrap(), &block, params.author.id));
+						Ok(CommRes::Text(match block.expires {
+							Some(expires) => format!("Blocked {} `{}` until {}", kind.name(), id, time::discord_timestamp(expires, "f")),
+							None => format!("Blocked {} `{}`", kind.name(), id)
+						}))
+					},
+					"remove" => {
+						match handle!(blocklist::remove(&mut params.db.write().unwrap(), kind, id)) {
+							true => Ok(CommRes::Text(format!("Unblocked {} `{}`", kind.name(), id))),
+							false => Err(error!(format!("The {} `{}` isn't blocked", kind.name(), id)))
+						}
+					},
+					_ => Err(syntax_error!())
+				}
+			}),
+			..Command::new()
+		},
+		// errorinfo
+		Command {
+			names: svec!["errorinfo"],
+			desc: "See the details of an error".to_string(),
+			options: vec![|option| {
+				option.name("id").kind(CommandOptionType::String).required(true)
+					.description("Reference ID of the error")
+			}],
+			cat: category.clone(),
+			owner_only: true,
+			func: |params: CommandParams| func!({
+				let id = handle_syntax_opt!(params.options.get_string("id"));
+				let report = handle_opt!(errors::get(&params.db.read().unwrap(), id.trim()), format!("There is no error with the ID \"{}\"", id));
+				Ok(CommRes::Msg(make_message(|m| {
+					m.add_embed(|e| {
+						e.title(format!("Error {}", report.id))
+						.fields(report.fields())
+						.color(CHLOE.config["bad_color"].as_i64().unwrap() as i32)
+					})
+				})))
+			}),
+			..Command::new()
+		},
+		// presence
+		Command {
+			names: svec!["presence", "status"],
+			desc: "Change my status".to_string(),
+			options: vec![|option| {
+				option.name("set").kind(CommandOptionType::SubCommand)
+					.description("Show an activity instead of the ones in the config")
+					.create_sub_option(|o| {
+						o.name("type").kind(CommandOptionType::String).required(true)
+							.description("What I'm doing")
+							.add_string_choice("Playing", "playing")
+							.add_string_choice("Watching", "watching")
+							.add_string_choice("Listening to", "listening")
+							.add_string_choice("Competing in", "competing")
+					})
+					.create_sub_option(|o| {
+						o.name("text").kind(CommandOptionType::String).required(true)
+							.description("Text of the activity. It can have {guilds}, {prefix}, {commands} and {shards}")
+					})
+			}, |option| {
+				option.name("reset").kind(CommandOptionType::SubCommand)
+					.description("Go back to the activities in the config")
+			}],
+			cat: category.clone(),
+			owner_only: true,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let text = match subcommand {
+					"set" => {
+						let kind = handle_syntax_opt!(options.get_string("type"));
+						let text = handle_syntax_opt!(options.get_string("text"));
+						if presence::activity(&kind, &text).is_none() {
+							return Err(syntax_error!());
+						}
+						if text.chars().count() > 128 {
+							return Err(error!("The text can't be longer than 128 characters"));
+						}
+						handle!(presence::set(&mut params.db.write().unwrap(), Some((&kind, &text))));
+						"Changed my status"
+					},
+					"reset" => {
+						handle!(presence::set(&mut params.db.write().unwrap(), None));
+						"Went back to the statuses in the config"
+					},
+					_ => return Err(syntax_error!())
+				};
+				let activity = presence::current(&CHLOE, &params.db.read().unwrap());
+				CHLOE.set_activity(activity).await;
+				Ok(CommRes::Text(text.to_string()))
+			}),
+			..Command::new()
+		},
+		// jobs
+		Command {
+			names: svec!["jobs"],
+			desc: "See or cancel scheduled jobs".to_string(),
+			options: vec![|option| {
+				option.name("list").kind(CommandOptionType::SubCommand)
+					.description("See the scheduled jobs")
+					.create_sub_option(|o| {
+						o.name("kind").kind(CommandOptionType::String).required(false)
+							.description("Only show jobs of this kind")
+					})
+			}, |option| {
+				option.name("cancel").kind(CommandOptionType::SubCommand)
+					.description("Cancel a scheduled job")
+					.create_sub_option(|o| {
+						o.name("id").kind(CommandOptionType::String).required(true)
+							.description("ID of the job")
+					})
+			}],
+			cat: category.clone(),
+			owner_only: true,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				match subcommand {
+					"list" => {
+						let kind = options.get_string("kind");
+						let jobs = scheduler::list(&params.db.read().unwrap(), kind.as_deref());
+						if jobs.is_empty() {
+							return Ok(CommRes::Text("There are no scheduled jobs".to_string()));
+						}
+						let mut lines: Vec<String> = jobs.iter().take(20).map(|job| {
+							let schedule = match &job.schedule {
+								Schedule::Once => "once".to_string(),
+								Schedule::Every(secs) => format!("every {}", time::format_duration(Duration::from_secs(*secs))),
+								Schedule::Cron(cron) => format!("`{}`", cron.expr())
+							};
+							format!("`{}` **{}** {} - next {}", job.id, job.kind, schedule, time::discord_timestamp(job.next_run, "R"))
+						}).collect();
+						if jobs.len() > lines.len() {
+							lines.push(format!("...and {} more", jobs.len() - lines.len()));
+						}
+						Ok(CommRes::Msg(make_message(|m| {
+							m.add_embed(|e| {
+								e.title("Scheduled jobs")
+								.description(lines.join("\n"))
+								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+							})
+						})))
+					},
+					"cancel" => {
+						let id = handle_syntax_opt!(options.get_string("id")).to_uppercase();
+						match handle!(scheduler::cancel(&mut params.db.write().unwrap(), &id)) {
+							true => Ok(CommRes::Text(format!("Cancelled the job `{}`", id))),
+							false => Err(error!(format!("There is no job with the ID \"{}\"", id)))
+						}
+					},
+					_ => Err(syntax_error!())
+				}
+			}),
+			..Command::new()
+		},
+		// shutdown
+		Command {
+			names: svec!["shutdown"],
+			desc: "Turn me off once the running commands finish".to_string(),
+			cat: category.clone(),
+			owner_only: true,
+			func: |_params: CommandParams| func!({
+				CHLOE.shutdown.request(ShutdownKind::Shutdown);
+				Ok(CommRes::Text("Shutting down...".to_string()))
+			}),
+			..Command::new()
+		},
+		// restart
+		Command {
+			names: svec!["restart"],
+			desc: "Restart me once the running commands finish".to_string(),
+			cat: category.clone(),
+			owner_only: true,
+			func: |_params: CommandParams| func!({
+				CHLOE.shutdown.request(ShutdownKind::Restart);
+				Ok(CommRes::Text("Restarting...".to_string()))
+			}),
+			..Command::new()
+		},
+	]
+}
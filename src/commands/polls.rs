@@ -0,0 +1,139 @@
+use super::*;
+use std::pin::Pin;
+use std::future::Future;
+use serenity::model::id::GuildId;
+use crate::core::polls::{self, Poll};
+use crate::core::scheduler::{self, JobContext};
+
+/// How long polls last when no duration is given, in seconds
+const DEFAULT_DURATION: u64 = 24 * 60 * 60;
+
+pub fn commands<'a>() -> Vec<Command<'a>> {
+	let category = "Polls".to_string();
+	vec![
+		// poll
+		Command {
+			names: svec!["poll"],
+			desc: "Ask the server a question, with buttons to vote on the answers".to_string(),
+			options: vec![|option| {
+				option.name("question").kind(CommandOptionType::String).required(true)
+					.description("Question")
+			}, |option| {
+				option.name("choices").kind(CommandOptionType::String).required(true)
+					.description(format!("Up to {} answers to choose from, separated by |", polls::MAX_CHOICES))
+			}, |option| {
+				option.name("duration").kind(CommandOptionType::String).required(false)
+					.description("How long voting lasts, like 30m or 2d (1 day if left out)")
+			}, |option| {
+				option.name("anonymous").kind(CommandOptionType::Boolean).required(false)
+					.description("Whether who voted for what is hidden")
+			}, |option| {
+				option.name("multiple").kind(CommandOptionType::Boolean).required(false)
+					.description("Whether members can vote for more than one answer")
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let question = handle_syntax_opt!(params.options.get_string("question")).trim().to_string();
+				if !(1..=256).contains(&question.chars().count()) {
+					return Err(error!("The question must be up to 256 characters long"));
+				}
+				let choices: Vec<String> = handle_syntax_opt!(params.options.get_string("choices")).split('|')
+					.map(|v| v.trim().to_string())
+					.filter(|v| !v.is_empty())
+					.collect();
+				if !(2..=polls::MAX_CHOICES).contains(&choices.len()) {
+					return Err(error!(format!("Polls need from 2 to {} answers, separated by |", polls::MAX_CHOICES)));
+				}
+				if choices.iter().any(|v| v.chars().count() > 100) {
+					return Err(error!("Answers must be up to 100 characters long"));
+				}
+				if choices.iter().enumerate().any(|(i, v)| choices[..i].iter().any(|other| other.eq_ignore_ascii_case(v))) {
+					return Err(error!("Answers can't be repeated"));
+				}
+				let duration = match params.options.get_string("duration") {
+					Some(v) => handle_opt!(time::parse_duration(&v), format!("\"{}\" is not a valid duration. Try something like 30m, 12h or 7d", v)).as_secs(),
+					None => DEFAULT_DURATION
+				};
+				if !(60..=polls::MAX_DURATION).contains(&duration) {
+					return Err(error!("Polls must last from a minute to 30 days"));
+				}
+				let poll = Poll::new(
+					question, choices,
+					params.options.get_bool("anonymous").unwrap_or(false),
+					params.options.get_bool("multiple").unwrap_or(false),
+					params.author.id, time::now() + duration
+				);
+				// It's saved with its end before it's posted, so that votes count
+				// right away and it can't be left without an end
+				let job_id = handle!(polls::create(&mut params.db.write().unwrap(), guild_id, &poll));
+				let (embed, components) = polls::render(&CHLOE, &poll, false);
+				let message = match params.follow_up(|m| m.add_embed(embed).set_components(components)).await {
+					Ok(v) => v,
+					Err(e) => {
+						let mut db = params.db.write().unwrap();
+						polls::remove(&mut db, guild_id, &poll.id).ok();
+						scheduler::cancel(&mut db, &job_id).ok();
+						return Err(error!("I couldn't post the poll", e));
+					}
+				};
+				handle!(polls::set_message(&mut params.db.write().unwrap(), guild_id, &poll.id, (message.channel_id, message.id)));
+				Ok(CommRes::None)
+			}),
+			..Command::new()
+		},
+		// endpoll
+		Command {
+			names: svec!["endpoll"],
+			desc: "End a poll before its time is up".to_string(),
+			options: vec![|option| {
+				option.name("poll").kind(CommandOptionType::String).required(true)
+					.description("ID of the poll, which is in its footer")
+					.set_autocomplete(true)
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let id = handle_syntax_opt!(params.options.get_string("poll")).trim().to_uppercase();
+				let poll = handle_opt!(polls::get(&params.db.read().unwrap(), guild_id, &id), format!("The server has no running poll with the ID `{}`", id));
+				if poll.author_id != params.author.id {
+					let member = handle_opt!(params.member.as_ref(), "I couldn't check your permissions");
+					let perms = handle!(member_permissions(&params.ctx, guild_id, member).await);
+					if !perms.contains(Permissions::MANAGE_MESSAGES) {
+						return Err(error!("Only whoever made the poll, or members with the Manage Messages permission, can end it"));
+					}
+				}
+				let poll = handle!(polls::end(&CHLOE, &params.ctx, &params.db, guild_id, &poll.id).await, "I couldn't end the poll");
+				Ok(CommRes::Text(format!("Ended the poll **{}**", poll.question)))
+			}),
+			autocomplete: Some(|params: CommandParams| func!({
+				let typed = match params.options.focused().and_then(|o| o.value.clone()) {
+					Some(Value::String(v)) => v.to_lowercase(),
+					_ => String::new()
+				};
+				let guild_id = match params.guild_id {
+					Some(v) => v,
+					None => return Vec::new()
+				};
+				polls::list(&params.db.read().unwrap(), guild_id).into_iter()
+					.filter(|poll| poll.id.to_lowercase().contains(typed.as_str()) || poll.question.to_lowercase().contains(typed.as_str()))
+					.map(|poll| poll.id)
+					.collect()
+			})),
+			..Command::new()
+		},
+	]
+}
+
+/// Ends a poll when its time is up
+pub fn end_poll(job: JobContext) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> {
+	func!({
+		let guild_id: u64 = job.payload["guild_id"].as_str().and_then(|v| v.parse().ok()).ok_or("The poll has no server")?;
+		let poll_id = job.payload["poll_id"].as_str().ok_or("The poll has no ID")?.to_string();
+		// It was ended early
+		if polls::get(&job.db.read().unwrap(), GuildId(guild_id), &poll_id).is_none() {
+			return Ok(());
+		}
+		polls::end(&CHLOE, &job.ctx, &job.db, GuildId(guild_id), &poll_id).await.map(|_| ())
+	})
+}
@@ -0,0 +1,523 @@
+use super::*;
+use std::pin::Pin;
+use std::future::Future;
+use serenity::builder::CreateMessage;
+use serenity::model::id::{ChannelId, UserId};
+use crate::core::db::Database;
+use crate::core::scheduler::{self, CatchUp, Cron, Job, JobContext, Schedule};
+use crate::core::timezone::TimeZone;
+
+/// Kind of the scheduled jobs that deliver reminders
+pub const JOB: &str = "reminder";
+/// How many reminders a user can have at once
+const MAX_REMINDERS: usize = 25;
+/// Shortest time between the runs of a recurring reminder, in seconds
+const MIN_INTERVAL: u64 = 60 * 60;
+/// Time of the day that reminders are set for when only the day is given
+const DEFAULT_TIME: i64 = 9 * 60 * 60;
+const WEEKDAYS: [&str; 7] = ["sunday", "monday", "tuesday", "wednesday", "thursday", "friday", "saturday"];
+
+pub fn commands<'a>() -> Vec<Command<'a>> {
+	let category = "Reminders".to_string();
+	vec![
+		// remind
+		Command {
+			names: svec!["remind", "remindme"],
+			desc: "Get reminded of something later, or regularly".to_string(),
+			options: vec![|option| {
+				option.name("when").kind(CommandOptionType::String).required(true)
+					.description("Like \"in 2h30m\", \"tomorrow 9am\", \"friday at 18:30\" or \"every monday 9:00\"")
+			}, |option| {
+				option.name("text").kind(CommandOptionType::String).required(true)
+					.description("What to remind you of")
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let when = handle_syntax_opt!(params.options.get_string("when"));
+				let text = handle_syntax_opt!(params.options.get_string("text"));
+				let zone = timezone(&params.db.read().unwrap(), params.author.id);
+				let now = time::now();
+				let invalid = format!("I don't understand when \"{}\" is. Try something like \"in 2h30m\", \"tomorrow 9am\", \"friday at 18:30\", \"2025-12-31 23:00\" or \"every monday 9:00\"", when);
+				let (when_text, parsed, text) = match params.inter {
+					Some(..) => {
+						let words: Vec<&str> = when.split_whitespace().collect();
+						(when.clone(), handle_opt!(parse_when(&words, now, &zone), invalid), text)
+					},
+					None => {
+						// Prefix commands can't tell where the time ends and the text
+						// begins, so the time is the longest start of the message that is one
+						let full = format!("{} {}", when, text);
+						let words: Vec<&str> = full.split_whitespace().collect();
+						let (parsed, n) = handle_opt!((1..words.len()).rev().find_map(|n| {
+							parse_when(&words[..n], now, &zone).map(|v| (v, n))
+						}), invalid);
+						(words[..n].join(" "), parsed, words[n..].join(" "))
+					}
+				};
+				if text.chars().count() > 1000 {
+					return Err(error!("Reminders can't be longer than 1000 characters"));
+				}
+				if user_reminders(&params.db.read().unwrap(), params.author.id).len() >= MAX_REMINDERS {
+					return Err(error!(format!("You can't have more than {} reminders. Delete some with the reminders command first", MAX_REMINDERS)));
+				}
+				let payload = serde_json::json!({
+					"user_id": params.author.id.to_string(),
+					"channel_id": params.channel_id.to_string(),
+					"guild_id": params.guild_id.map(|v| v.to_string()),
+					"text": text,
+					"when": when_text,
+					"created": now
+				});
+				let job = match parsed {
+					When::Once(timestamp) => {
+						if timestamp <= now {
+							return Err(error!("That time has already passed"));
+						}
+						Job::once(JOB, payload, timestamp)
+					},
+					When::Every(schedule) => {
+						if matches!(schedule, Schedule::Every(secs) if secs < MIN_INTERVAL) {
+							return Err(error!("Recurring reminders can't be more often than once an hour"));
+						}
+						// Only one reminder is sent for all the runs I missed while offline
+						handle!(Job::recurring(JOB, payload, schedule)).catch_up(CatchUp::Once)
+					}
+				};
+				handle!(scheduler::schedule(&mut params.db.write().unwrap(), &job));
+				let mut reply = format!("Alright, I will remind you {} ({})", time::discord_timestamp(job.next_run, "R"), time::discord_timestamp(job.next_run, "f"));
+				if !matches!(job.schedule, Schedule::Once) {
+					reply += &format!(", and then {}", when_text);
+				}
+				reply += &format!(". Reminder ID: `{}`", job.id);
+				Ok(CommRes::Text(reply))
+			}),
+			..Command::new()
+		},
+		// reminders
+		Command {
+			names: svec!["reminders"],
+			desc: "See and manage your reminders".to_string(),
+			options: vec![|option| {
+				option.name("list").kind(CommandOptionType::SubCommand)
+					.description("See your reminders")
+			}, |option| {
+				option.name("delete").kind(CommandOptionType::SubCommand)
+					.description("Delete one of your reminders")
+					.create_sub_option(|o| {
+						o.name("id").kind(CommandOptionType::String).required(true)
+							.description("ID of the reminder")
+					})
+			}, |option| {
+				option.name("timezone").kind(CommandOptionType::SubCommand)
+					.description("See or set the time zone your reminders' times are in")
+					.create_sub_option(|o| {
+						o.name("timezone").kind(CommandOptionType::String).required(false)
+							.description("Your time zone, like Europe/Berlin, or a UTC offset like UTC+2")
+					})
+			}, |option| {
+				option.name("dm").kind(CommandOptionType::SubCommand)
+					.description("Choose whether I send your reminders in DMs instead of where you set them")
+					.create_sub_option(|o| {
+						o.name("enabled").kind(CommandOptionType::Boolean).required(true)
+							.description("Whether I send your reminders in DMs")
+					})
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let user_id = params.author.id.to_string();
+				match subcommand {
+					"list" => {
+						let (reminders, zone) = {
+							let db = params.db.read().unwrap();
+							(user_reminders(&db, params.author.id), timezone(&db, params.author.id))
+						};
+						if reminders.is_empty() {
+							return Ok(CommRes::Text("You don't have any reminders".to_string()));
+						}
+						let lines: Vec<String> = reminders.iter().map(|job| {
+							let text: String = job.payload["text"].as_str().unwrap_or_default().chars().take(100).collect();
+							let mut line = format!("`{}` {} - {}", job.id, time::discord_timestamp(job.next_run, "R"), text);
+							if !matches!(job.schedule, Schedule::Once) {
+								line += &format!(" (*{}*)", job.payload["when"].as_str().unwrap_or_default());
+							}
+							line
+						}).collect();
+						Ok(CommRes::Msg(make_message(|m| {
+							m.add_embed(|e| {
+								e.title("Your reminders")
+								.description(lines.join("\n"))
+								.footer(|f| f.text(format!("Time zone: {}", zone.name())))
+								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+							})
+						})))
+					},
+					"delete" => {
+						let id = handle_syntax_opt!(options.get_string("id")).trim().to_uppercase();
+						let mut db = params.db.write().unwrap();
+						match scheduler::get(&db, &id) {
+							Some(job) if job.kind == JOB && job.payload["user_id"].as_str() == Some(user_id.as_str()) => {
+								handle!(scheduler::cancel(&mut db, &id));
+								Ok(CommRes::Text(format!("Deleted the reminder `{}`", id)))
+							},
+							_ => Err(error!(format!("You don't have a reminder with the ID \"{}\"", id)))
+						}
+					},
+					"timezone" => {
+						let zone = match options.get_string("timezone") {
+							Some(v) => handle_opt!(TimeZone::parse(&v), format!("\"{}\" is not a time zone I know. Try something like Europe/Berlin, America/New_York or UTC+2", v)),
+							None => {
+								let zone = timezone(&params.db.read().unwrap(), params.author.id);
+								return Ok(CommRes::Text(format!("Your time zone is {}", zone.name())));
+							}
+						};
+						handle!(params.db.write().unwrap().set(&["users", user_id.as_str(), "settings", "timezone"], zone.name().into()));
+						let mut reply = format!("Your time zone is now {}. Reminders you already set keep their times", zone.name());
+						if matches!(zone, TimeZone::Fixed(..)) {
+							reply += ". UTC offsets don't follow daylight saving time, so use the name of your time zone if your clocks change";
+						}
+						Ok(CommRes::Text(reply))
+					},
+					"dm" => {
+						let enabled = handle_syntax_opt!(options.get_bool("enabled"));
+						handle!(params.db.write().unwrap().set(&["users", user_id.as_str(), "settings", "reminders_dm"], enabled.into()));
+						Ok(CommRes::Text(match enabled {
+							true => "I will now send your reminders in DMs".to_string(),
+							false => "I will now send your reminders where you set them".to_string()
+						}))
+					},
+					_ => Err(syntax_error!())
+				}
+			}),
+			..Command::new()
+		},
+	]
+}
+
+/// Delivers a reminder, in DMs if the user wants it there or if the channel
+/// it was set in can't be used anymore
+pub fn send_reminder(job: JobContext) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> {
+	func!({
+		let id_of = |key: &str| job.payload[key].as_str().and_then(|v| v.parse().ok());
+		let user_id = UserId(id_of("user_id").ok_or("The reminder has no user")?);
+		let channel_id = ChannelId(id_of("channel_id").ok_or("The reminder has no channel")?);
+		let dm = job.db.read().unwrap()
+			.get(&["users", user_id.to_string().as_str(), "settings", "reminders_dm"])
+			.as_bool().unwrap_or(false);
+		if !dm {
+			match job.send_message(channel_id, reminder_message(&job, user_id)).await {
+				Ok(..) => return Ok(()),
+				Err(e) => tracing::warn!(id = %job.id, error = %e, "Unable to send a reminder in its channel, so it's being sent in DMs")
+			}
+		}
+		match job.send_dm(user_id, reminder_message(&job, user_id)).await {
+			Ok(..) => Ok(()),
+			Err(e) => Err(format!("{e}"))
+		}
+	})
+}
+
+fn reminder_message<'a>(job: &JobContext, user_id: UserId) -> CreateMessage<'a> {
+	let mut description = job.payload["text"].as_str().unwrap_or_default().to_string();
+	if let Some(created) = job.payload["created"].as_u64() {
+		description += &format!("\n\nSet {}", time::discord_timestamp(created, "R"));
+	}
+	if job.scheduled_for + 60 < time::now() {
+		description += &format!("\nThis was due {}, but I was offline", time::discord_timestamp(job.scheduled_for, "R"));
+	}
+	make_message(|m| {
+		m.content(format!("<@{}>", user_id))
+		.allowed_mentions(|a| a.users(vec![user_id]))
+		.add_embed(|e| {
+			e.title("Reminder")
+			.description(description)
+			.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+		})
+	})
+}
+
+/// The user's time zone, which is UTC unless they set one
+fn timezone(db: &Database, user_id: UserId) -> TimeZone {
+	match db.get(&["users", user_id.to_string().as_str(), "settings", "timezone"]) {
+		Value::String(name) => TimeZone::parse(name).unwrap_or(TimeZone::UTC),
+		// It used to be a UTC offset in seconds
+		value => TimeZone::Fixed(value.as_i64().unwrap_or(0))
+	}
+}
+
+/// The user's reminders, soonest first
+fn user_reminders(db: &Database, user_id: UserId) -> Vec<Job> {
+	let user_id = user_id.to_string();
+	scheduler::list(db, Some(JOB)).into_iter()
+		.filter(|job| job.payload["user_id"].as_str() == Some(user_id.as_str()))
+		.collect()
+}
+
+enum When {
+	Once(u64),
+	Every(Schedule)
+}
+
+/// Parses times like "in 2h30m", "tomorrow 9am", "friday at 18:30",
+/// "2025-12-31 23:00" or "every monday 9:00", in a time zone
+fn parse_when(words: &[&str], now: u64, zone: &TimeZone) -> Option<When> {
+	let words: Vec<String> = words.iter().map(|v| v.to_lowercase()).collect();
+	let words: Vec<&str> = words.iter().map(|v| v.as_str()).collect();
+	match words.split_first()? {
+		(&"in", rest) => now.checked_add(parse_relative(rest)?).map(When::Once),
+		(&"every", rest) => parse_every(rest, zone).map(When::Every),
+		_ => match parse_relative(&words) {
+			Some(v) => now.checked_add(v).map(When::Once),
+			None => parse_absolute(&words, now, zone).map(When::Once)
+		}
+	}
+}
+
+/// Parses durations like "2h30m", "an hour" or "2 days 12 hours", in seconds
+fn parse_relative(words: &[&str]) -> Option<u64> {
+	let mut s: String = words.iter().map(|v| match *v {
+		"a" | "an" => "1",
+		v => v
+	}).collect();
+	for (long, short) in [
+		("weeks", "w"), ("week", "w"), ("days", "d"), ("day", "d"),
+		("hours", "h"), ("hour", "h"), ("hrs", "h"), ("hr", "h"),
+		("minutes", "m"), ("minute", "m"), ("mins", "m"), ("min", "m"),
+		("seconds", "s"), ("second", "s"), ("secs", "s"), ("sec", "s")
+	] {
+		s = s.replace(long, short);
+	}
+	time::parse_duration(&s).map(|v| v.as_secs()).filter(|v| *v > 0)
+}
+
+/// Parses times of the day like "9am", "9:30pm", "21:00", "noon" or
+/// "midnight", in seconds since midnight
+fn parse_time(s: &str) -> Option<i64> {
+	match s {
+		"noon" => return Some(12 * 3600),
+		"midnight" => return Some(0),
+		_ => ()
+	}
+	let (s, pm) = match (s.strip_suffix("am"), s.strip_suffix("pm")) {
+		(Some(v), _) => (v, Some(false)),
+		(_, Some(v)) => (v, Some(true)),
+		_ => (s, None)
+	};
+	let (hours, minutes): (u32, u32) = match s.split_once(':') {
+		Some((hours, minutes)) => (hours.parse().ok()?, minutes.parse().ok()?),
+		None => (s.parse().ok()?, 0)
+	};
+	let hours = match pm {
+		Some(pm) if (1..=12).contains(&hours) => hours % 12 + if pm { 12 } else { 0 },
+		None if hours < 24 => hours,
+		_ => return None
+	};
+	match minutes < 60 {
+		true => Some(hours as i64 * 3600 + minutes as i64 * 60),
+		false => None
+	}
+}
+
+/// Splits the time of the day, and the "at" before it, off the end of the words
+fn split_time<'a>(words: &'a [&'a str]) -> (&'a [&'a str], Option<i64>) {
+	let n = words.len();
+	let (day, time) = match words {
+		// "9 am"
+		[.., hours, "am" | "pm"] => match parse_time(&format!("{}{}", hours, words[n - 1])) {
+			Some(time) => (&words[..n - 2], Some(time)),
+			None => (words, None)
+		},
+		// A lone number is more likely to be part of the text than a time
+		[_, .., last] if last.chars().all(|c| c.is_ascii_digit()) => match parse_time(last) {
+			Some(time) => (&words[..n - 1], Some(time)),
+			None => (words, None)
+		},
+		[.., last] if !last.chars().all(|c| c.is_ascii_digit()) => match parse_time(last) {
+			Some(time) => (&words[..n - 1], Some(time)),
+			None => (words, None)
+		},
+		_ => (words, None)
+	};
+	match time {
+		Some(..) => (day.strip_suffix(&["at"]).unwrap_or(day), time),
+		None => (day, time)
+	}
+}
+
+/// Parses day names like "monday" or "mon", from 0 (Sunday) to 6 (Saturday)
+fn parse_weekday(s: &str) -> Option<i64> {
+	WEEKDAYS.iter().position(|v| s == *v || s == &v[..3] || s.strip_suffix('s') == Some(v)).map(|v| v as i64)
+}
+
+/// Parses dates like "2025-12-31", in days since 1970-01-01
+fn parse_date(s: &str) -> Option<i64> {
+	let parts: Vec<&str> = s.split('-').collect();
+	let (year, month, day): (i64, u32, u32) = match parts.as_slice() {
+		[year, month, day] => (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?),
+		_ => return None
+	};
+	if !(1970..=9999).contains(&year) {
+		return None;
+	}
+	let days = time::days_from_civil(year, month, day);
+	// Dates like February 31st end up on another day
+	match days >= 0 && time::civil(days as u64 * 86400) == (year, month, day) {
+		true => Some(days),
+		false => None
+	}
+}
+
+/// Parses times like "tomorrow 9am", "friday at 18:30" or "2025-12-31 23:00",
+/// in a time zone. Times without a day are the next time it's that time
+fn parse_absolute(words: &[&str], now: u64, zone: &TimeZone) -> Option<u64> {
+	let (day, time) = split_time(words);
+	let day: Vec<&str> = day.iter().copied().filter(|v| *v != "on").collect();
+	let local_now = zone.to_local(now);
+	let today = local_now.div_euclid(86400);
+	let local = match day.as_slice() {
+		[] => {
+			let t = today * 86400 + time?;
+			if t <= local_now { t + 86400 } else { t }
+		},
+		["today"] => today * 86400 + time.unwrap_or(DEFAULT_TIME),
+		["tomorrow"] => (today + 1) * 86400 + time.unwrap_or(DEFAULT_TIME),
+		[word] => match parse_weekday(word) {
+			Some(weekday) => {
+				// 1970-01-01 was a Thursday
+				let days = (weekday - (today + 4)).rem_euclid(7);
+				let t = (today + days) * 86400 + time.unwrap_or(DEFAULT_TIME);
+				if t <= local_now { t + 7 * 86400 } else { t }
+			},
+			None => parse_date(word)? * 86400 + time.unwrap_or(DEFAULT_TIME)
+		},
+		_ => return None
+	};
+	zone.to_utc(local)
+}
+
+/// Parses schedules like "2 hours", "day at 9:00", "weekday 8am" or
+/// "monday and friday 18:00", in a time zone
+fn parse_every(words: &[&str], zone: &TimeZone) -> Option<Schedule> {
+	// "every hour" is "every 1 hour"
+	let interval = parse_relative(words).or_else(|| parse_relative(&[&["1"], words].concat()));
+	if let Some(secs) = interval {
+		return Some(Schedule::Every(secs));
+	}
+	let (day, time) = split_time(words);
+	let weekdays = match day {
+		[] if time.is_some() => "*".to_string(),
+		["day"] => "*".to_string(),
+		["weekday" | "weekdays"] => "1-5".to_string(),
+		["weekend" | "weekends"] => "0,6".to_string(),
+		_ => {
+			let days: Option<Vec<String>> = day.join(",").split(',')
+				.filter(|v| !v.is_empty() && *v != "and")
+				.map(|v| parse_weekday(v).map(|v| v.to_string()))
+				.collect();
+			match days {
+				Some(days) if !days.is_empty() => days.join(","),
+				_ => return None
+			}
+		}
+	};
+	let time = time.unwrap_or(DEFAULT_TIME);
+	let cron = Cron::parse(&format!("{} {} * * {}", time % 3600 / 60, time / 3600, weekdays)).ok()?;
+	Some(Schedule::Cron(cron.in_zone(zone.clone())))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::timezone::Zone;
+	use std::sync::Arc;
+
+	/// UNIX timestamp of a UTC date and time
+	fn at(year: i64, month: u32, day: u32, hour: u64, minute: u64) -> u64 {
+		time::days_from_civil(year, month, day) as u64 * 86400 + hour * 3600 + minute * 60
+	}
+
+	/// When the tests pretend it is. 2024-01-03 was a Wednesday
+	fn now() -> u64 {
+		at(2024, 1, 3, 12, 0)
+	}
+
+	fn once(when: &str, zone: &TimeZone) -> Option<u64> {
+		let words: Vec<&str> = when.split_whitespace().collect();
+		match parse_when(&words, now(), zone)? {
+			When::Once(timestamp) => Some(timestamp),
+			When::Every(..) => panic!("\"{when}\" is recurring")
+		}
+	}
+
+	#[test]
+	fn parse_when_reads_times() {
+		assert_eq!(once("in 2h30m", &TimeZone::UTC), Some(now() + 2 * 3600 + 30 * 60));
+		assert_eq!(once("an hour", &TimeZone::UTC), Some(now() + 3600));
+		assert_eq!(once("tomorrow 9am", &TimeZone::UTC), Some(at(2024, 1, 4, 9, 0)));
+		assert_eq!(once("friday at 18:30", &TimeZone::UTC), Some(at(2024, 1, 5, 18, 30)));
+		assert_eq!(once("on wednesday", &TimeZone::UTC), Some(at(2024, 1, 10, 9, 0)));
+		assert_eq!(once("13:00", &TimeZone::UTC), Some(at(2024, 1, 3, 13, 0)));
+		assert_eq!(once("11:00", &TimeZone::UTC), Some(at(2024, 1, 4, 11, 0)));
+		assert_eq!(once("2025-12-31 23:00", &TimeZone::UTC), Some(at(2025, 12, 31, 23, 0)));
+	}
+
+	#[test]
+	fn parse_when_reads_times_in_time_zones() {
+		assert_eq!(once("friday at 18:30", &TimeZone::Fixed(2 * 3600)), Some(at(2024, 1, 5, 16, 30)));
+		assert_eq!(once("friday at 18:30", &TimeZone::Fixed(-5 * 3600)), Some(at(2024, 1, 5, 23, 30)));
+		// It's already Thursday in UTC+14
+		assert_eq!(once("tomorrow", &TimeZone::Fixed(14 * 3600)), Some(at(2024, 1, 4, 19, 0)));
+		// Berlin is in daylight saving time in July
+		let berlin = TimeZone::Zone(Arc::new(Zone::from_rule("Europe/Berlin", "CET-1CEST,M3.5.0,M10.5.0/3").unwrap()));
+		assert_eq!(once("friday at 18:30", &berlin), Some(at(2024, 1, 5, 17, 30)));
+		assert_eq!(once("2024-07-01 18:30", &berlin), Some(at(2024, 7, 1, 16, 30)));
+	}
+
+	#[test]
+	fn parse_when_reads_schedules() {
+		let words = ["every", "monday", "9:00"];
+		let cron = match parse_when(&words, now(), &TimeZone::Fixed(3600)) {
+			Some(When::Every(Schedule::Cron(cron))) => cron,
+			_ => panic!("\"every monday 9:00\" isn't a cron schedule")
+		};
+		assert_eq!(cron.expr(), "0 9 * * 1");
+		assert_eq!(cron.zone().name(), "UTC+1");
+		assert_eq!(cron.next_after(now()), Some(at(2024, 1, 8, 8, 0)));
+		let words = ["every", "2", "hours"];
+		assert!(matches!(parse_when(&words, now(), &TimeZone::UTC), Some(When::Every(Schedule::Every(7200)))));
+		let words = ["every", "weekday", "at", "8am"];
+		assert!(matches!(parse_when(&words, now(), &TimeZone::UTC), Some(When::Every(Schedule::Cron(cron))) if cron.expr() == "0 8 * * 1-5"));
+	}
+
+	#[test]
+	fn parse_when_rejects_invalid_times() {
+		for when in ["", "2025-02-31", "2025-02-31 9:00", "someday", "in", "friday at 25:00", "every", "every someday"] {
+			let words: Vec<&str> = when.split_whitespace().collect();
+			assert!(parse_when(&words, now(), &TimeZone::UTC).is_none(), "{when}");
+		}
+		// Times that don't fit in a timestamp
+		assert!(parse_when(&["in", "1w"], u64::MAX - 10, &TimeZone::UTC).is_none());
+		assert!(parse_when(&["18446744073709551615s"], 10, &TimeZone::UTC).is_none());
+	}
+
+	#[test]
+	fn split_time_splits_off_the_time() {
+		assert_eq!(split_time(&["friday", "at", "18:30"]), (&["friday"][..], Some(18 * 3600 + 30 * 60)));
+		assert_eq!(split_time(&["tomorrow", "9", "am"]), (&["tomorrow"][..], Some(9 * 3600)));
+		assert_eq!(split_time(&["tomorrow", "9pm"]), (&["tomorrow"][..], Some(21 * 3600)));
+		assert_eq!(split_time(&["noon"]), (&[][..], Some(12 * 3600)));
+		// A lone number isn't a time
+		assert_eq!(split_time(&["5"]), (&["5"][..], None));
+		assert_eq!(split_time(&["friday", "at"]), (&["friday", "at"][..], None));
+		assert_eq!(split_time(&["friday", "13pm"]), (&["friday", "13pm"][..], None));
+	}
+
+	#[test]
+	fn parse_date_rejects_dates_that_dont_exist() {
+		assert_eq!(parse_date("2025-12-31"), Some(time::days_from_civil(2025, 12, 31)));
+		assert_eq!(parse_date("2024-02-29"), Some(time::days_from_civil(2024, 2, 29)));
+		for date in ["2025-02-31", "2025-02-29", "2025-13-01", "2025-00-10", "2025-01-00", "1969-12-31", "99999999999-01-01", "2025-01", "2025/01/01"] {
+			assert_eq!(parse_date(date), None, "{date}");
+		}
+	}
+}
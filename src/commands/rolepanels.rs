@@ -0,0 +1,246 @@
+use super::*;
+use crate::core::rolepanels::{self, Panel, PanelMode, PanelRole, PanelStyle};
+
+pub fn commands<'a>() -> Vec<Command<'a>> {
+	let category = "Roles".to_string();
+	vec![
+		// rolepanel
+		Command {
+			names: svec!["rolepanel", "reactionroles"],
+			desc: "Make panels that members pick their own roles with".to_string(),
+			options: vec![|option| {
+				option.name("create").kind(CommandOptionType::SubCommand)
+					.description("Make a new panel, which you can add roles to before posting it")
+					.create_sub_option(|o| {
+						o.name("style").kind(CommandOptionType::String).required(true)
+							.description("How roles are picked")
+							.add_string_choice("Buttons", "buttons")
+							.add_string_choice("Select menu", "select")
+							.add_string_choice("Reactions", "reactions")
+					})
+					.create_sub_option(|o| {
+						o.name("mode").kind(CommandOptionType::String).required(true)
+							.description("Which of the roles members can have")
+							.add_string_choice("Only one", "unique")
+							.add_string_choice("Any of them", "multiple")
+							.add_string_choice("Any of them, without taking them away", "verify")
+					})
+					.create_sub_option(|o| {
+						o.name("title").kind(CommandOptionType::String).required(true)
+							.description("Title of the panel")
+					})
+			}, |option| {
+				option.name("description").kind(CommandOptionType::SubCommand)
+					.description("Change the text below the panel's title")
+					.create_sub_option(|o| {
+						o.name("panel").kind(CommandOptionType::String).required(true)
+							.description("ID of the panel")
+							.set_autocomplete(true)
+					})
+					.create_sub_option(|o| {
+						o.name("text").kind(CommandOptionType::String).required(false)
+							.description("Text (it's removed if left out)")
+					})
+			}, |option| {
+				option.name("add").kind(CommandOptionType::SubCommand)
+					.description("Add a role to a panel")
+					.create_sub_option(|o| {
+						o.name("panel").kind(CommandOptionType::String).required(true)
+							.description("ID of the panel")
+							.set_autocomplete(true)
+					})
+					.create_sub_option(|o| {
+						o.name("role").kind(CommandOptionType::Role).required(true)
+							.description("Role")
+					})
+					.create_sub_option(|o| {
+						o.name("emoji").kind(CommandOptionType::String).required(false)
+							.description("Emoji of the role, which reaction panels need")
+					})
+					.create_sub_option(|o| {
+						o.name("label").kind(CommandOptionType::String).required(false)
+							.description("Text of its button or option (the role's name if left out)")
+					})
+			}, |option| {
+				option.name("remove").kind(CommandOptionType::SubCommand)
+					.description("Remove a role from a panel")
+					.create_sub_option(|o| {
+						o.name("panel").kind(CommandOptionType::String).required(true)
+							.description("ID of the panel")
+							.set_autocomplete(true)
+					})
+					.create_sub_option(|o| {
+						o.name("role").kind(CommandOptionType::Role).required(true)
+							.description("Role")
+					})
+			}, |option| {
+				option.name("post").kind(CommandOptionType::SubCommand)
+					.description("Post a panel in a channel, or update it where it is")
+					.create_sub_option(|o| {
+						o.name("panel").kind(CommandOptionType::String).required(true)
+							.description("ID of the panel")
+							.set_autocomplete(true)
+					})
+					.create_sub_option(|o| {
+						o.name("channel").kind(CommandOptionType::Channel).required(false)
+							.description("Channel (this one if left out, or where it is if it was posted)")
+					})
+			}, |option| {
+				option.name("delete").kind(CommandOptionType::SubCommand)
+					.description("Delete a panel and its message")
+					.create_sub_option(|o| {
+						o.name("panel").kind(CommandOptionType::String).required(true)
+							.description("ID of the panel")
+							.set_autocomplete(true)
+					})
+			}, |option| {
+				option.name("list").kind(CommandOptionType::SubCommand)
+					.description("See the server's panels")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_ROLES,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				let panels = rolepanels::list(&params.db.read().unwrap(), guild_id);
+				match subcommand {
+					"create" => {
+						let style = handle_syntax_opt!(options.get_string("style").and_then(|v| PanelStyle::from_name(&v)));
+						let mode = handle_syntax_opt!(options.get_string("mode").and_then(|v| PanelMode::from_name(&v)));
+						let title = handle_syntax_opt!(options.get_string("title"));
+						if title.chars().count() > 256 {
+							return Err(error!("The title must be up to 256 characters long"));
+						}
+						if panels.len() >= rolepanels::MAX_PANELS {
+							return Err(error!(format!("Servers can only have up to {} panels", rolepanels::MAX_PANELS)));
+						}
+						let panel = Panel::new(style, mode, title);
+						handle!(rolepanels::save(&mut params.db.write().unwrap(), guild_id, &panel));
+						return Ok(CommRes::Text(format!(
+							"Made the panel `{}`. Add roles to it with `{}rolepanel add {} <role>`, then post it with `{}rolepanel post {}`",
+							panel.id, params.prefix, panel.id, params.prefix, panel.id
+						)));
+					},
+					"list" => {
+						if panels.is_empty() {
+							return Ok(CommRes::Text(format!("The server has no panels. Make one with `{}rolepanel create`", params.prefix)));
+						}
+						let lines: Vec<String> = panels.iter().map(|panel| {
+							format!("`{}` **{}**: {} roles, {}, {}{}", panel.id, panel.title, panel.roles.len(), panel.style.name(), panel.mode.name(), match panel.message {
+								Some((channel_id, message_id)) => format!(", [posted]({})", message_id.link(channel_id, Some(guild_id))),
+								None => String::new()
+							})
+						}).collect();
+						return Ok(CommRes::Msg(make_message(|m| {
+							m.add_embed(|e| {
+								e.title("Role panels")
+								.description(lines.join("\n"))
+								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+							})
+						})));
+					},
+					_ => ()
+				}
+				let id = handle_syntax_opt!(options.get_string("panel")).to_uppercase();
+				let mut panel = handle_opt!(panels.into_iter().find(|v| v.id == id), format!("The server has no panel with the ID `{}`", id));
+				let text = match subcommand {
+					"description" => {
+						panel.description = options.get_string("text").filter(|v| !v.trim().is_empty());
+						if panel.description.as_ref().is_some_and(|v| v.chars().count() > 2000) {
+							return Err(error!("The text must be up to 2000 characters long"));
+						}
+						"Changed the panel's text".to_string()
+					},
+					"add" => {
+						let role = handle_syntax_opt!(options.get_role("role"));
+						if panel.roles.iter().any(|v| v.role_id == role.id) {
+							return Err(error!("That role is already on the panel"));
+						}
+						if panel.roles.len() >= rolepanels::MAX_ROLES {
+							return Err(error!(format!("Panels can only have up to {} roles", rolepanels::MAX_ROLES)));
+						}
+						check_role(&params, role).await?;
+						let mut emoji = options.get_string("emoji");
+						let mut label = options.get_string("label");
+						// Without slash commands' separate options, text that isn't
+						// an emoji is the start of the label
+						if params.msg.is_some() && emoji.as_deref().is_some_and(|v| rolepanels::parse_emoji(v).is_none()) {
+							label = Some(format!("{} {}", emoji.take().unwrap_or_default(), label.unwrap_or_default()).trim().to_string());
+						}
+						if emoji.as_deref().is_some_and(|v| rolepanels::parse_emoji(v).is_none()) {
+							return Err(error!("That isn't an emoji"));
+						}
+						if emoji.is_none() && panel.style == PanelStyle::Reactions {
+							return Err(error!("Roles on reaction panels need an emoji"));
+						}
+						let label = label.filter(|v| !v.trim().is_empty()).unwrap_or(role.name.clone());
+						if label.chars().count() > 80 {
+							return Err(error!("The label must be up to 80 characters long"));
+						}
+						panel.roles.push(PanelRole {
+							role_id: role.id,
+							label,
+							emoji: emoji.map(|v| v.trim().to_string())
+						});
+						format!("Added <@&{}> to the panel", role.id)
+					},
+					"remove" => {
+						let role = handle_syntax_opt!(options.get_role("role"));
+						let count = panel.roles.len();
+						panel.roles.retain(|v| v.role_id != role.id);
+						if panel.roles.len() == count {
+							return Err(error!("That role isn't on the panel"));
+						}
+						format!("Removed <@&{}> from the panel", role.id)
+					},
+					"post" => {
+						if panel.roles.is_empty() {
+							return Err(error!(format!("The panel has no roles. Add some with `{}rolepanel add {} <role>`", params.prefix, panel.id)));
+						}
+						let channel_id = match (options.get_channel("channel"), panel.message) {
+							(Some(channel), _) => Some(channel.id),
+							(None, Some(..)) => None,
+							(None, None) => Some(params.channel_id)
+						};
+						let link = handle!(rolepanels::publish(&CHLOE, &params.ctx, guild_id, &mut panel, channel_id).await, "I couldn't post the panel");
+						handle!(rolepanels::save(&mut params.db.write().unwrap(), guild_id, &panel));
+						return Ok(CommRes::Text(format!("Posted the panel: {}", link)));
+					},
+					"delete" => {
+						if let Some((channel_id, message_id)) = panel.message {
+							channel_id.delete_message(&params.ctx.http, message_id).await.ok();
+						}
+						handle!(rolepanels::remove(&mut params.db.write().unwrap(), guild_id, &panel.id));
+						return Ok(CommRes::Text(format!("Deleted the panel `{}`", panel.id)));
+					},
+					_ => return Err(syntax_error!())
+				};
+				// Changes show up on the panel right away once it's posted
+				let text = match panel.message {
+					Some(..) => match rolepanels::publish(&CHLOE, &params.ctx, guild_id, &mut panel, None).await {
+						Ok(..) => text,
+						Err(e) => format!("{}, but I couldn't update its message: {}", text, e)
+					},
+					None => text
+				};
+				handle!(rolepanels::save(&mut params.db.write().unwrap(), guild_id, &panel));
+				Ok(CommRes::Text(text))
+			}),
+			autocomplete: Some(|params: CommandParams| func!({
+				let typed = match params.options.focused().and_then(|o| o.value.clone()) {
+					Some(Value::String(v)) => v.to_lowercase(),
+					_ => String::new()
+				};
+				let guild_id = match params.guild_id {
+					Some(v) => v,
+					None => return Vec::new()
+				};
+				rolepanels::list(&params.db.read().unwrap(), guild_id).into_iter()
+					.filter(|panel| panel.id.to_lowercase().contains(typed.as_str()) || panel.title.to_lowercase().contains(typed.as_str()))
+					.map(|panel| panel.id)
+					.collect()
+			})),
+			..Command::new()
+		},
+	]
+}
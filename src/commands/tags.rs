@@ -0,0 +1,201 @@
+use super::*;
+use crate::core::tags::{self, Tag};
+
+/// How many tags `tag list` shows
+const MAX_LISTED: usize = 100;
+
+pub fn commands<'a>() -> Vec<Command<'a>> {
+	let category = "Tags".to_string();
+	vec![
+		// tag
+		Command {
+			names: svec!["tag", "tags"],
+			desc: "Use the server's own text commands, or make them".to_string(),
+			options: vec![|option| {
+				option.name("show").kind(CommandOptionType::SubCommand)
+					.description("Use a tag")
+					.create_sub_option(|o| {
+						o.name("name").kind(CommandOptionType::String).required(true)
+							.description("Name of the tag")
+							.set_autocomplete(true)
+					})
+					.create_sub_option(|o| {
+						o.name("args").kind(CommandOptionType::String).required(false)
+							.description("Text that goes where the tag has {args}")
+					})
+			}, |option| {
+				option.name("create").kind(CommandOptionType::SubCommand)
+					.description("Make a tag")
+					.create_sub_option(|o| {
+						o.name("name").kind(CommandOptionType::String).required(true)
+							.description("Name of the tag, which is used like a command")
+					})
+					.create_sub_option(|o| {
+						o.name("content").kind(CommandOptionType::String).required(true)
+							.description("Text of the tag. It can have {user}, {args} and {channel}")
+					})
+			}, |option| {
+				option.name("edit").kind(CommandOptionType::SubCommand)
+					.description("Change the text of a tag")
+					.create_sub_option(|o| {
+						o.name("name").kind(CommandOptionType::String).required(true)
+							.description("Name of the tag")
+							.set_autocomplete(true)
+					})
+					.create_sub_option(|o| {
+						o.name("content").kind(CommandOptionType::String).required(true)
+							.description("Text of the tag. It can have {user}, {args} and {channel}")
+					})
+			}, |option| {
+				option.name("embed").kind(CommandOptionType::SubCommand)
+					.description("Choose whether a tag is sent as an embed")
+					.create_sub_option(|o| {
+						o.name("name").kind(CommandOptionType::String).required(true)
+							.description("Name of the tag")
+							.set_autocomplete(true)
+					})
+					.create_sub_option(|o| {
+						o.name("enabled").kind(CommandOptionType::Boolean).required(true)
+							.description("Whether it's an embed")
+					})
+			}, |option| {
+				option.name("delete").kind(CommandOptionType::SubCommand)
+					.description("Delete a tag")
+					.create_sub_option(|o| {
+						o.name("name").kind(CommandOptionType::String).required(true)
+							.description("Name of the tag")
+							.set_autocomplete(true)
+					})
+			}, |option| {
+				option.name("list").kind(CommandOptionType::SubCommand)
+					.description("See the server's tags")
+			}, |option| {
+				option.name("info").kind(CommandOptionType::SubCommand)
+					.description("See who made a tag and how much it's used")
+					.create_sub_option(|o| {
+						o.name("name").kind(CommandOptionType::String).required(true)
+							.description("Name of the tag")
+							.set_autocomplete(true)
+					})
+			}],
+			cat: category.clone(),
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				if subcommand == "list" {
+					let tags = tags::list(&params.db.read().unwrap(), guild_id);
+					if tags.is_empty() {
+						return Ok(CommRes::Text("The server has no tags".to_string()));
+					}
+					let mut names: Vec<String> = tags.iter().take(MAX_LISTED).map(|tag| format!("`{}`", tag.name)).collect();
+					if tags.len() > MAX_LISTED {
+						names.push(format!("and {} more", tags.len() - MAX_LISTED));
+					}
+					return Ok(CommRes::Msg(make_message(|m| {
+						m.add_embed(|e| {
+							e.title(format!("Tags ({})", tags.len()))
+							.description(names.join(", "))
+							.footer(|f| f.text(format!("Use them like commands, like {}{}", params.prefix, tags[0].name)))
+							.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+						})
+					})));
+				}
+				let name = handle_syntax_opt!(options.get_string("name")).to_lowercase();
+				match subcommand {
+					"show" => {
+						let tag = handle_opt!(tags::use_tag(&mut params.db.write().unwrap(), guild_id, &name), format!("The server has no tag called \"{}\"", name));
+						return Ok(CommRes::Msg(tag.message(&CHLOE, params.author.id, params.channel_id, &options.get_string("args").unwrap_or_default())));
+					},
+					"info" => {
+						let tag = handle_opt!(tags::get(&params.db.read().unwrap(), guild_id, &name), format!("The server has no tag called \"{}\"", name));
+						return Ok(CommRes::Msg(make_message(|m| {
+							m.add_embed(|e| {
+								e.title(format!("Tag {}", tag.name))
+								.field("Made by", format!("<@{}>", tag.author_id), true)
+								.field("Made", time::discord_timestamp(tag.created, "R"), true)
+								.field("Uses", tag.uses, true);
+								if let Some(edited) = tag.edited {
+									e.field("Edited", time::discord_timestamp(edited, "R"), true);
+								}
+								e.field("Embed", if tag.embed { "Yes" } else { "No" }, true)
+								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+							})
+						})));
+					},
+					_ => ()
+				}
+				// Everything else changes tags, which only admins can do
+				let member = handle_opt!(params.member.as_ref(), "I couldn't check your permissions");
+				let perms = handle!(member_permissions(&params.ctx, guild_id, member).await);
+				if !perms.contains(Permissions::MANAGE_GUILD) {
+					return Err(error!("You need the Manage Server permission to change tags"));
+				}
+				let content = options.get_string("content").map(|v| v.trim().to_string());
+				if content.as_ref().is_some_and(|v| v.chars().count() > tags::MAX_CONTENT) {
+					return Err(error!(format!("The text must be up to {} characters long", tags::MAX_CONTENT)));
+				}
+				let mut db = params.db.write().unwrap();
+				// Read after checking permissions, which waits, so that uses and
+				// changes made meanwhile aren't lost
+				let tag = tags::get(&db, guild_id, &name);
+				let text = match subcommand {
+					"create" => {
+						if tag.is_some() {
+							return Err(error!(format!("The server already has a tag called \"{}\"", name)));
+						}
+						if !tags::is_valid_name(&name) {
+							return Err(error!("Tag names must be one word of up to 32 letters, numbers, - and _"));
+						}
+						if CHLOE.command(&name).is_some() {
+							return Err(error!(format!("I have a command called \"{}\" already", name)));
+						}
+						if tags::list(&db, guild_id).len() >= tags::MAX_TAGS {
+							return Err(error!(format!("Servers can only have up to {} tags", tags::MAX_TAGS)));
+						}
+						let tag = Tag::new(&name, handle_syntax_opt!(content), params.author.id);
+						handle!(tags::save(&mut db, guild_id, &tag));
+						format!("Made the tag. Use it with `{}{}`", params.prefix, tag.name)
+					},
+					"edit" => {
+						let mut tag = handle_opt!(tag, format!("The server has no tag called \"{}\"", name));
+						tag.content = handle_syntax_opt!(content);
+						tag.edited = Some(time::now());
+						handle!(tags::save(&mut db, guild_id, &tag));
+						"Changed the tag".to_string()
+					},
+					"embed" => {
+						let mut tag = handle_opt!(tag, format!("The server has no tag called \"{}\"", name));
+						tag.embed = handle_syntax_opt!(options.get_bool("enabled"));
+						handle!(tags::save(&mut db, guild_id, &tag));
+						match tag.embed {
+							true => "The tag will now be sent as an embed".to_string(),
+							false => "The tag won't be sent as an embed anymore".to_string()
+						}
+					},
+					"delete" => {
+						handle_opt!(tag, format!("The server has no tag called \"{}\"", name));
+						handle!(tags::remove(&mut db, guild_id, &name));
+						format!("Deleted the tag \"{}\"", name)
+					},
+					_ => return Err(syntax_error!())
+				};
+				Ok(CommRes::Text(text))
+			}),
+			autocomplete: Some(|params: CommandParams| func!({
+				let typed = match params.options.focused().and_then(|o| o.value.clone()) {
+					Some(Value::String(v)) => v.to_lowercase(),
+					_ => String::new()
+				};
+				let guild_id = match params.guild_id {
+					Some(v) => v,
+					None => return Vec::new()
+				};
+				tags::list(&params.db.read().unwrap(), guild_id).into_iter()
+					.map(|tag| tag.name)
+					.filter(|name| name.contains(typed.as_str()))
+					.collect()
+			})),
+			..Command::new()
+		},
+	]
+}
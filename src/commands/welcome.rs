@@ -0,0 +1,220 @@
+use super::*;
+use crate::core::welcome::{self, Greeting};
+
+/// Longest greeting template, which leaves room for the placeholders to grow
+const MAX_TEMPLATE: usize = 1500;
+
+pub fn commands<'a>() -> Vec<Command<'a>> {
+	let category = "Welcome".to_string();
+	vec![
+		// welcome
+		Command {
+			names: svec!["welcome"],
+			desc: "Set up the message that members get when they join".to_string(),
+			options: greeting_options(),
+			cat: category.clone(),
+			perms: Permissions::MANAGE_GUILD,
+			func: |params: CommandParams| func!({
+				greeting(params, Greeting::Welcome).await
+			}),
+			..Command::new()
+		},
+		// goodbye
+		Command {
+			names: svec!["goodbye"],
+			desc: "Set up the message that's sent when members leave".to_string(),
+			options: greeting_options(),
+			cat: category.clone(),
+			perms: Permissions::MANAGE_GUILD,
+			func: |params: CommandParams| func!({
+				greeting(params, Greeting::Goodbye).await
+			}),
+			..Command::new()
+		},
+		// joinroles
+		Command {
+			names: svec!["joinroles", "autoroles"],
+			desc: "Choose roles that members get when they join".to_string(),
+			options: vec![|option| {
+				option.name("add").kind(CommandOptionType::SubCommand)
+					.description("Give a role to members when they join")
+					.create_sub_option(|o| {
+						o.name("role").kind(CommandOptionType::Role).required(true)
+							.description("Role")
+					})
+			}, |option| {
+				option.name("remove").kind(CommandOptionType::SubCommand)
+					.description("Stop giving a role to members when they join")
+					.create_sub_option(|o| {
+						o.name("role").kind(CommandOptionType::Role).required(true)
+							.description("Role")
+					})
+			}, |option| {
+				option.name("list").kind(CommandOptionType::SubCommand)
+					.description("See the roles that members get when they join")
+			}],
+			cat: category.clone(),
+			perms: Permissions::MANAGE_ROLES,
+			func: |params: CommandParams| func!({
+				let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+				let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+				if subcommand == "add" {
+					check_role(&params, handle_syntax_opt!(options.get_role("role"))).await?;
+				}
+				let mut db = params.db.write().unwrap();
+				let mut roles = welcome::join_roles(&db, guild_id);
+				let text = match subcommand {
+					"add" => {
+						let role = handle_syntax_opt!(options.get_role("role"));
+						if roles.contains(&role.id) {
+							return Err(error!("Members already get that role when they join"));
+						}
+						if roles.len() >= welcome::MAX_JOIN_ROLES {
+							return Err(error!(format!("Members can only get up to {} roles when they join", welcome::MAX_JOIN_ROLES)));
+						}
+						roles.push(role.id);
+						format!("Members will now get <@&{}> when they join", role.id)
+					},
+					"remove" => {
+						let role = handle_syntax_opt!(options.get_role("role"));
+						let count = roles.len();
+						roles.retain(|v| *v != role.id);
+						if roles.len() == count {
+							return Err(error!("Members don't get that role when they join"));
+						}
+						format!("Members won't get <@&{}> when they join anymore", role.id)
+					},
+					"list" => return Ok(CommRes::Text(match roles.is_empty() {
+						true => "Members don't get any roles when they join".to_string(),
+						false => format!("Members get these roles when they join: {}", roles.iter().map(|v| format!("<@&{}>", v)).collect::<Vec<String>>().join(" "))
+					})),
+					_ => return Err(syntax_error!())
+				};
+				handle!(welcome::set_join_roles(&mut db, guild_id, &roles));
+				Ok(CommRes::Text(text))
+			}),
+			..Command::new()
+		},
+	]
+}
+
+/// Options of the welcome and goodbye commands, which work the same way
+fn greeting_options() -> Vec<OptionFn> {
+	vec![|option| {
+		option.name("status").kind(CommandOptionType::SubCommand)
+			.description("See how the message is set up")
+	}, |option| {
+		option.name("channel").kind(CommandOptionType::SubCommand)
+			.description("Choose the channel the message is sent in, or stop sending it in one")
+			.create_sub_option(|o| {
+				o.name("channel").kind(CommandOptionType::Channel).required(false)
+					.description("Channel (it stops being sent in one if left out)")
+			})
+	}, |option| {
+		option.name("dm").kind(CommandOptionType::SubCommand)
+			.description("Choose whether the message is also sent to the member by DM")
+			.create_sub_option(|o| {
+				o.name("enabled").kind(CommandOptionType::Boolean).required(true)
+					.description("Whether it's sent by DM")
+			})
+	}, |option| {
+		option.name("message").kind(CommandOptionType::SubCommand)
+			.description("Change the text of the message")
+			.create_sub_option(|o| {
+				o.name("text").kind(CommandOptionType::String).required(false)
+					.description("It can have {user}, {mention}, {server} and {member_count} (the default if left out)")
+			})
+	}, |option| {
+		option.name("embed").kind(CommandOptionType::SubCommand)
+			.description("Choose whether the message is sent as an embed")
+			.create_sub_option(|o| {
+				o.name("enabled").kind(CommandOptionType::Boolean).required(true)
+					.description("Whether it's an embed")
+			})
+			.create_sub_option(|o| {
+				o.name("color").kind(CommandOptionType::String).required(false)
+					.description("Color of the embed, like #E6CB67")
+			})
+	}, |option| {
+		option.name("preview").kind(CommandOptionType::SubCommand)
+			.description("See what the message looks like, as if you were the member")
+	}]
+}
+
+/// Runs the welcome or goodbye command
+async fn greeting<'a>(params: CommandParams, greeting: Greeting) -> Result<CommRes<'a>, CommErr> {
+	let (subcommand, options) = handle_syntax_opt!(params.options.subcommand());
+	let guild_id = handle_opt!(params.guild_id, "This command can only be used in servers");
+	let mut settings = welcome::settings(&params.db.read().unwrap(), guild_id, greeting);
+	let text = match subcommand {
+		"status" => {
+			let mut places = Vec::new();
+			if let Some(channel_id) = settings.channel {
+				places.push(format!("<#{}>", channel_id));
+			}
+			if settings.dm {
+				places.push("DMs".to_string());
+			}
+			return Ok(CommRes::Msg(make_message(|m| {
+				m.add_embed(|e| {
+					e.title(format!("{} message", match greeting {
+						Greeting::Welcome => "Welcome",
+						Greeting::Goodbye => "Goodbye"
+					}))
+					.field("Sent in", if places.is_empty() { "Nowhere, so it's off".to_string() } else { places.join(" and ") }, false)
+					.field("Text", settings.template(greeting), false)
+					.field("Embed", match (settings.embed, settings.color) {
+						(true, Some(color)) => format!("Yes, #{:06X}", color),
+						(true, None) => "Yes".to_string(),
+						(false, _) => "No".to_string()
+					}, false)
+					.footer(|f| f.text(format!("Use {}{} preview to see it", params.prefix, greeting.key())))
+					.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
+				})
+			})));
+		},
+		"channel" => {
+			settings.channel = options.get_channel("channel").map(|v| v.id);
+			match settings.channel {
+				Some(channel_id) => format!("The {} message will now be sent in <#{}>", greeting.key(), channel_id),
+				None => format!("The {} message won't be sent in a channel anymore", greeting.key())
+			}
+		},
+		"dm" => {
+			settings.dm = handle_syntax_opt!(options.get_bool("enabled"));
+			match settings.dm {
+				true => format!("The {} message will now be sent by DM, to members that have their DMs open", greeting.key()),
+				false => format!("The {} message won't be sent by DM anymore", greeting.key())
+			}
+		},
+		"message" => {
+			settings.template = options.get_string("text").map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+			match &settings.template {
+				Some(template) if template.chars().count() > MAX_TEMPLATE => {
+					return Err(error!(format!("The text must be up to {} characters long", MAX_TEMPLATE)));
+				},
+				Some(..) => format!("Changed the {} message. Use `{}{} preview` to see it", greeting.key(), params.prefix, greeting.key()),
+				None => format!("The {} message is back to the default one", greeting.key())
+			}
+		},
+		"embed" => {
+			settings.embed = handle_syntax_opt!(options.get_bool("enabled"));
+			if let Some(color) = options.get_string("color") {
+				let color = color.trim().trim_start_matches('#').trim_start_matches("0x").to_string();
+				settings.color = Some(handle_opt!(u32::from_str_radix(&color, 16).ok().filter(|v| *v <= 0xFFFFFF), "That isn't a valid color, like #E6CB67"));
+			}
+			match settings.embed {
+				true => format!("The {} message will now be sent as an embed", greeting.key()),
+				false => format!("The {} message won't be sent as an embed anymore", greeting.key())
+			}
+		},
+		"preview" => {
+			let guild = handle!(params.ctx.http.get_guild_with_counts(guild_id.0).await);
+			let message = welcome::message(&CHLOE, &settings, greeting, &params.author, &guild);
+			return Ok(CommRes::Msg(message));
+		},
+		_ => return Err(syntax_error!())
+	};
+	handle!(welcome::set_settings(&mut params.db.write().unwrap(), guild_id, greeting, &settings));
+	Ok(CommRes::Text(text))
+}
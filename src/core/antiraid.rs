@@ -0,0 +1,399 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{RwLock, Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde_json::Value;
+use serenity::client::Context;
+use serenity::model::guild::{Member, VerificationLevel};
+use serenity::model::id::{GuildId, RoleId, UserId};
+use super::db::Database;
+use super::infractions::{self, Infraction, InfractionKind};
+use super::scheduler::{self, Job};
+use super::{modlog, time, ChloeManager};
+
+/// Kind of the scheduled jobs that end raid mode once joins calm down
+pub const END_JOB: &str = "raid_end";
+/// Settings of the policy that servers can change, as used by the antiraid command
+pub const SETTINGS: [&str; 9] = ["enabled", "joins", "seconds", "account_age", "action", "timeout", "verification", "duration", "alert_role"];
+
+/// What happens to members that join during a raid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinAction {
+	Nothing,
+	Timeout,
+	Kick
+}
+
+impl JoinAction {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"none" => Some(JoinAction::Nothing),
+			"timeout" => Some(JoinAction::Timeout),
+			"kick" => Some(JoinAction::Kick),
+			_ => None
+		}
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			JoinAction::Nothing => "none",
+			JoinAction::Timeout => "timeout",
+			JoinAction::Kick => "kick"
+		}
+	}
+}
+
+/// How a server detects raids and reacts to them
+pub struct Policy {
+	/// Whether raids are detected automatically. Raid mode can be turned on
+	/// by hand either way
+	pub enabled: bool,
+	/// Raid mode starts when this many members join within `seconds`
+	pub joins: u64,
+	pub seconds: u64,
+	/// Members whose accounts are younger than this many seconds get the
+	/// join action even without a raid
+	pub account_age: Option<u64>,
+	pub action: JoinAction,
+	/// How long the join action's timeouts last, in seconds
+	pub timeout: u64,
+	/// Whether the verification level is raised during raids
+	pub verification: bool,
+	/// Automatic raid mode ends once nobody joined for this many seconds
+	pub duration: u64,
+	/// Role that's pinged when a raid starts
+	pub alert_role: Option<RoleId>
+}
+
+/// Reads a duration that's either a number of seconds or text like "10m"
+fn duration(value: &Value) -> Option<u64> {
+	match value {
+		Value::String(v) => Some(time::parse_duration(v)?.as_secs()),
+		Value::Number(v) => v.as_u64(),
+		_ => None
+	}
+}
+
+/// The server's policy. Servers that haven't changed a setting use the one
+/// in the config's `antiraid`
+pub fn policy(config: &Value, db: &Database, guild_id: GuildId) -> Policy {
+	let guild = db.get(&["guilds", guild_id.to_string().as_str(), "settings", "antiraid"]);
+	let get = |key: &str| match &guild[key] {
+		Value::Null => &config["antiraid"][key],
+		v => v
+	};
+	Policy {
+		enabled: get("enabled").as_bool().unwrap_or(false),
+		joins: get("joins").as_u64().unwrap_or(10).max(2),
+		seconds: duration(get("seconds")).unwrap_or(10).max(1),
+		account_age: duration(get("account_age")).filter(|v| *v > 0),
+		action: get("action").as_str().and_then(JoinAction::from_name).unwrap_or(JoinAction::Timeout),
+		timeout: duration(get("timeout")).unwrap_or(60 * 60).clamp(60, infractions::MAX_TIMEOUT),
+		verification: get("verification").as_bool().unwrap_or(true),
+		duration: duration(get("duration")).unwrap_or(10 * 60).max(60),
+		alert_role: get("alert_role").as_str().and_then(|v| v.parse().ok()).map(RoleId)
+	}
+}
+
+/// Checks a value given to the antiraid command, and turns it into what's
+/// stored for the setting
+pub fn parse_setting(setting: &str, value: &str) -> Result<Value, String> {
+	let value = value.trim().to_lowercase();
+	let seconds = |min: u64, max: u64| -> Result<Value, String> {
+		let seconds = value.parse().ok()
+			.or_else(|| time::parse_duration(&value).map(|v| v.as_secs()))
+			.ok_or("That isn't a valid duration, like 30s or 10m")?;
+		match seconds >= min && seconds <= max {
+			true => Ok(seconds.into()),
+			false => Err(format!("It must be between {} and {}", time::format_duration(Duration::from_secs(min)), time::format_duration(Duration::from_secs(max))))
+		}
+	};
+	match setting {
+		"enabled" | "verification" => match value.as_str() {
+			"on" | "true" | "yes" => Ok(true.into()),
+			"off" | "false" | "no" => Ok(false.into()),
+			_ => Err("It must be on or off".to_string())
+		},
+		"joins" => match value.parse::<u64>() {
+			Ok(v) if (2..=100).contains(&v) => Ok(v.into()),
+			_ => Err("It must be a number between 2 and 100".to_string())
+		},
+		"seconds" => seconds(1, 5 * 60),
+		"account_age" => match value.as_str() {
+			"off" | "0" => Ok(0.into()),
+			_ => seconds(60, 365 * 24 * 60 * 60)
+		},
+		"action" => match JoinAction::from_name(&value) {
+			Some(v) => Ok(v.name().into()),
+			None => Err("It must be none, timeout or kick".to_string())
+		},
+		"timeout" => seconds(60, infractions::MAX_TIMEOUT),
+		"duration" => seconds(60, 24 * 60 * 60),
+		"alert_role" => match value.as_str() {
+			"off" | "none" => Ok(Value::Null),
+			_ => match value.trim_start_matches("<@&").trim_end_matches('>').parse::<u64>() {
+				Ok(v) => Ok(v.to_string().into()),
+				Err(..) => Err("It must be a role mention, a role ID or off".to_string())
+			}
+		},
+		_ => Err(format!("The settings are {}", SETTINGS.iter().map(|v| format!("`{}`", v)).collect::<Vec<String>>().join(", ")))
+	}
+}
+
+/// Changes a setting of the server's policy, as given by `parse_setting`
+pub fn set_setting(db: &mut Database, guild_id: GuildId, setting: &str, value: Value) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "settings", "antiraid", setting], value)
+}
+
+/// Goes back to the config's policy
+pub fn reset(db: &mut Database, guild_id: GuildId) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "settings", "antiraid"], Value::Null)
+}
+
+/// A raid that the server is in
+pub struct Raid {
+	/// UNIX timestamp of when raid mode started
+	pub since: u64,
+	/// Whether a moderator turned raid mode on, in which case it only ends
+	/// when they turn it off
+	pub manual: bool,
+	pub reason: String,
+	/// Verification level from before the raid, if it was raised
+	verification: Option<u64>
+}
+
+impl Raid {
+	fn from_value(value: &Value) -> Option<Self> {
+		Some(Self {
+			since: value["since"].as_u64()?,
+			manual: value["manual"].as_bool().unwrap_or(false),
+			reason: value["reason"].as_str().unwrap_or_default().to_string(),
+			verification: value["verification"].as_u64()
+		})
+	}
+	fn to_value(&self) -> Value {
+		serde_json::json!({
+			"since": self.since,
+			"manual": self.manual,
+			"reason": self.reason,
+			"verification": self.verification
+		})
+	}
+}
+
+/// The raid the server is in, if it's in raid mode
+pub fn raid(db: &Database, guild_id: GuildId) -> Option<Raid> {
+	Raid::from_value(db.get(&["guilds", guild_id.to_string().as_str(), "raid"]))
+}
+
+fn set_raid(db: &mut Database, guild_id: GuildId, raid: Option<&Raid>) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "raid"], match raid {
+		Some(v) => v.to_value(),
+		None => Value::Null
+	})
+}
+
+/// Replaces when raid mode ends by itself with `duration` seconds from now,
+/// or makes it not end by itself with None
+fn schedule_end(db: &mut Database, guild_id: GuildId, duration: Option<u64>) -> Result<(), String> {
+	let guild = guild_id.to_string();
+	for job in scheduler::list(db, Some(END_JOB)) {
+		if job.payload["guild_id"].as_str() == Some(guild.as_str()) {
+			scheduler::cancel(db, &job.id)?;
+		}
+	}
+	if let Some(duration) = duration {
+		scheduler::schedule(db, &Job::once(END_JOB, serde_json::json!({
+			"guild_id": guild
+		}), time::now() + duration))?;
+	}
+	Ok(())
+}
+
+/// Recent joins in each server
+#[derive(Default)]
+pub struct AntiRaid {
+	joins: Mutex<HashMap<GuildId, VecDeque<(Instant, UserId)>>>
+}
+
+impl AntiRaid {
+	/// Adds the join to the server's recent ones. Returns who joined in the
+	/// last `window`, including them
+	fn remember(&self, guild_id: GuildId, user_id: UserId, window: Duration) -> Vec<UserId> {
+		let mut joins = self.joins.lock().unwrap();
+		let recent = joins.entry(guild_id).or_default();
+		while recent.front().is_some_and(|(at, _)| at.elapsed() > window) {
+			recent.pop_front();
+		}
+		recent.push_back((Instant::now(), user_id));
+		recent.iter().map(|(_, id)| *id).collect()
+	}
+	fn forget(&self, guild_id: GuildId) {
+		self.joins.lock().unwrap().remove(&guild_id);
+	}
+}
+
+/// Puts the server in raid mode. A moderator turning it on by hand makes it
+/// stay on until they turn it off. Returns false if it was on already
+pub async fn start(chloe: &ChloeManager<'_>, ctx: &Context, db: &RwLock<Database>, guild_id: GuildId, manual: bool, reason: &str) -> Result<bool, String> {
+	let policy = {
+		let mut db = db.write().unwrap();
+		if let Some(mut raid) = raid(&db, guild_id) {
+			if manual && !raid.manual {
+				raid.manual = true;
+				set_raid(&mut db, guild_id, Some(&raid))?;
+				schedule_end(&mut db, guild_id, None)?;
+			}
+			return Ok(false);
+		}
+		let policy = policy(&chloe.config, &db, guild_id);
+		set_raid(&mut db, guild_id, Some(&Raid {
+			since: time::now(),
+			manual,
+			reason: reason.to_string(),
+			verification: None
+		}))?;
+		schedule_end(&mut db, guild_id, if manual { None } else { Some(policy.duration) })?;
+		policy
+	};
+	let mut lines = vec![reason.to_string()];
+	if policy.verification {
+		match raise_verification(ctx, guild_id).await {
+			Ok(Some(previous)) => {
+				let mut db = db.write().unwrap();
+				if let Some(mut raid) = raid(&db, guild_id) {
+					raid.verification = Some(previous);
+					set_raid(&mut db, guild_id, Some(&raid))?;
+				}
+				lines.push("I raised the verification level to require a verified phone".to_string());
+			},
+			Ok(None) => (),
+			Err(e) => {
+				tracing::warn!(guild = %guild_id, error = %e, "Unable to raise the verification level for a raid");
+				lines.push("I couldn't raise the verification level, since I need the Manage Server permission".to_string());
+			}
+		}
+	}
+	lines.push(match policy.action {
+		JoinAction::Nothing => "Nothing happens to new members".to_string(),
+		JoinAction::Timeout => format!("New members get timed out for {}", time::format_duration(Duration::from_secs(policy.timeout))),
+		JoinAction::Kick => "New members get kicked".to_string()
+	});
+	if !manual {
+		lines.push(format!("Raid mode ends once nobody joined for {}", time::format_duration(Duration::from_secs(policy.duration))));
+	}
+	modlog::raid_alert(chloe, ctx, db, guild_id, "Raid mode started", &lines.join("\n"), policy.alert_role).await;
+	Ok(true)
+}
+
+/// Takes the server out of raid mode. Returns false if it wasn't in it
+pub async fn end(chloe: &ChloeManager<'_>, ctx: &Context, db: &RwLock<Database>, guild_id: GuildId, reason: &str) -> Result<bool, String> {
+	let raid = {
+		let mut db = db.write().unwrap();
+		let raid = match raid(&db, guild_id) {
+			Some(v) => v,
+			None => return Ok(false)
+		};
+		set_raid(&mut db, guild_id, None)?;
+		schedule_end(&mut db, guild_id, None)?;
+		raid
+	};
+	chloe.antiraid.forget(guild_id);
+	let mut lines = vec![reason.to_string()];
+	if let Some(level) = raid.verification {
+		let level: VerificationLevel = serde_json::from_value(level.into()).map_err(|e| format!("{e}"))?;
+		match { guild_id }.edit(&ctx.http, |g| g.verification_level(level)).await {
+			Ok(..) => lines.push("I put the verification level back to what it was".to_string()),
+			Err(e) => {
+				tracing::warn!(guild = %guild_id, error = %e, "Unable to restore the verification level after a raid");
+				lines.push("I couldn't put the verification level back to what it was".to_string());
+			}
+		}
+	}
+	modlog::raid_alert(chloe, ctx, db, guild_id, "Raid mode ended", &lines.join("\n"), None).await;
+	Ok(true)
+}
+
+/// Sets the server's verification level to Higher, which needs a verified
+/// phone. Returns the previous level, if it was lower
+async fn raise_verification(ctx: &Context, guild_id: GuildId) -> serenity::Result<Option<u64>> {
+	let guild = ctx.http.get_guild(guild_id.0).await?;
+	if guild.verification_level >= VerificationLevel::Higher {
+		return Ok(None);
+	}
+	{ guild_id }.edit(&ctx.http, |g| g.verification_level(VerificationLevel::Higher)).await?;
+	Ok(Some(guild.verification_level.num()))
+}
+
+/// Watches joins for raids and young accounts, and reacts to them as the
+/// server's policy says
+pub async fn member_joined(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, member: &Member) {
+	if member.user.bot {
+		return;
+	}
+	let guild_id = member.guild_id;
+	let (policy, raid) = {
+		let db = db.read().unwrap();
+		(policy(&chloe.config, &db, guild_id), raid(&db, guild_id))
+	};
+	if !policy.enabled && raid.is_none() {
+		return;
+	}
+	let recent = chloe.antiraid.remember(guild_id, member.user.id, Duration::from_secs(policy.seconds));
+	let (targets, reason) = match raid {
+		Some(raid) => {
+			if !raid.manual {
+				if let Err(e) = schedule_end(&mut db.write().unwrap(), guild_id, Some(policy.duration)) {
+					tracing::warn!(guild = %guild_id, error = %e, "Unable to extend raid mode");
+				}
+			}
+			(vec![member.user.id], "Joined during a raid".to_string())
+		},
+		None if recent.len() as u64 >= policy.joins => {
+			let reason = format!("{} members joined in {} seconds", recent.len(), policy.seconds);
+			match start(chloe, ctx, &db, guild_id, false, &reason).await {
+				// The members that started it were let in before anyone knew
+				Ok(true) => (recent, "Joined during a raid".to_string()),
+				// Someone else started it meanwhile, and dealt with the others
+				Ok(false) => (vec![member.user.id], "Joined during a raid".to_string()),
+				Err(e) => {
+					tracing::warn!(guild = %guild_id, error = %e, "Unable to start raid mode");
+					(vec![member.user.id], "Joined during a raid".to_string())
+				}
+			}
+		},
+		None => {
+			let age = time::now().saturating_sub(member.user.created_at().unix_timestamp() as u64);
+			match policy.account_age {
+				Some(min) if age < min => {
+					let description = format!("<@{}> ({}) joined with an account created {}", member.user.id, member.user.tag(), time::discord_timestamp(time::now() - age, "R"));
+					modlog::raid_alert(chloe, ctx, &db, guild_id, "Young account joined", &description, None).await;
+					(vec![member.user.id], "Account is too young".to_string())
+				},
+				_ => return
+			}
+		}
+	};
+	for user_id in targets {
+		if let Err(e) = act(ctx, &db, guild_id, user_id, &policy, &reason).await {
+			tracing::warn!(guild = %guild_id, user = %user_id, error = %e, "Unable to act on a member that joined during a raid");
+		}
+	}
+}
+
+/// Gives the member the policy's join action, and adds it to their history
+/// as issued by me
+async fn act(ctx: &Context, db: &RwLock<Database>, guild_id: GuildId, user_id: UserId, policy: &Policy, reason: &str) -> Result<(), String> {
+	let audit_reason = format!("Anti-raid: {}", reason);
+	let (kind, duration) = match policy.action {
+		JoinAction::Nothing => return Ok(()),
+		JoinAction::Timeout => {
+			infractions::timeout_member(ctx, guild_id, user_id, Some(time::now() + policy.timeout), &audit_reason).await.map_err(|e| format!("{e}"))?;
+			(InfractionKind::Timeout, Some(policy.timeout))
+		},
+		JoinAction::Kick => {
+			guild_id.kick_with_reason(&ctx.http, user_id, &audit_reason).await.map_err(|e| format!("{e}"))?;
+			(InfractionKind::Kick, None)
+		}
+	};
+	let me = ctx.http.get_current_user().await.map_err(|e| format!("{e}"))?.id;
+	infractions::add(&mut db.write().unwrap(), guild_id, &Infraction::new(kind, user_id, me, Some(audit_reason), duration))
+}
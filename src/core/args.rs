@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use futures::future::{BoxFuture, FutureExt};
+use serde_json::Value;
+use serenity::model::channel::{Channel, Message};
+use serenity::model::id::{ChannelId, RoleId, UserId};
+use serenity::model::prelude::command::CommandOptionType;
+use serenity::model::application::interaction::application_command::CommandDataOptionValue;
+use super::{CommOption, CommOptions, CommErr};
+
+/// Splits the next argument off `s`. Arguments are separated by whitespace, and
+/// double quotes can be used to group words into a single argument
+pub fn next_arg(s: &str) -> Option<(String, &str)> {
+	let s = s.trim_start();
+	if s.is_empty() {
+		return None;
+	}
+	if let Some(quoted) = s.strip_prefix('"') {
+		if let Some(end) = quoted.find('"') {
+			return Some((quoted[..end].to_string(), &quoted[end + 1..]));
+		}
+	}
+	match s.find(char::is_whitespace) {
+		Some(end) => Some((s[..end].to_string(), &s[end..])),
+		None => Some((s.to_string(), ""))
+	}
+}
+
+/// Parses either a raw ID or a mention such as `<@123>`, `<#123>` or `<@&123>`
+pub fn parse_mention(arg: &str, sigils: &[&str]) -> Option<u64> {
+	if let Ok(id) = arg.parse() {
+		return Some(id);
+	}
+	let inner = arg.strip_prefix('<')?.strip_suffix('>')?;
+	for sigil in sigils {
+		if let Some(id) = inner.strip_prefix(sigil) {
+			if let Ok(id) = id.parse() {
+				return Some(id);
+			}
+		}
+	}
+	None
+}
+
+fn option_kind(option: &Value) -> CommandOptionType {
+	serde_json::from_value(option["type"].clone()).unwrap_or(CommandOptionType::Unknown)
+}
+
+async fn resolve_user(arg: &str, ctx: &serenity::client::Context) -> Option<CommandDataOptionValue> {
+	let id = parse_mention(arg, &["@!", "@"])?;
+	let user = UserId(id).to_user(ctx).await.ok()?;
+	Some(CommandDataOptionValue::User(user, None))
+}
+
+async fn resolve_role(arg: &str, msg: &Message, ctx: &serenity::client::Context) -> Option<CommandDataOptionValue> {
+	let roles = msg.guild_id?.roles(&ctx.http).await.ok()?;
+	let role = match parse_mention(arg, &["@&"]) {
+		Some(id) => roles.get(&RoleId(id)).cloned(),
+		None => roles.into_values().find(|r| r.name.eq_ignore_ascii_case(arg))
+	}?;
+	Some(CommandDataOptionValue::Role(role))
+}
+
+async fn resolve(arg: &str, kind: CommandOptionType, msg: &Message, ctx: &serenity::client::Context) -> Option<CommandDataOptionValue> {
+	match kind {
+		CommandOptionType::String => Some(CommandDataOptionValue::String(arg.to_string())),
+		CommandOptionType::Integer => arg.parse().ok().map(CommandDataOptionValue::Integer),
+		CommandOptionType::Number => arg.parse().ok().map(CommandDataOptionValue::Number),
+		CommandOptionType::Boolean => match arg.to_lowercase().as_str() {
+			"true" | "yes" | "y" | "on" | "1" => Some(CommandDataOptionValue::Boolean(true)),
+			"false" | "no" | "n" | "off" | "0" => Some(CommandDataOptionValue::Boolean(false)),
+			_ => None
+		},
+		CommandOptionType::User => resolve_user(arg, ctx).await,
+		CommandOptionType::Channel => {
+			let id = parse_mention(arg, &["#"])?;
+			let (name, kind) = match ChannelId(id).to_channel(ctx).await.ok()? {
+				// Channels of other servers can't be used
+				Channel::Guild(c) if c.guild_id == msg.guild_id? => (Some(c.name), c.kind),
+				Channel::Category(c) if c.guild_id == msg.guild_id? => (Some(c.name), c.kind),
+				Channel::Private(c) => (None, c.kind),
+				_ => return None
+			};
+			serde_json::from_value(serde_json::json!({
+				"id": id.to_string(),
+				"name": name,
+				"type": kind
+			})).ok().map(CommandDataOptionValue::Channel)
+		},
+		CommandOptionType::Role => resolve_role(arg, msg, ctx).await,
+		CommandOptionType::Mentionable => match resolve_user(arg, ctx).await {
+			Some(v) => Some(v),
+			None => resolve_role(arg, msg, ctx).await
+		},
+		_ => None
+	}
+}
+
+impl CommOptions {
+	/// Parses the arguments of a prefix command according to the command's
+	/// options, as returned by `Command::option_data`.
+	/// Each option takes one argument, except for a trailing string option,
+	/// which takes the rest of the message. Optional arguments that can't be
+	/// parsed are skipped
+	pub fn from_args<'a>(arg_str: &'a str, options: &'a [Value], msg: &'a Message, ctx: &'a serenity::client::Context) -> BoxFuture<'a, Result<Self, CommErr>> {
+		async move {
+			let mut new_options: HashMap<String, CommOption> = HashMap::new();
+			if options.iter().any(|o| matches!(option_kind(o), CommandOptionType::SubCommand | CommandOptionType::SubCommandGroup)) {
+				let (arg, rest) = match next_arg(arg_str) {
+					Some(v) => v,
+					None => return Err(CommErr::SyntaxError)
+				};
+				let option = match options.iter().find(|o| o["name"].as_str().is_some_and(|n| n.eq_ignore_ascii_case(&arg))) {
+					Some(v) => v,
+					None => return Err(CommErr::SyntaxError)
+				};
+				let name = option["name"].as_str().unwrap().to_string();
+				let sub_options = match &option["options"] {
+					Value::Array(v) => v.as_slice(),
+					_ => &[]
+				};
+				new_options.insert(name.clone(), CommOption {
+					name,
+					value: None,
+					kind: option_kind(option),
+					options: CommOptions::from_args(rest, sub_options, msg, ctx).await?,
+					resolved: None,
+					focused: false
+				});
+				return Ok(Self(new_options));
+			}
+			let mut rest = arg_str;
+			let mut attachments = msg.attachments.iter();
+			for (i, option) in options.iter().enumerate() {
+				let name = option["name"].as_str().unwrap_or_default().to_string();
+				let kind = option_kind(option);
+				let required = option["required"].as_bool().unwrap_or(false);
+				let resolved = if kind == CommandOptionType::Attachment {
+					attachments.next().map(|a| (a.url.clone(), CommandDataOptionValue::Attachment(a.clone())))
+				}
+				else if kind == CommandOptionType::String && i == options.len() - 1 {
+					let arg = rest.trim();
+					match arg.is_empty() {
+						true => None,
+						false => {
+							rest = "";
+							Some((arg.to_string(), CommandDataOptionValue::String(arg.to_string())))
+						}
+					}
+				}
+				else {
+					match next_arg(rest) {
+						Some((arg, next)) => match resolve(&arg, kind, msg, ctx).await {
+							Some(v) => {
+								rest = next;
+								Some((arg, v))
+							},
+							None => None
+						},
+						None => None
+					}
+				};
+				match resolved {
+					Some((arg, resolved)) => {
+						new_options.insert(name.clone(), CommOption {
+							name,
+							value: Some(Value::String(arg)),
+							kind,
+							options: CommOptions(HashMap::new()),
+							resolved: Some(resolved),
+							focused: false
+						});
+					},
+					None if required => return Err(CommErr::SyntaxError),
+					None => ()
+				}
+			}
+			Ok(Self(new_options))
+		}.boxed()
+	}
+}
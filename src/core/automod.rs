@@ -0,0 +1,408 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{RwLock, Arc, Mutex};
+use std::time::{Duration, Instant};
+use lazy_static::lazy_static;
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+use serenity::client::Context;
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use super::db::Database;
+use super::infractions::{self, Infraction, InfractionKind};
+use super::{modlog, time, ChloeManager};
+
+lazy_static! {
+	static ref INVITE: Regex = Regex::new(r"(?i)(discord\.gg|discord(app)?\.com/invite)/[a-z0-9-]+").unwrap();
+}
+
+/// How long members' messages are remembered for, for the rules that look at
+/// several of them
+const HISTORY_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+	Words,
+	Invites,
+	Mentions,
+	Spam,
+	Caps,
+	Attachments
+}
+
+impl RuleKind {
+	pub const ALL: [RuleKind; 6] = [
+		RuleKind::Words,
+		RuleKind::Invites,
+		RuleKind::Mentions,
+		RuleKind::Spam,
+		RuleKind::Caps,
+		RuleKind::Attachments
+	];
+	pub fn from_key(key: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|v| v.key() == key)
+	}
+	/// Key of this kind of rule in the database
+	pub fn key(&self) -> &'static str {
+		match self {
+			RuleKind::Words => "words",
+			RuleKind::Invites => "invites",
+			RuleKind::Mentions => "mentions",
+			RuleKind::Spam => "spam",
+			RuleKind::Caps => "caps",
+			RuleKind::Attachments => "attachments"
+		}
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			RuleKind::Words => "Banned words",
+			RuleKind::Invites => "Invite links",
+			RuleKind::Mentions => "Mass mentions",
+			RuleKind::Spam => "Repeated messages",
+			RuleKind::Caps => "Excessive caps",
+			RuleKind::Attachments => "Attachment floods"
+		}
+	}
+	/// Numbers that can be changed in the rule's settings, with their default,
+	/// lowest and highest values
+	pub fn limits(&self) -> &'static [(&'static str, u64, u64, u64)] {
+		match self {
+			RuleKind::Mentions => &[("max", 5, 1, 50)],
+			RuleKind::Spam => &[("count", 4, 2, 20), ("seconds", 10, 1, 60)],
+			RuleKind::Caps => &[("percent", 70, 50, 100), ("min_length", 10, 1, 2000)],
+			RuleKind::Attachments => &[("count", 5, 2, 50), ("seconds", 10, 1, 60)],
+			RuleKind::Words | RuleKind::Invites => &[]
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutomodAction {
+	/// Deletes the message
+	Delete,
+	/// Deletes the message and warns its author, which can escalate
+	Warn,
+	/// Deletes the message and times its author out for some seconds
+	Timeout(u64),
+	/// Only posts it in the moderation log
+	Log
+}
+
+impl AutomodAction {
+	pub fn from_name(name: &str, duration: Option<u64>) -> Option<Self> {
+		match name {
+			"delete" => Some(AutomodAction::Delete),
+			"warn" => Some(AutomodAction::Warn),
+			"timeout" => Some(AutomodAction::Timeout(duration?)),
+			"log" => Some(AutomodAction::Log),
+			_ => None
+		}
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			AutomodAction::Delete => "delete",
+			AutomodAction::Warn => "warn",
+			AutomodAction::Timeout(..) => "timeout",
+			AutomodAction::Log => "log"
+		}
+	}
+	/// Describes the action, like "delete and time out for 10m"
+	pub fn describe(&self) -> String {
+		match self {
+			AutomodAction::Delete => "delete".to_string(),
+			AutomodAction::Warn => "delete and warn".to_string(),
+			AutomodAction::Timeout(duration) => format!("delete and time out for {}", time::format_duration(Duration::from_secs(*duration))),
+			AutomodAction::Log => "only log".to_string()
+		}
+	}
+}
+
+/// A server's automod rule of some kind. Servers have one of each kind
+pub struct Rule {
+	pub kind: RuleKind,
+	pub enabled: bool,
+	pub action: AutomodAction,
+	pub exempt_roles: Vec<RoleId>,
+	pub exempt_channels: Vec<ChannelId>,
+	/// Banned words and phrases, in lowercase
+	pub words: Vec<String>,
+	/// Banned patterns, which are matched without caring about case
+	pub regexes: Vec<String>,
+	/// Numbers that fine-tune the rule, as listed in `RuleKind::limits`
+	pub settings: HashMap<String, u64>
+}
+
+impl Rule {
+	fn from_value(kind: RuleKind, value: &Value) -> Self {
+		let ids = |key: &str| -> Vec<u64> {
+			value[key].as_array().map(|v| v.iter().filter_map(|v| v.as_str()?.parse().ok()).collect()).unwrap_or_default()
+		};
+		let strings = |key: &str| -> Vec<String> {
+			value[key].as_array().map(|v| v.iter().filter_map(|v| v.as_str().map(|v| v.to_string())).collect()).unwrap_or_default()
+		};
+		Self {
+			kind,
+			enabled: value["enabled"].as_bool().unwrap_or(false),
+			action: value["action"].as_str()
+				.and_then(|v| AutomodAction::from_name(v, value["duration"].as_u64()))
+				.unwrap_or(AutomodAction::Delete),
+			exempt_roles: ids("exempt_roles").into_iter().map(RoleId).collect(),
+			exempt_channels: ids("exempt_channels").into_iter().map(ChannelId).collect(),
+			words: strings("words"),
+			regexes: strings("regexes"),
+			settings: kind.limits().iter().map(|(name, default, ..)| {
+				(name.to_string(), value["settings"][*name].as_u64().unwrap_or(*default))
+			}).collect()
+		}
+	}
+	fn to_value(&self) -> Value {
+		serde_json::json!({
+			"enabled": self.enabled,
+			"action": self.action.name(),
+			"duration": match self.action {
+				AutomodAction::Timeout(duration) => Some(duration),
+				_ => None
+			},
+			"exempt_roles": self.exempt_roles.iter().map(|v| v.to_string()).collect::<Vec<String>>(),
+			"exempt_channels": self.exempt_channels.iter().map(|v| v.to_string()).collect::<Vec<String>>(),
+			"words": self.words,
+			"regexes": self.regexes,
+			"settings": self.settings
+		})
+	}
+	pub fn setting(&self, name: &str) -> u64 {
+		self.settings.get(name).copied().unwrap_or(0)
+	}
+	fn is_exempt(&self, channel_id: ChannelId, roles: &[RoleId]) -> bool {
+		self.exempt_channels.contains(&channel_id) || roles.iter().any(|v| self.exempt_roles.contains(v))
+	}
+}
+
+pub fn get(db: &Database, guild_id: GuildId, kind: RuleKind) -> Rule {
+	Rule::from_value(kind, db.get(&["guilds", guild_id.to_string().as_str(), "automod", kind.key()]))
+}
+
+pub fn set(db: &mut Database, guild_id: GuildId, rule: &Rule) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "automod", rule.kind.key()], rule.to_value())
+}
+
+/// Every rule of the server, enabled or not
+pub fn rules(db: &Database, guild_id: GuildId) -> Vec<Rule> {
+	RuleKind::ALL.iter().map(|kind| get(db, guild_id, *kind)).collect()
+}
+
+/// Compiles a banned pattern. Their size is limited, since they're run on
+/// every message
+pub fn compile(pattern: &str) -> Result<Regex, String> {
+	RegexBuilder::new(pattern)
+		.case_insensitive(true)
+		.size_limit(1 << 16)
+		.build()
+		.map_err(|e| format!("{e}"))
+}
+
+#[derive(Clone)]
+struct Recent {
+	at: Instant,
+	content: String,
+	attachments: usize
+}
+
+/// What automod remembers between messages
+#[derive(Default)]
+pub struct AutoMod {
+	/// Recent messages of each member, for the rules that look at several of them
+	history: Mutex<HashMap<(GuildId, UserId), VecDeque<Recent>>>,
+	/// Compiled banned patterns, by pattern
+	regexes: Mutex<HashMap<String, Option<Regex>>>
+}
+
+impl AutoMod {
+	/// Adds the message to its author's history. Returns their messages from
+	/// the last minute, including this one
+	fn remember(&self, guild_id: GuildId, msg: &Message) -> Vec<Recent> {
+		let mut history = self.history.lock().unwrap();
+		// Members that stopped talking are forgotten every now and then
+		if history.len() > 10000 {
+			history.retain(|_, v| v.back().is_some_and(|m| m.at.elapsed() < HISTORY_WINDOW));
+		}
+		let recent = history.entry((guild_id, msg.author.id)).or_default();
+		while recent.front().is_some_and(|m| m.at.elapsed() >= HISTORY_WINDOW) {
+			recent.pop_front();
+		}
+		recent.push_back(Recent {
+			at: Instant::now(),
+			content: msg.content.trim().to_lowercase(),
+			attachments: msg.attachments.len()
+		});
+		recent.iter().cloned().collect()
+	}
+	/// Forgets the member's messages, so that they aren't punished twice for the same ones
+	fn forget(&self, guild_id: GuildId, user_id: UserId) {
+		self.history.lock().unwrap().remove(&(guild_id, user_id));
+	}
+	fn regex(&self, pattern: &str) -> Option<Regex> {
+		let mut regexes = self.regexes.lock().unwrap();
+		if regexes.len() > 1000 {
+			regexes.clear();
+		}
+		regexes.entry(pattern.to_string()).or_insert_with(|| compile(pattern).ok()).clone()
+	}
+	/// Why the message breaks the rule, if it does
+	fn violation(&self, rule: &Rule, msg: &Message, recent: &[Recent]) -> Option<String> {
+		let within = |seconds: u64| recent.iter().filter(move |m| m.at.elapsed() <= Duration::from_secs(seconds));
+		match rule.kind {
+			RuleKind::Words => {
+				let content = msg.content.to_lowercase();
+				let words: Vec<&str> = content.split(|c: char| !c.is_alphanumeric()).collect();
+				if let Some(word) = rule.words.iter().find(|w| match w.contains(char::is_whitespace) {
+					true => content.contains(w.as_str()),
+					false => words.contains(&w.as_str())
+				}) {
+					return Some(format!("Used the banned word ||{}||", word));
+				}
+				rule.regexes.iter()
+					.find(|p| self.regex(p).is_some_and(|r| r.is_match(&msg.content)))
+					.map(|p| format!("Matched the banned pattern `{}`", p))
+			},
+			RuleKind::Invites => match INVITE.is_match(&msg.content) {
+				true => Some("Posted an invite link".to_string()),
+				false => None
+			},
+			RuleKind::Mentions => {
+				let mut users: Vec<UserId> = msg.mentions.iter().map(|u| u.id).collect();
+				users.sort();
+				users.dedup();
+				let count = users.len() + msg.mention_roles.len();
+				match count as u64 > rule.setting("max") {
+					true => Some(format!("Mentioned {} users and roles", count)),
+					false => None
+				}
+			},
+			RuleKind::Spam => {
+				let content = msg.content.trim().to_lowercase();
+				if content.is_empty() {
+					return None;
+				}
+				let count = within(rule.setting("seconds")).filter(|m| m.content == content).count();
+				match count as u64 >= rule.setting("count") {
+					true => Some(format!("Sent the same message {} times in {} seconds", count, rule.setting("seconds"))),
+					false => None
+				}
+			},
+			RuleKind::Caps => {
+				let letters: Vec<char> = msg.content.chars().filter(|c| c.is_alphabetic()).collect();
+				let upper = letters.iter().filter(|c| c.is_uppercase()).count();
+				match letters.len() as u64 >= rule.setting("min_length") && upper as u64 * 100 >= rule.setting("percent") * letters.len() as u64 {
+					true => Some(format!("Wrote {}% of the message in capital letters", upper * 100 / letters.len().max(1))),
+					false => None
+				}
+			},
+			RuleKind::Attachments => {
+				if msg.attachments.is_empty() {
+					return None;
+				}
+				let count: usize = within(rule.setting("seconds")).map(|m| m.attachments).sum();
+				match count as u64 >= rule.setting("count") {
+					true => Some(format!("Sent {} attachments in {} seconds", count, rule.setting("seconds"))),
+					false => None
+				}
+			}
+		}
+	}
+}
+
+/// Checks the message against the server's rules, and acts on the first one
+/// it breaks. Returns whether the message was deleted, in which case it
+/// shouldn't be treated as a command
+pub async fn check(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, msg: &Message) -> bool {
+	let guild_id = match msg.guild_id {
+		Some(v) if !msg.author.bot => v,
+		_ => return false
+	};
+	let rules: Vec<Rule> = rules(&db.read().unwrap(), guild_id).into_iter().filter(|v| v.enabled).collect();
+	if rules.is_empty() {
+		return false;
+	}
+	let roles = msg.member.as_ref().map(|m| m.roles.as_slice()).unwrap_or_default();
+	let recent = chloe.automod.remember(guild_id, msg);
+	let (rule, reason) = match rules.iter()
+		.filter(|rule| !rule.is_exempt(msg.channel_id, roles))
+		.find_map(|rule| chloe.automod.violation(rule, msg, &recent).map(|reason| (rule, reason)))
+	{
+		Some(v) => v,
+		None => return false
+	};
+	chloe.automod.forget(guild_id, msg.author.id);
+	let deleted = match rule.action {
+		AutomodAction::Log => false,
+		_ => match msg.delete(&ctx.http).await {
+			Ok(..) => true,
+			Err(e) => {
+				tracing::warn!(guild = %guild_id, error = %e, "Unable to delete a message that broke an automod rule");
+				false
+			}
+		}
+	};
+	let audit_reason = format!("Automod: {}", reason);
+	let notice = match rule.action {
+		AutomodAction::Warn => match punish(ctx, &db, guild_id, msg.author.id, InfractionKind::Warn, None, &audit_reason).await {
+			Ok(..) => {
+				let (count, escalation) = {
+					let db = db.read().unwrap();
+					let count = infractions::warnings(&db, guild_id, msg.author.id);
+					(count, infractions::escalations(&chloe.config, &db, guild_id).into_iter().find(|v| v.warnings == count as u64))
+				};
+				let mut notice = format!("<@{}>, you were warned: {}. You have {} warnings now", msg.author.id, reason, count);
+				if let Some(escalation) = escalation {
+					let audit_reason = format!("Automod: Reached {} warnings", escalation.warnings);
+					match infractions::escalate(ctx, &db, guild_id, msg.author.id, &escalation, &audit_reason).await {
+						Ok(text) => notice += &format!(", so you were {}", text),
+						Err(e) => tracing::warn!(guild = %guild_id, error = %e, "Unable to escalate an automod warning")
+					}
+				}
+				Some(notice)
+			},
+			Err(e) => {
+				tracing::warn!(guild = %guild_id, error = %e, "Unable to warn someone that broke an automod rule");
+				None
+			}
+		},
+		AutomodAction::Timeout(duration) => {
+			let duration = duration.min(infractions::MAX_TIMEOUT);
+			let until = time::now() + duration;
+			match infractions::timeout_member(ctx, guild_id, msg.author.id, Some(until), &audit_reason).await {
+				Ok(..) => match punish(ctx, &db, guild_id, msg.author.id, InfractionKind::Timeout, Some(duration), &audit_reason).await {
+					Ok(..) => Some(format!("<@{}> was timed out until {}: {}", msg.author.id, time::discord_timestamp(until, "f"), reason)),
+					Err(e) => {
+						tracing::warn!(guild = %guild_id, error = %e, "Unable to record an automod timeout");
+						None
+					}
+				},
+				Err(e) => {
+					tracing::warn!(guild = %guild_id, error = %e, "Unable to time out someone that broke an automod rule");
+					None
+				}
+			}
+		},
+		AutomodAction::Delete | AutomodAction::Log => None
+	};
+	if let Some(notice) = notice {
+		let author_id = msg.author.id;
+		let sent = msg.channel_id.send_message(&ctx.http, |m| {
+			m.content(notice)
+			.allowed_mentions(|a| a.users(vec![author_id]))
+		}).await;
+		if let Err(e) = sent {
+			tracing::warn!(guild = %guild_id, error = %e, "Unable to send an automod notice");
+		}
+	}
+	modlog::automod_triggered(chloe, ctx, db, msg, rule.kind.name(), &reason, &rule.action.describe()).await;
+	deleted
+}
+
+/// Adds an infraction issued by me to the member's history. `duration` is how
+/// long a timeout lasts, in seconds
+async fn punish(ctx: &Context, db: &RwLock<Database>, guild_id: GuildId, user_id: UserId, kind: InfractionKind, duration: Option<u64>, reason: &str) -> Result<(), String> {
+	let me = ctx.http.get_current_user().await.map_err(|e| format!("{e}"))?.id;
+	infractions::add(&mut db.write().unwrap(), guild_id, &Infraction::new(kind, user_id, me, Some(reason.to_string()), duration))
+}
@@ -0,0 +1,128 @@
+use serde_json::Value;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use super::db::Database;
+use super::time;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+	User,
+	Guild,
+	Channel
+}
+
+impl BlockKind {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name.to_lowercase().as_str() {
+			"user" | "users" => Some(BlockKind::User),
+			"server" | "servers" | "guild" | "guilds" => Some(BlockKind::Guild),
+			"channel" | "channels" => Some(BlockKind::Channel),
+			_ => None
+		}
+	}
+	/// Key of this kind of block in the database
+	pub fn key(&self) -> &'static str {
+		match self {
+			BlockKind::User => "users",
+			BlockKind::Guild => "guilds",
+			BlockKind::Channel => "channels"
+		}
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			BlockKind::User => "user",
+			BlockKind::Guild => "server",
+			BlockKind::Channel => "channel"
+		}
+	}
+}
+
+pub struct Block {
+	pub kind: BlockKind,
+	pub id: u64,
+	pub reason: Option<String>,
+	/// UNIX timestamp of when the block ends
+	pub expires: Option<u64>
+}
+
+impl Block {
+	fn from_value(kind: BlockKind, id: u64, value: &Value) -> Self {
+		Self {
+			kind,
+			id,
+			reason: value["reason"].as_str().map(|v| v.to_string()),
+			expires: value["expires"].as_u64()
+		}
+	}
+	pub fn is_active(&self) -> bool {
+		match self.expires {
+			Some(expires) => expires > time::now(),
+			None => true
+		}
+	}
+}
+
+pub fn get(db: &Database, kind: BlockKind, id: u64) -> Option<Block> {
+	let value = db.get(&["blocklist", kind.key(), id.to_string().as_str()]);
+	if value.is_null() {
+		return None;
+	}
+	let block = Block::from_value(kind, id, value);
+	match block.is_active() {
+		true => Some(block),
+		false => None
+	}
+}
+
+pub fn add(db: &mut Database, block: &Block, by: UserId) -> Result<(), String> {
+	db.set(&["blocklist", block.kind.key(), block.id.to_string().as_str()], serde_json::json!({
+		"reason": block.reason,
+		"expires": block.expires,
+		"by": by.to_string(),
+		"at": time::now()
+	}))
+}
+
+/// Returns whether there was an active block to remove
+pub fn remove(db: &mut Database, kind: BlockKind, id: u64) -> Result<bool, String> {
+	let existed = get(db, kind, id).is_some();
+	db.set(&["blocklist", kind.key(), id.to_string().as_str()], Value::Null)?;
+	Ok(existed)
+}
+
+/// Returns every active block, and removes the ones that expired
+pub fn list(db: &mut Database) -> Result<Vec<Block>, String> {
+	let mut blocks = Vec::new();
+	let mut expired = Vec::new();
+	for kind in [BlockKind::User, BlockKind::Guild, BlockKind::Channel] {
+		if let Value::Object(entries) = db.get(&["blocklist", kind.key()]) {
+			for (id, value) in entries.iter() {
+				let id = match id.parse() {
+					Ok(v) => v,
+					Err(..) => continue
+				};
+				let block = Block::from_value(kind, id, value);
+				match block.is_active() {
+					true => blocks.push(block),
+					false => expired.push((kind, id))
+				}
+			}
+		}
+	}
+	for (kind, id) in expired {
+		db.set(&["blocklist", kind.key(), id.to_string().as_str()], Value::Null)?;
+	}
+	Ok(blocks)
+}
+
+/// Returns the block that stops the user from using commands here, if there is one
+pub fn check(db: &Database, user_id: UserId, guild_id: Option<GuildId>, channel_id: ChannelId) -> Option<Block> {
+	if let Some(block) = get(db, BlockKind::User, user_id.0) {
+		return Some(block);
+	}
+	if let Some(guild_id) = guild_id {
+		if let Some(block) = get(db, BlockKind::Guild, guild_id.0) {
+			return Some(block);
+		}
+	}
+	get(db, BlockKind::Channel, channel_id.0)
+}
@@ -1,12 +1,15 @@
 use std::fs;
 use std::io;
-use std::io::{Read, Write, Seek, BufRead};
-use std::path::Path;
+use std::io::{Read, Write, BufRead};
+use std::path::{Path, PathBuf};
 use serde_json::Value;
 
 pub struct Database {
+	path: PathBuf,
 	db_tmp_file: fs::File,
-	data: Value
+	data: Value,
+	/// Changes that haven't been applied to database.json yet
+	pending_writes: u64
 }
 
 impl Database {
@@ -30,17 +33,17 @@ impl Database {
 				return Err(format!("Unable to create the database's database_tmp.json file: {e}"));
 			}
 		}
-		let db_tmp_file = match fs::File::options().read(true).write(true).append(true).open(db_tmp_file_path.to_str().unwrap()) {
+		let db_tmp_file = match fs::File::options().read(true).append(true).open(db_tmp_file_path.to_str().unwrap()) {
 			Ok(v) => v,
 			Err(e) => return Err(format!("Unable to open the database's database_tmp.json file: {e}"))
 		};
-		let mut db_file = match fs::File::options().read(true).write(true).open(db_file_path.to_str().unwrap()) {
+		let mut db_file = match fs::File::options().read(true).open(db_file_path.to_str().unwrap()) {
 			Ok(v) => v,
 			Err(e) => return Err(format!("Unable to open the database's database.json file: {e}"))
 		};
 		let mut db_file_content = String::new();
 		db_file.read_to_string(&mut db_file_content).unwrap();
-		let data: Value = match serde_json::from_str(&*db_file_content) {
+		let data: Value = match serde_json::from_str(&db_file_content) {
 			Ok(v) => v,
 			Err(e) => return Err(format!("Unable to parse the database's database.json file as JSON: {e}"))
 		};
@@ -48,38 +51,61 @@ impl Database {
 			return Err("The database's database.json file must contain a JSON object in its root".to_string())
 		}
 		let mut db = Database {
+			path: path.to_path_buf(),
 			db_tmp_file: db_tmp_file.try_clone().unwrap(),
-			data: data
+			data,
+			pending_writes: 0
 		};
 		if db_tmp_file.metadata().unwrap().len() != 0 {
-			println!("Applying changes from database_tmp.json to database.json");
+			tracing::info!("Applying changes from database_tmp.json to database.json");
 			for line in io::BufReader::new(db_tmp_file.try_clone().unwrap()).lines() {
-				let new_data: Value = serde_json::from_str(&*line.unwrap()).unwrap();
-				let path = match &new_data[0] {
-					Value::Array(v) => {
-						v.iter().map(|x| match x {
-							Value::String(v) => v.as_str(),
-							_ => ""
-						}).collect()
-					},
-					_ => Vec::new()
+				let new_data: Value = serde_json::from_str(&line.unwrap()).unwrap();
+				// Lines are either one change, or an object with several
+				// changes that were made together
+				let changes = match &new_data["changes"] {
+					Value::Array(v) => v.iter().collect(),
+					_ => vec![&new_data]
 				};
-				db.silently_set(path.as_slice(), new_data[1].clone()).unwrap();
+				for change in changes {
+					let path = match &change[0] {
+						Value::Array(v) => {
+							v.iter().map(|x| match x {
+								Value::String(v) => v.as_str(),
+								_ => ""
+							}).collect()
+						},
+						_ => Vec::new()
+					};
+					db.silently_set(path.as_slice(), change[1].clone()).unwrap();
+				}
 			}
-			db_tmp_file.set_len(0).unwrap();
-			db_file.set_len(0).unwrap();
-			db_file.rewind().unwrap();
-			writeln!(db_file, "{}", db.data.to_string()).unwrap();
-			println!("Done");
+			db.compact()?;
+			tracing::info!("Done");
 		}
 		Ok (
 			db
 		)
 	}
+	/// Whether the value at the path, from `data`, can be set. It can't if one
+	/// of its parents is there but isn't an object
+	fn can_set(mut data: &Value, path: &[&str]) -> bool {
+		for key in &path[..path.len().saturating_sub(1)] {
+			data = match data {
+				Value::Object(v) => match v.get(*key) {
+					Some(v) => v,
+					None => return true
+				},
+				_ => return false
+			};
+		}
+		data.is_object()
+	}
 	fn silently_set(&mut self, path: &[&str], value: Value) -> Result<(), ()> {
+		if !Self::can_set(&self.data, path) {
+			return Err(());
+		}
 		let mut data = &mut self.data;
-		for i in 0..(path.len() - 1) {
-			let key = path[i];
+		for key in &path[..path.len() - 1] {
 			if data.get(key).is_some() {
 				data = match data.is_object() {
 					true => data.get_mut(key).unwrap(),
@@ -102,12 +128,83 @@ impl Database {
 		}
 		Ok(())
 	}
-	pub fn set(&mut self, path: &[&str], value: Value) -> Result<(), ()> {
-		self.silently_set(path, value.clone())?;
+	pub fn set(&mut self, path: &[&str], value: Value) -> Result<(), String> {
+		if self.silently_set(path, value.clone()).is_err() {
+			return Err(format!("Unable to set {}: one of its parents is not an object", path.join(".")));
+		}
 		writeln!(&mut self.db_tmp_file, "{}", serde_json::to_string(&serde_json::json!([path.to_vec(), value])).unwrap()).unwrap();
+		self.pending_writes += 1;
+		Ok(())
+	}
+	/// Sets several values at once. They're written to database_tmp.json in
+	/// one line, so after a crash either all of them are applied or none are
+	pub fn set_all(&mut self, changes: &[(&[&str], Value)]) -> Result<(), String> {
+		// Everything is checked before anything is changed. A change replaces
+		// what's under its path, so paths under an earlier change's are checked
+		// against its value
+		for (i, (path, _)) in changes.iter().enumerate() {
+			let valid = match changes[..i].iter().rev().find(|(v, _)| v.len() < path.len() && path.starts_with(v)) {
+				Some((_, Value::Null)) => true,
+				Some((parent, value)) => Self::can_set(value, &path[parent.len()..]),
+				None => Self::can_set(&self.data, path)
+			};
+			if !valid {
+				return Err(format!("Unable to set {}: one of its parents is not an object", path.join(".")));
+			}
+		}
+		for (path, value) in changes.iter() {
+			if self.silently_set(path, value.clone()).is_err() {
+				return Err(format!("Unable to set {}: one of its parents is not an object", path.join(".")));
+			}
+		}
+		let changes: Vec<Value> = changes.iter().map(|(path, value)| serde_json::json!([path.to_vec(), value])).collect();
+		writeln!(&mut self.db_tmp_file, "{}", serde_json::to_string(&serde_json::json!({"changes": changes})).unwrap()).unwrap();
+		self.pending_writes += 1;
 		Ok(())
 	}
-	pub fn get(&mut self, path: &[&str]) -> &Value {
+	/// Size of the changes that haven't been applied to database.json yet, in bytes
+	pub fn log_size(&self) -> u64 {
+		self.db_tmp_file.metadata().map(|m| m.len()).unwrap_or(0)
+	}
+	/// Makes sure the changes are on the disk, and not just in the OS' buffers
+	pub fn sync(&self) -> Result<(), String> {
+		if let Err(e) = self.db_tmp_file.sync_all() {
+			return Err(format!("Unable to sync the database's database_tmp.json file: {e}"));
+		}
+		Ok(())
+	}
+	/// Applies the changes in database_tmp.json to database.json and empties
+	/// database_tmp.json. database.json is replaced in one go, so a crash
+	/// halfway through doesn't lose anything
+	pub fn compact(&mut self) -> Result<(), String> {
+		self.sync()?;
+		let db_file_path = self.path.join("database.json");
+		let new_db_file_path = self.path.join("database_new.json");
+		let written = fs::File::create(&new_db_file_path).and_then(|mut file| {
+			writeln!(file, "{}", self.data)?;
+			file.sync_all()
+		});
+		if let Err(e) = written {
+			return Err(format!("Unable to write the database's database_new.json file: {e}"));
+		}
+		if let Err(e) = fs::rename(&new_db_file_path, &db_file_path) {
+			return Err(format!("Unable to replace the database's database.json file: {e}"));
+		}
+		if let Err(e) = self.db_tmp_file.set_len(0) {
+			return Err(format!("Unable to empty the database's database_tmp.json file: {e}"));
+		}
+		self.pending_writes = 0;
+		Ok(())
+	}
+	pub fn pending_writes(&self) -> u64 {
+		self.pending_writes
+	}
+	/// Size of the database's files, in bytes
+	pub fn size(&self) -> u64 {
+		let db_file_size = fs::metadata(self.path.join("database.json")).map(|m| m.len()).unwrap_or(0);
+		db_file_size + self.log_size()
+	}
+	pub fn get(&self, path: &[&str]) -> &Value {
 		let mut data = &self.data;
 		for key in path {
 			if data.get(key).is_some() {
@@ -125,4 +222,13 @@ impl Database {
 		}
 		data
 	}
+	/// Iterates over the keys and values of the object at `path` without
+	/// copying them, or over nothing if it isn't an object
+	pub fn entries(&self, path: &[&str]) -> impl Iterator<Item = (&String, &Value)> {
+		self.get(path).as_object().into_iter().flatten()
+	}
+	/// Number of keys in the object at `path`, or 0 if it isn't an object
+	pub fn count(&self, path: &[&str]) -> usize {
+		self.get(path).as_object().map(|v| v.len()).unwrap_or(0)
+	}
 }
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use serde_json::Value;
+use serenity::model::id::{GuildId, RoleId, UserId};
+use super::db::Database;
+use super::{errors, time};
+
+/// Time between daily rewards, in seconds
+pub const DAILY_COOLDOWN: u64 = 24 * 60 * 60;
+/// Most money a member can have, which keeps sums far from overflowing
+pub const MAX_BALANCE: u64 = 1_000_000_000_000;
+/// Most items a server's shop can have
+pub const MAX_ITEMS: usize = 50;
+
+/// How a server's economy works
+pub struct Settings {
+	/// Name of the currency, like "coins"
+	pub currency: String,
+	/// How much the daily reward is
+	pub daily: u64
+}
+
+impl Settings {
+	/// An amount with the currency's name, like "100 coins"
+	pub fn format(&self, amount: u64) -> String {
+		format!("{} {}", amount, self.currency)
+	}
+}
+
+/// The server's settings, which fall back to the config's `economy`
+pub fn settings(config: &Value, db: &Database, guild_id: GuildId) -> Settings {
+	let value = db.get(&["guilds", guild_id.to_string().as_str(), "settings", "economy"]);
+	let get = |key: &str| match &value[key] {
+		Value::Null => &config["economy"][key],
+		v => v
+	};
+	Settings {
+		currency: get("currency").as_str().unwrap_or("coins").to_string(),
+		daily: get("daily").as_u64().unwrap_or(100).min(MAX_BALANCE)
+	}
+}
+
+pub fn set_settings(db: &mut Database, guild_id: GuildId, settings: &Settings) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "settings", "economy"], serde_json::json!({
+		"currency": settings.currency,
+		"daily": settings.daily
+	}))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKind {
+	Daily,
+	Payment,
+	Purchase,
+	/// Money given back for a purchase that couldn't be completed
+	Refund,
+	/// Money given or taken by an admin
+	Admin
+}
+
+impl TransactionKind {
+	pub fn from_key(key: &str) -> Option<Self> {
+		match key {
+			"daily" => Some(TransactionKind::Daily),
+			"payment" => Some(TransactionKind::Payment),
+			"purchase" => Some(TransactionKind::Purchase),
+			"refund" => Some(TransactionKind::Refund),
+			"admin" => Some(TransactionKind::Admin),
+			_ => None
+		}
+	}
+	/// Key of this kind of transaction in the database
+	pub fn key(&self) -> &'static str {
+		match self {
+			TransactionKind::Daily => "daily",
+			TransactionKind::Payment => "payment",
+			TransactionKind::Purchase => "purchase",
+			TransactionKind::Refund => "refund",
+			TransactionKind::Admin => "admin"
+		}
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			TransactionKind::Daily => "Daily reward",
+			TransactionKind::Payment => "Payment",
+			TransactionKind::Purchase => "Purchase",
+			TransactionKind::Refund => "Refund",
+			TransactionKind::Admin => "Admin"
+		}
+	}
+}
+
+/// A change to a member's balance, as kept in the server's transaction log
+pub struct Transaction {
+	pub id: String,
+	pub kind: TransactionKind,
+	pub user_id: UserId,
+	/// How much the balance changed by
+	pub amount: i64,
+	/// Balance after the change
+	pub balance: u64,
+	/// Who paid or was paid, or the admin that made the change
+	pub other_id: Option<UserId>,
+	/// What was bought, or why the change was made
+	pub note: Option<String>,
+	/// UNIX timestamp of when it happened
+	pub created: u64
+}
+
+impl Transaction {
+	pub fn new(kind: TransactionKind, user_id: UserId, amount: i64, other_id: Option<UserId>, note: Option<String>) -> Self {
+		Self {
+			id: errors::new_id(),
+			kind,
+			user_id,
+			amount,
+			balance: 0,
+			other_id,
+			note,
+			created: time::now()
+		}
+	}
+	fn from_value(id: &str, value: &Value) -> Option<Self> {
+		Some(Self {
+			id: id.to_string(),
+			kind: TransactionKind::from_key(value["kind"].as_str()?)?,
+			user_id: UserId(value["user_id"].as_str()?.parse().ok()?),
+			amount: value["amount"].as_i64()?,
+			balance: value["balance"].as_u64().unwrap_or(0),
+			other_id: value["other_id"].as_str().and_then(|v| v.parse().ok()).map(UserId),
+			note: value["note"].as_str().map(|v| v.to_string()),
+			created: value["created"].as_u64().unwrap_or(0)
+		})
+	}
+	fn to_value(&self) -> Value {
+		serde_json::json!({
+			"kind": self.kind.key(),
+			"user_id": self.user_id.to_string(),
+			"amount": self.amount,
+			"balance": self.balance,
+			"other_id": self.other_id.map(|v| v.to_string()),
+			"note": self.note,
+			"created": self.created
+		})
+	}
+}
+
+/// Why money couldn't change hands
+#[derive(Debug)]
+pub enum EconomyError {
+	/// The member doesn't have enough, and has this much
+	NotEnough(UserId, u64),
+	/// The member would have more than MAX_BALANCE
+	TooMuch(UserId),
+	/// The daily reward was claimed already, and can be claimed again at this
+	/// UNIX timestamp
+	Cooldown(u64),
+	SoldOut,
+	Database(String)
+}
+
+pub fn balance(db: &Database, guild_id: GuildId, user_id: UserId) -> u64 {
+	db.get(&["guilds", guild_id.to_string().as_str(), "balances", user_id.to_string().as_str()]).as_u64().unwrap_or(0)
+}
+
+/// Changes balances and logs the transactions in one write, so that money is
+/// never made or lost halfway. Nothing changes if a balance would go below 0
+/// or above MAX_BALANCE. `also` are other changes that are made along with
+/// them, like giving what was bought
+pub fn apply(config: &Value, db: &mut Database, guild_id: GuildId, transactions: &mut [Transaction], also: &[(&[&str], Value)]) -> Result<(), EconomyError> {
+	let mut balances: HashMap<UserId, u64> = HashMap::new();
+	for transaction in transactions.iter_mut() {
+		let user_id = transaction.user_id;
+		let old = *balances.entry(user_id).or_insert_with(|| balance(db, guild_id, user_id));
+		let new = match old.checked_add_signed(transaction.amount) {
+			Some(v) if v <= MAX_BALANCE => v,
+			Some(..) => return Err(EconomyError::TooMuch(user_id)),
+			None => return Err(EconomyError::NotEnough(user_id, old))
+		};
+		balances.insert(user_id, new);
+		transaction.balance = new;
+	}
+	let guild = guild_id.to_string();
+	let balances: Vec<(String, Value)> = balances.into_iter().map(|(user_id, balance)| (user_id.to_string(), match balance {
+		0 => Value::Null,
+		v => v.into()
+	})).collect();
+	let balance_paths: Vec<[&str; 4]> = balances.iter().map(|(user, _)| ["guilds", guild.as_str(), "balances", user.as_str()]).collect();
+	let transaction_paths: Vec<[&str; 4]> = transactions.iter().map(|v| ["guilds", guild.as_str(), "transactions", v.id.as_str()]).collect();
+	let mut changes: Vec<(&[&str], Value)> = balance_paths.iter().zip(balances.iter()).map(|(path, (_, value))| (path.as_slice(), value.clone()))
+		.chain(transaction_paths.iter().zip(transactions.iter()).map(|(path, v)| (path.as_slice(), v.to_value())))
+		.collect();
+	changes.extend(also.iter().cloned());
+	db.set_all(&changes).map_err(EconomyError::Database)?;
+	if let Err(e) = trim_log(db, guild_id, config["economy"]["max_transactions"].as_u64().unwrap_or(1000) as usize) {
+		tracing::warn!(guild = %guild_id, error = %e, "Unable to forget old transactions");
+	}
+	Ok(())
+}
+
+/// Forgets the oldest transactions once there are more than `max`
+fn trim_log(db: &mut Database, guild_id: GuildId, max: usize) -> Result<(), String> {
+	let guild = guild_id.to_string();
+	let path = ["guilds", guild.as_str(), "transactions"];
+	if db.count(&path) <= max {
+		return Ok(());
+	}
+	let mut transactions: Vec<(String, u64)> = db.entries(&path).map(|(id, v)| (id.clone(), v["created"].as_u64().unwrap_or(0))).collect();
+	transactions.sort_by_key(|(_, created)| *created);
+	for (id, _) in transactions.iter().take(transactions.len() - max) {
+		db.set(&["guilds", guild.as_str(), "transactions", id.as_str()], Value::Null)?;
+	}
+	Ok(())
+}
+
+/// The server's transactions, or the member's if there's one, newest first
+pub fn transactions(db: &Database, guild_id: GuildId, user_id: Option<UserId>) -> Vec<Transaction> {
+	let mut transactions: Vec<Transaction> = db.entries(&["guilds", guild_id.to_string().as_str(), "transactions"])
+		.filter_map(|(id, v)| Transaction::from_value(id, v))
+		.filter(|v| user_id.is_none() || user_id == Some(v.user_id))
+		.collect();
+	transactions.sort_by_key(|v| std::cmp::Reverse(v.created));
+	transactions
+}
+
+/// When the member last claimed the daily reward, as a UNIX timestamp
+pub fn last_daily(db: &Database, guild_id: GuildId, user_id: UserId) -> Option<u64> {
+	db.get(&["guilds", guild_id.to_string().as_str(), "daily", user_id.to_string().as_str()]).as_u64()
+}
+
+/// Gives the member the daily reward if they can claim it
+pub fn claim_daily(config: &Value, db: &mut Database, guild_id: GuildId, user_id: UserId) -> Result<Transaction, EconomyError> {
+	if let Some(last) = last_daily(db, guild_id, user_id) {
+		if last + DAILY_COOLDOWN > time::now() {
+			return Err(EconomyError::Cooldown(last + DAILY_COOLDOWN));
+		}
+	}
+	let amount = settings(config, db, guild_id).daily;
+	let mut transaction = [Transaction::new(TransactionKind::Daily, user_id, amount as i64, None, None)];
+	let (guild, user) = (guild_id.to_string(), user_id.to_string());
+	apply(config, db, guild_id, &mut transaction, &[
+		(&["guilds", guild.as_str(), "daily", user.as_str()], time::now().into())
+	])?;
+	let [transaction] = transaction;
+	Ok(transaction)
+}
+
+/// Moves money from a member to another. Returns the payer's transaction
+pub fn pay(config: &Value, db: &mut Database, guild_id: GuildId, from: UserId, to: UserId, amount: u64) -> Result<Transaction, EconomyError> {
+	let mut transactions = [
+		Transaction::new(TransactionKind::Payment, from, -(amount as i64), Some(to), None),
+		Transaction::new(TransactionKind::Payment, to, amount as i64, Some(from), None)
+	];
+	apply(config, db, guild_id, &mut transactions, &[])?;
+	let [transaction, _] = transactions;
+	Ok(transaction)
+}
+
+/// Members that have money with how much they have, from the most
+pub fn leaderboard(db: &Database, guild_id: GuildId) -> Vec<(UserId, u64)> {
+	let mut members: Vec<(UserId, u64)> = db.entries(&["guilds", guild_id.to_string().as_str(), "balances"])
+		.filter_map(|(id, balance)| Some((UserId(id.parse().ok()?), balance.as_u64()?)))
+		.collect();
+	members.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+	members
+}
+
+/// Something that's sold in a server's shop
+pub struct Item {
+	pub id: String,
+	pub name: String,
+	pub description: Option<String>,
+	pub price: u64,
+	/// Role that buyers get, instead of the item going to their inventory
+	pub role_id: Option<RoleId>,
+	/// How many are left, or None if there's no limit
+	pub stock: Option<u64>
+}
+
+impl Item {
+	pub fn new(name: String, price: u64) -> Self {
+		Self {
+			id: errors::new_id(),
+			name,
+			description: None,
+			price,
+			role_id: None,
+			stock: None
+		}
+	}
+	fn from_value(id: &str, value: &Value) -> Option<Self> {
+		Some(Self {
+			id: id.to_string(),
+			name: value["name"].as_str()?.to_string(),
+			description: value["description"].as_str().map(|v| v.to_string()),
+			price: value["price"].as_u64()?,
+			role_id: value["role_id"].as_str().and_then(|v| v.parse().ok()).map(RoleId),
+			stock: value["stock"].as_u64()
+		})
+	}
+	fn to_value(&self) -> Value {
+		serde_json::json!({
+			"name": self.name,
+			"description": self.description,
+			"price": self.price,
+			"role_id": self.role_id.map(|v| v.to_string()),
+			"stock": self.stock
+		})
+	}
+}
+
+/// The server's shop, from the cheapest item
+pub fn items(db: &Database, guild_id: GuildId) -> Vec<Item> {
+	let mut items: Vec<Item> = db.entries(&["guilds", guild_id.to_string().as_str(), "shop"])
+		.filter_map(|(id, v)| Item::from_value(id, v))
+		.collect();
+	items.sort_by(|a, b| a.price.cmp(&b.price).then(a.name.cmp(&b.name)));
+	items
+}
+
+/// Finds an item of the server's shop by its name, ignoring case
+pub fn find_item(db: &Database, guild_id: GuildId, name: &str) -> Option<Item> {
+	let name = name.trim().to_lowercase();
+	items(db, guild_id).into_iter().find(|v| v.name.to_lowercase() == name)
+}
+
+pub fn save_item(db: &mut Database, guild_id: GuildId, item: &Item) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "shop", item.id.as_str()], item.to_value())
+}
+
+pub fn remove_item(db: &mut Database, guild_id: GuildId, id: &str) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "shop", id], Value::Null)
+}
+
+/// Items the member bought with how many they have, by name. Roles aren't
+/// kept here, since the member has them instead
+pub fn inventory(db: &Database, guild_id: GuildId, user_id: UserId) -> Vec<(String, u64)> {
+	let mut items: Vec<(String, u64)> = db.entries(&["guilds", guild_id.to_string().as_str(), "inventories", user_id.to_string().as_str()])
+		.filter_map(|(_, v)| Some((v["name"].as_str()?.to_string(), v["count"].as_u64()?)))
+		.collect();
+	items.sort();
+	items
+}
+
+/// Takes the price of the item from the member, and gives them the item at
+/// the same time unless it's a role, which the caller gives
+pub fn buy(config: &Value, db: &mut Database, guild_id: GuildId, user_id: UserId, item: &Item) -> Result<Transaction, EconomyError> {
+	if item.stock == Some(0) {
+		return Err(EconomyError::SoldOut);
+	}
+	let (guild, user) = (guild_id.to_string(), user_id.to_string());
+	let inventory_path = ["guilds", guild.as_str(), "inventories", user.as_str(), item.id.as_str()];
+	let shop_path = ["guilds", guild.as_str(), "shop", item.id.as_str()];
+	let mut also: Vec<(&[&str], Value)> = Vec::new();
+	if let Some(stock) = item.stock {
+		let mut value = item.to_value();
+		value["stock"] = (stock - 1).into();
+		also.push((&shop_path, value));
+	}
+	if item.role_id.is_none() {
+		let count = db.get(&inventory_path)["count"].as_u64().unwrap_or(0);
+		also.push((&inventory_path, serde_json::json!({
+			"name": item.name,
+			"count": count + 1
+		})));
+	}
+	let mut transaction = [Transaction::new(TransactionKind::Purchase, user_id, -(item.price as i64), None, Some(item.name.clone()))];
+	apply(config, db, guild_id, &mut transaction, &also)?;
+	let [transaction] = transaction;
+	Ok(transaction)
+}
+
+/// Gives back the price of a role the member couldn't be given, and puts it
+/// back in stock if it's still in the shop
+pub fn refund(config: &Value, db: &mut Database, guild_id: GuildId, user_id: UserId, item: &Item) -> Result<Transaction, EconomyError> {
+	let guild = guild_id.to_string();
+	let shop_path = ["guilds", guild.as_str(), "shop", item.id.as_str()];
+	let mut also: Vec<(&[&str], Value)> = Vec::new();
+	if let Some(mut current) = Item::from_value(&item.id, db.get(&shop_path)) {
+		if let Some(stock) = current.stock {
+			current.stock = Some(stock + 1);
+			also.push((&shop_path, current.to_value()));
+		}
+	}
+	let mut transaction = [Transaction::new(TransactionKind::Refund, user_id, item.price as i64, None, Some(item.name.clone()))];
+	apply(config, db, guild_id, &mut transaction, &also)?;
+	let [transaction] = transaction;
+	Ok(transaction)
+}
@@ -0,0 +1,133 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::Value;
+use super::db::Database;
+use super::hooks::CommandInfo;
+use super::time;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Makes a short random ID, like "K3Q9ZT1B", that users can tell the owners about
+pub fn new_id() -> String {
+	let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+	let mut hasher = RandomState::new().build_hasher();
+	hasher.write_u64(nanos);
+	hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+	let mut n = hasher.finish();
+	let digits = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+	let mut id = String::new();
+	for _ in 0..8 {
+		id.push(digits[(n % 36) as usize] as char);
+		n /= 36;
+	}
+	id
+}
+
+pub struct ErrorReport {
+	pub id: String,
+	pub command: String,
+	pub source: String,
+	pub args: String,
+	pub user_id: u64,
+	pub user_name: String,
+	pub guild_id: Option<u64>,
+	pub channel_id: u64,
+	/// What the user was told
+	pub message: String,
+	/// Internal details of the error
+	pub error: String,
+	/// UNIX timestamp of when the error happened
+	pub time: u64
+}
+
+impl ErrorReport {
+	pub fn new(info: &CommandInfo, message: &str, error: &str) -> Self {
+		Self {
+			id: info.id.clone(),
+			command: info.name.clone(),
+			source: info.source.to_string(),
+			args: info.args.clone(),
+			user_id: info.author.id.0,
+			user_name: info.author.tag(),
+			guild_id: info.guild_id.map(|v| v.0),
+			channel_id: info.channel_id.0,
+			message: message.to_string(),
+			error: error.to_string(),
+			time: time::now()
+		}
+	}
+	fn to_value(&self) -> Value {
+		serde_json::json!({
+			"command": self.command,
+			"source": self.source,
+			"args": self.args,
+			"user_id": self.user_id.to_string(),
+			"user_name": self.user_name,
+			"guild_id": self.guild_id.map(|v| v.to_string()),
+			"channel_id": self.channel_id.to_string(),
+			"message": self.message,
+			"error": self.error,
+			"time": self.time
+		})
+	}
+	fn from_value(id: &str, value: &Value) -> Self {
+		let string = |key: &str| value[key].as_str().unwrap_or_default().to_string();
+		let id_of = |key: &str| value[key].as_str().and_then(|v| v.parse().ok());
+		Self {
+			id: id.to_string(),
+			command: string("command"),
+			source: string("source"),
+			args: string("args"),
+			user_id: id_of("user_id").unwrap_or(0),
+			user_name: string("user_name"),
+			guild_id: id_of("guild_id"),
+			channel_id: id_of("channel_id").unwrap_or(0),
+			message: string("message"),
+			error: string("error"),
+			time: value["time"].as_u64().unwrap_or(0)
+		}
+	}
+	/// Embed fields describing the error, for the error channel and the errorinfo command
+	pub fn fields(&self) -> Vec<(String, String, bool)> {
+		let or_none = |v: &str| if v.is_empty() { "None".to_string() } else { format!("```\n{}\n```", v.chars().take(1000).collect::<String>()) };
+		vec![
+			("Command".to_string(), format!("{} ({})", self.command, self.source), true),
+			("User".to_string(), format!("{} (`{}`)", self.user_name, self.user_id), true),
+			("Server".to_string(), match self.guild_id {
+				Some(v) => format!("`{}`", v),
+				None => "DMs".to_string()
+			}, true),
+			("Channel".to_string(), format!("<#{}> (`{}`)", self.channel_id, self.channel_id), true),
+			("Time".to_string(), time::discord_timestamp(self.time, "f"), true),
+			("Arguments".to_string(), or_none(&self.args), false),
+			("Message".to_string(), or_none(&self.message), false),
+			("Error".to_string(), or_none(&self.error), false)
+		]
+	}
+}
+
+/// Stores the report, and forgets the oldest ones once there are more than `max`
+pub fn store(db: &mut Database, report: &ErrorReport, max: usize) -> Result<(), String> {
+	db.set(&["errors", report.id.as_str()], report.to_value())?;
+	let mut reports: Vec<(String, u64)> = match db.get(&["errors"]) {
+		Value::Object(v) => v.iter().map(|(id, r)| (id.clone(), r["time"].as_u64().unwrap_or(0))).collect(),
+		_ => Vec::new()
+	};
+	if reports.len() > max {
+		reports.sort_by_key(|(_, time)| *time);
+		for (id, _) in reports.iter().take(reports.len() - max) {
+			db.set(&["errors", id.as_str()], Value::Null)?;
+		}
+	}
+	Ok(())
+}
+
+pub fn get(db: &Database, id: &str) -> Option<ErrorReport> {
+	let id = id.to_uppercase();
+	match db.get(&["errors", id.as_str()]) {
+		Value::Null => None,
+		v => Some(ErrorReport::from_value(&id, v))
+	}
+}
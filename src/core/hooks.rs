@@ -0,0 +1,194 @@
+use std::fmt;
+use std::pin::Pin;
+use std::future::Future;
+use std::sync::{RwLock, Arc};
+use std::time::{Duration, Instant};
+use serenity::model::user::User;
+use serenity::model::id::{ChannelId, GuildId};
+use super::db::Database;
+use serde_json::Value;
+use super::errors::{self, ErrorReport};
+use super::{ChloeManager, Command, CommandParams, CommOptions, CommRes, CommErr, member_permissions};
+
+/// Runs before a command. Returning an error stops the command from running,
+/// and the error is shown to the user
+pub type BeforeHook<'a> = for<'b> fn(chloe: &'b ChloeManager<'a>, command: &'b Command<'a>, params: &'b CommandParams) -> Pin<Box<dyn Future<Output = Result<(), CommErr>> + Send + 'b>>;
+/// Runs after a command (or after a before hook stopped it), with its result
+/// and how long it took
+pub type AfterHook<'a> = for<'b> fn(chloe: &'b ChloeManager<'a>, info: &'b CommandInfo, result: &'b Result<CommRes<'a>, CommErr>, elapsed: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + 'b>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+	Prefix,
+	Slash
+}
+
+impl fmt::Display for Source {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Source::Prefix => write!(f, "prefix"),
+			Source::Slash => write!(f, "slash")
+		}
+	}
+}
+
+/// What after hooks get to know about an invocation, since the command's
+/// `CommandParams` are gone by the time they run
+pub struct CommandInfo {
+	pub id: String,
+	pub name: String,
+	pub source: Source,
+	/// The arguments the command was given, as text
+	pub args: String,
+	pub author: User,
+	pub guild_id: Option<GuildId>,
+	pub channel_id: ChannelId,
+	pub ctx: serenity::client::Context,
+	pub db: Arc<RwLock<Database>>
+}
+
+impl CommandInfo {
+	pub fn new(command: &Command, params: &CommandParams) -> Self {
+		Self {
+			id: params.id.clone(),
+			name: command.names[0].clone(),
+			source: match params.inter {
+				Some(..) => Source::Slash,
+				None => Source::Prefix
+			},
+			args: match &params.msg {
+				Some(msg) => msg.content.split_once(char::is_whitespace).map(|(_, v)| v.trim().to_string()).unwrap_or_default(),
+				None => describe_options(&params.options)
+			},
+			author: params.author.clone(),
+			guild_id: params.guild_id,
+			channel_id: params.channel_id,
+			ctx: params.ctx.clone(),
+			db: params.db.clone()
+		}
+	}
+}
+
+/// Writes slash command options as text, like "add user: 123 reason: spam"
+fn describe_options(options: &CommOptions) -> String {
+	let mut parts = Vec::new();
+	for option in options.0.values() {
+		match &option.value {
+			Some(Value::String(v)) => parts.push(format!("{}: {}", option.name, v)),
+			Some(v) => parts.push(format!("{}: {}", option.name, v)),
+			None => {
+				parts.push(option.name.clone());
+				let sub_options = describe_options(&option.options);
+				if !sub_options.is_empty() {
+					parts.push(sub_options);
+				}
+			}
+		}
+	}
+	parts.join(" ")
+}
+
+/// Makes sure only the bot's owners use owner commands
+pub fn check_owner<'a, 'b>(chloe: &'b ChloeManager<'a>, command: &'b Command<'a>, params: &'b CommandParams) -> Pin<Box<dyn Future<Output = Result<(), CommErr>> + Send + 'b>> {
+	Box::pin(async move {
+		if command.owner_only && !chloe.is_owner(params.author.id) {
+			return Err(CommErr::Error("Only my owners can use this command".to_string(), String::new()));
+		}
+		Ok(())
+	})
+}
+
+/// Makes sure the member has the command's permissions
+pub fn check_permissions<'a, 'b>(_chloe: &'b ChloeManager<'a>, command: &'b Command<'a>, params: &'b CommandParams) -> Pin<Box<dyn Future<Output = Result<(), CommErr>> + Send + 'b>> {
+	Box::pin(async move {
+		if command.perms.is_empty() {
+			return Ok(());
+		}
+		let (guild_id, member) = match (params.guild_id, &params.member) {
+			(Some(guild_id), Some(member)) => (guild_id, member),
+			_ => return Err(CommErr::Error("This command can only be used in servers".to_string(), String::new()))
+		};
+		let perms = match member_permissions(&params.ctx, guild_id, member).await {
+			Ok(v) => v,
+			Err(e) => return Err(CommErr::Error(String::new(), format!("{e}")))
+		};
+		if !perms.contains(command.perms) {
+			let missing = command.perms - perms;
+			return Err(CommErr::Error(format!("You need the following permissions to use this command: {}", missing.get_permission_names().join(", ")), String::new()));
+		}
+		Ok(())
+	})
+}
+
+/// Makes sure the user isn't using the command again too soon
+pub fn check_cooldown<'a, 'b>(chloe: &'b ChloeManager<'a>, command: &'b Command<'a>, params: &'b CommandParams) -> Pin<Box<dyn Future<Output = Result<(), CommErr>> + Send + 'b>> {
+	Box::pin(async move {
+		if command.cooldown.is_zero() {
+			return Ok(());
+		}
+		let cooldowns = chloe.cooldowns.lock().unwrap();
+		if let Some(last) = cooldowns.get(&(command.names[0].clone(), params.author.id)) {
+			let elapsed = last.elapsed();
+			if elapsed < command.cooldown {
+				return Err(CommErr::Error(format!("Please wait {} more seconds before using this command again", (command.cooldown - elapsed).as_secs() + 1), String::new()));
+			}
+		}
+		Ok(())
+	})
+}
+
+/// Starts the user's cooldown once the command worked, so mistakes like
+/// syntax errors don't make them wait
+pub fn record_cooldown<'a, 'b>(chloe: &'b ChloeManager<'a>, info: &'b CommandInfo, result: &'b Result<CommRes<'a>, CommErr>, elapsed: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + 'b>> {
+	Box::pin(async move {
+		let cooldown = match chloe.command(&info.name) {
+			Some(command) if !command.cooldown.is_zero() && result.is_ok() => command.cooldown,
+			_ => return
+		};
+		let mut cooldowns = chloe.cooldowns.lock().unwrap();
+		cooldowns.retain(|(name, _), last| name != &info.name || last.elapsed() < cooldown);
+		// From when the command started, like the user would count it
+		cooldowns.insert((info.name.clone(), info.author.id), Instant::now() - elapsed);
+	})
+}
+
+/// Logs the internal details of errors
+pub fn log_errors<'a, 'b>(_chloe: &'b ChloeManager<'a>, _info: &'b CommandInfo, result: &'b Result<CommRes<'a>, CommErr>, _elapsed: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + 'b>> {
+	Box::pin(async move {
+		match result {
+			Err(CommErr::Error(e1, e2)) if !e2.is_empty() => tracing::error!(message = %e1, error = %e2, "Command failed"),
+			Err(CommErr::UnknownError) => tracing::error!("Command failed with an unknown error"),
+			_ => ()
+		}
+	})
+}
+
+/// Stores errors that have internal details under their reference ID, and
+/// posts them to the error channel, if there is one
+pub fn report_errors<'a, 'b>(chloe: &'b ChloeManager<'a>, info: &'b CommandInfo, result: &'b Result<CommRes<'a>, CommErr>, _elapsed: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + 'b>> {
+	Box::pin(async move {
+		let report = match result {
+			Err(CommErr::Error(e1, e2)) if !e2.is_empty() => ErrorReport::new(info, e1, e2),
+			Err(CommErr::UnknownError) => ErrorReport::new(info, "", ""),
+			_ => return
+		};
+		let max = chloe.config["max_error_reports"].as_u64().unwrap_or(1000) as usize;
+		if let Err(e) = errors::store(&mut info.db.write().unwrap(), &report, max) {
+			tracing::error!(error = %e, "Unable to store the error report");
+		}
+		let channel_id = match chloe.config["error_channel"].as_str().and_then(|v| v.parse().ok()) {
+			Some(v) => ChannelId(v),
+			None => return
+		};
+		let sent = channel_id.send_message(info.ctx.http.as_ref(), |m| {
+			m.add_embed(|e| {
+				e.title(format!("Error {}", report.id))
+				.fields(report.fields())
+				.color(chloe.config["bad_color"].as_i64().unwrap() as i32)
+			})
+		}).await;
+		if let Err(e) = sent {
+			tracing::error!(error = %e, "Unable to post the error report");
+		}
+	})
+}
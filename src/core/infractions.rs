@@ -0,0 +1,315 @@
+use std::sync::RwLock;
+use serde_json::Value;
+use serenity::builder::EditMember;
+use serenity::client::Context;
+use serenity::model::Timestamp;
+use serenity::model::id::{GuildId, UserId};
+use super::db::Database;
+use super::errors;
+use super::scheduler::{self, Job};
+use super::time;
+
+/// Kind of the scheduled jobs that lift temporary bans
+pub const UNBAN_JOB: &str = "unban";
+/// Longest timeout Discord allows, in seconds
+pub const MAX_TIMEOUT: u64 = 28 * 24 * 60 * 60;
+/// Longest temporary ban, in seconds. Longer ones might as well be forever
+pub const MAX_BAN: u64 = 366 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfractionKind {
+	Warn,
+	Timeout,
+	Untimeout,
+	Kick,
+	Ban,
+	Unban
+}
+
+impl InfractionKind {
+	pub fn from_key(key: &str) -> Option<Self> {
+		match key {
+			"warn" => Some(InfractionKind::Warn),
+			"timeout" => Some(InfractionKind::Timeout),
+			"untimeout" => Some(InfractionKind::Untimeout),
+			"kick" => Some(InfractionKind::Kick),
+			"ban" => Some(InfractionKind::Ban),
+			"unban" => Some(InfractionKind::Unban),
+			_ => None
+		}
+	}
+	/// Key of this kind of infraction in the database
+	pub fn key(&self) -> &'static str {
+		match self {
+			InfractionKind::Warn => "warn",
+			InfractionKind::Timeout => "timeout",
+			InfractionKind::Untimeout => "untimeout",
+			InfractionKind::Kick => "kick",
+			InfractionKind::Ban => "ban",
+			InfractionKind::Unban => "unban"
+		}
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			InfractionKind::Warn => "Warning",
+			InfractionKind::Timeout => "Timeout",
+			InfractionKind::Untimeout => "Timeout removed",
+			InfractionKind::Kick => "Kick",
+			InfractionKind::Ban => "Ban",
+			InfractionKind::Unban => "Unban"
+		}
+	}
+}
+
+/// Something a member of a server was warned or punished for. Moderation
+/// actions that lift punishments, like unbans, are kept here too so that the
+/// history is complete
+pub struct Infraction {
+	pub id: String,
+	pub kind: InfractionKind,
+	pub user_id: UserId,
+	/// Who issued it. Automatic ones are issued by me
+	pub moderator_id: UserId,
+	pub reason: Option<String>,
+	/// UNIX timestamp of when it was issued
+	pub created: u64,
+	/// How long the punishment lasts, in seconds, if it ends by itself
+	pub duration: Option<u64>
+}
+
+impl Infraction {
+	pub fn new(kind: InfractionKind, user_id: UserId, moderator_id: UserId, reason: Option<String>, duration: Option<u64>) -> Self {
+		Self {
+			id: errors::new_id(),
+			kind,
+			user_id,
+			moderator_id,
+			reason,
+			created: time::now(),
+			duration
+		}
+	}
+	fn from_value(id: &str, user_id: UserId, value: &Value) -> Option<Self> {
+		Some(Self {
+			id: id.to_string(),
+			kind: InfractionKind::from_key(value["kind"].as_str()?)?,
+			user_id,
+			moderator_id: UserId(value["moderator_id"].as_str()?.parse().ok()?),
+			reason: value["reason"].as_str().map(|v| v.to_string()),
+			created: value["created"].as_u64().unwrap_or(0),
+			duration: value["duration"].as_u64()
+		})
+	}
+}
+
+pub fn add(db: &mut Database, guild_id: GuildId, infraction: &Infraction) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "infractions", infraction.user_id.to_string().as_str(), infraction.id.as_str()], serde_json::json!({
+		"kind": infraction.kind.key(),
+		"moderator_id": infraction.moderator_id.to_string(),
+		"reason": infraction.reason,
+		"created": infraction.created,
+		"duration": infraction.duration
+	}))
+}
+
+/// Returns the member's infractions, oldest first
+pub fn list(db: &Database, guild_id: GuildId, user_id: UserId) -> Vec<Infraction> {
+	let mut infractions = Vec::new();
+	if let Value::Object(entries) = db.get(&["guilds", guild_id.to_string().as_str(), "infractions", user_id.to_string().as_str()]) {
+		for (id, value) in entries.iter() {
+			if let Some(infraction) = Infraction::from_value(id, user_id, value) {
+				infractions.push(infraction);
+			}
+		}
+	}
+	infractions.sort_by_key(|v| v.created);
+	infractions
+}
+
+/// How many warnings the member has
+pub fn warnings(db: &Database, guild_id: GuildId, user_id: UserId) -> usize {
+	list(db, guild_id, user_id).iter().filter(|v| v.kind == InfractionKind::Warn).count()
+}
+
+/// Finds an infraction of any member of the server by its ID
+pub fn get(db: &Database, guild_id: GuildId, id: &str) -> Option<Infraction> {
+	if let Value::Object(users) = db.get(&["guilds", guild_id.to_string().as_str(), "infractions"]) {
+		for (user_id, entries) in users.iter() {
+			let value = &entries[id];
+			if value.is_null() {
+				continue;
+			}
+			return Infraction::from_value(id, UserId(user_id.parse().ok()?), value);
+		}
+	}
+	None
+}
+
+pub fn remove(db: &mut Database, guild_id: GuildId, infraction: &Infraction) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "infractions", infraction.user_id.to_string().as_str(), infraction.id.as_str()], Value::Null)
+}
+
+/// Removes the member's warnings, keeping their other infractions. Returns how
+/// many there were
+pub fn clear_warnings(db: &mut Database, guild_id: GuildId, user_id: UserId) -> Result<usize, String> {
+	let warnings: Vec<Infraction> = list(db, guild_id, user_id).into_iter().filter(|v| v.kind == InfractionKind::Warn).collect();
+	for warning in warnings.iter() {
+		remove(db, guild_id, warning)?;
+	}
+	Ok(warnings.len())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EscalationAction {
+	/// Times the member out for some seconds
+	Timeout(u64),
+	Kick,
+	/// Bans the member for some seconds, or forever
+	Ban(Option<u64>)
+}
+
+/// What happens automatically when a member reaches a number of warnings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Escalation {
+	pub warnings: u64,
+	pub action: EscalationAction
+}
+
+impl Escalation {
+	/// Reads an entry like `{warnings: 3, action: "timeout", duration: "1h"}`
+	pub fn from_value(value: &Value) -> Option<Self> {
+		let duration = match &value["duration"] {
+			Value::String(v) => Some(time::parse_duration(v)?.as_secs()),
+			Value::Number(v) => Some(v.as_u64()?),
+			_ => None
+		};
+		let action = match value["action"].as_str()? {
+			"timeout" => EscalationAction::Timeout(duration?),
+			"kick" => EscalationAction::Kick,
+			"ban" => EscalationAction::Ban(duration),
+			_ => return None
+		};
+		Some(Self {
+			warnings: value["warnings"].as_u64().filter(|v| *v > 0)?,
+			action
+		})
+	}
+	pub fn to_value(&self) -> Value {
+		let (action, duration) = match self.action {
+			EscalationAction::Timeout(duration) => ("timeout", Some(duration)),
+			EscalationAction::Kick => ("kick", None),
+			EscalationAction::Ban(duration) => ("ban", duration)
+		};
+		serde_json::json!({
+			"warnings": self.warnings,
+			"action": action,
+			"duration": duration
+		})
+	}
+	/// Describes the action, like "a 1h timeout"
+	pub fn describe(&self) -> String {
+		match self.action {
+			EscalationAction::Timeout(duration) => format!("a {} timeout", time::format_duration(std::time::Duration::from_secs(duration))),
+			EscalationAction::Kick => "a kick".to_string(),
+			EscalationAction::Ban(Some(duration)) => format!("a {} ban", time::format_duration(std::time::Duration::from_secs(duration))),
+			EscalationAction::Ban(None) => "a ban".to_string()
+		}
+	}
+}
+
+/// The server's escalations, by number of warnings. Servers that haven't
+/// changed them use the config's `warn_escalation`
+pub fn escalations(config: &Value, db: &Database, guild_id: GuildId) -> Vec<Escalation> {
+	let entries = match db.get(&["guilds", guild_id.to_string().as_str(), "settings", "warn_escalation"]) {
+		Value::Array(v) => v,
+		_ => match &config["warn_escalation"] {
+			Value::Array(v) => v,
+			_ => return Vec::new()
+		}
+	};
+	let mut escalations: Vec<Escalation> = entries.iter().filter_map(|v| {
+		let escalation = Escalation::from_value(v);
+		if escalation.is_none() {
+			tracing::warn!(entry = %v, "Invalid warn escalation");
+		}
+		escalation
+	}).collect();
+	escalations.sort_by_key(|v| v.warnings);
+	escalations.dedup_by_key(|v| v.warnings);
+	escalations
+}
+
+/// Replaces the server's escalations, or goes back to the config's with None
+pub fn set_escalations(db: &mut Database, guild_id: GuildId, escalations: Option<&[Escalation]>) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "settings", "warn_escalation"], match escalations {
+		Some(escalations) => Value::Array(escalations.iter().map(|v| v.to_value()).collect()),
+		None => Value::Null
+	})
+}
+
+/// Replaces when a user's ban ends with `duration` seconds from now, or makes
+/// it not end by itself with None. Returns when it ends
+pub fn schedule_unban(db: &mut Database, guild_id: GuildId, user_id: UserId, duration: Option<u64>) -> Result<Option<u64>, String> {
+	let (guild, user) = (guild_id.to_string(), user_id.to_string());
+	let until = match duration {
+		Some(duration) => Some(time::now().checked_add(duration).ok_or("The ban is too long")?),
+		None => None
+	};
+	for job in scheduler::list(db, Some(UNBAN_JOB)) {
+		if job.payload["guild_id"].as_str() == Some(guild.as_str()) && job.payload["user_id"].as_str() == Some(user.as_str()) {
+			scheduler::cancel(db, &job.id)?;
+		}
+	}
+	match until {
+		Some(until) => {
+			let job = Job::once(UNBAN_JOB, serde_json::json!({
+				"guild_id": guild,
+				"user_id": user
+			}), until);
+			scheduler::schedule(db, &job)?;
+			Ok(Some(job.next_run))
+		},
+		None => Ok(None)
+	}
+}
+
+/// Times the member out until a UNIX timestamp, or removes their timeout with None
+pub async fn timeout_member(ctx: &Context, guild_id: GuildId, user_id: UserId, until: Option<u64>, audit_reason: &str) -> serenity::Result<()> {
+	let mut edit = EditMember::default();
+	match until {
+		Some(until) => edit.disable_communication_until_datetime(Timestamp::from_unix_timestamp(until as i64).map_err(|_| serenity::Error::Other("Invalid timestamp"))?),
+		None => edit.enable_communication()
+	};
+	let map = serenity::json::hashmap_to_json_map(edit.0);
+	ctx.http.edit_member(guild_id.0, user_id.0, &map, Some(audit_reason)).await?;
+	Ok(())
+}
+
+/// Gives the member the punishment of the escalation they reached, and adds
+/// it to their history as issued by me. Returns what happened to them, like
+/// "kicked"
+pub async fn escalate(ctx: &Context, db: &RwLock<Database>, guild_id: GuildId, user_id: UserId, escalation: &Escalation, audit_reason: &str) -> Result<String, String> {
+	let (kind, duration, text) = match escalation.action {
+		EscalationAction::Timeout(duration) => {
+			let until = time::now() + duration.min(MAX_TIMEOUT);
+			timeout_member(ctx, guild_id, user_id, Some(until), audit_reason).await.map_err(|e| format!("{e}"))?;
+			(InfractionKind::Timeout, Some(duration), format!("timed out until {}", time::discord_timestamp(until, "f")))
+		},
+		EscalationAction::Kick => {
+			guild_id.kick_with_reason(&ctx.http, user_id, audit_reason).await.map_err(|e| format!("{e}"))?;
+			(InfractionKind::Kick, None, "kicked".to_string())
+		},
+		EscalationAction::Ban(duration) => {
+			let duration = duration.map(|v| v.min(MAX_BAN));
+			guild_id.ban_with_reason(&ctx.http, user_id, 0, audit_reason).await.map_err(|e| format!("{e}"))?;
+			match schedule_unban(&mut db.write().unwrap(), guild_id, user_id, duration)? {
+				Some(until) => (InfractionKind::Ban, duration, format!("banned until {}", time::discord_timestamp(until, "f"))),
+				None => (InfractionKind::Ban, None, "banned".to_string())
+			}
+		}
+	};
+	let me = ctx.http.get_current_user().await.map_err(|e| format!("{e}"))?.id;
+	let reason = format!("Reached {} warnings", escalation.warnings);
+	add(&mut db.write().unwrap(), guild_id, &Infraction::new(kind, user_id, me, Some(reason), duration))?;
+	Ok(text)
+}
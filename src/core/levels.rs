@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{RwLock, Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde_json::Value;
+use serenity::client::Context;
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
+use super::db::Database;
+use super::ChloeManager;
+
+/// Longest cooldown between messages that give XP, in seconds
+pub const MAX_COOLDOWN: u64 = 60 * 60;
+/// Highest multiplier of a channel or role
+pub const MAX_MULTIPLIER: f64 = 5.0;
+const DEFAULT_MESSAGE: &str = "{mention} reached level **{level}**!";
+
+/// Where level-ups are announced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Announce {
+	Off,
+	/// In the channel of the message that leveled the member up
+	Here,
+	Channel(ChannelId)
+}
+
+/// How a server's leveling works
+pub struct Settings {
+	pub enabled: bool,
+	/// Range of XP that a message gives
+	pub xp: (u64, u64),
+	/// Seconds before a member's messages give XP again
+	pub cooldown: u64,
+	pub announce: Announce,
+	/// Text of level-up announcements with placeholders, or None for the default one
+	pub message: Option<String>,
+	/// Roles that members get when they reach levels, by level
+	pub rewards: Vec<(u64, RoleId)>,
+	pub channel_multipliers: HashMap<ChannelId, f64>,
+	pub role_multipliers: HashMap<RoleId, f64>,
+	/// Channels where messages don't give XP
+	pub ignored_channels: Vec<ChannelId>
+}
+
+impl Settings {
+	pub fn message(&self) -> &str {
+		self.message.as_deref().unwrap_or(DEFAULT_MESSAGE)
+	}
+	/// What XP is multiplied by in the channel for a member with the roles.
+	/// Of the roles, the one with the highest multiplier counts
+	pub fn multiplier(&self, channel_id: ChannelId, roles: &[RoleId]) -> f64 {
+		let channel = self.channel_multipliers.get(&channel_id).copied().unwrap_or(1.0);
+		let role = roles.iter().filter_map(|v| self.role_multipliers.get(v)).copied().reduce(f64::max).unwrap_or(1.0);
+		channel * role
+	}
+	/// Roles for the levels after `old` up to `new`
+	pub fn rewards_between(&self, old: u64, new: u64) -> Vec<RoleId> {
+		self.rewards.iter().filter(|(level, _)| *level > old && *level <= new).map(|(_, role_id)| *role_id).collect()
+	}
+}
+
+/// Reads a map of IDs to multipliers
+fn multipliers<T: From<u64> + std::hash::Hash + Eq>(value: &Value) -> HashMap<T, f64> {
+	value.as_object().map(|v| v.iter().filter_map(|(id, m)| Some((T::from(id.parse::<u64>().ok()?), m.as_f64()?))).collect()).unwrap_or_default()
+}
+
+/// The server's settings. Servers that haven't changed the XP range or the
+/// cooldown use the config's `levels`
+pub fn settings(config: &Value, db: &Database, guild_id: GuildId) -> Settings {
+	let value = db.get(&["guilds", guild_id.to_string().as_str(), "settings", "levels"]);
+	let get = |key: &str| match &value[key] {
+		Value::Null => &config["levels"][key],
+		v => v
+	};
+	let xp = match (get("xp")[0].as_u64(), get("xp")[1].as_u64()) {
+		(Some(min), Some(max)) if min <= max => (min, max),
+		_ => (15, 25)
+	};
+	let mut rewards: Vec<(u64, RoleId)> = value["rewards"].as_object().map(|v| v.iter().filter_map(|(level, role_id)| {
+		Some((level.parse().ok()?, RoleId(role_id.as_str()?.parse().ok()?)))
+	}).collect()).unwrap_or_default();
+	rewards.sort();
+	Settings {
+		enabled: value["enabled"].as_bool().unwrap_or(false),
+		xp,
+		cooldown: get("cooldown").as_u64().unwrap_or(60).min(MAX_COOLDOWN),
+		announce: match value["announce"].as_str() {
+			Some("off") => Announce::Off,
+			Some(channel_id) => channel_id.parse().map(|v| Announce::Channel(ChannelId(v))).unwrap_or(Announce::Here),
+			None => Announce::Here
+		},
+		message: value["message"].as_str().map(|v| v.to_string()),
+		rewards,
+		channel_multipliers: multipliers(&value["channel_multipliers"]),
+		role_multipliers: multipliers(&value["role_multipliers"]),
+		ignored_channels: value["ignored_channels"].as_array()
+			.map(|v| v.iter().filter_map(|v| v.as_str()?.parse().ok()).map(ChannelId).collect())
+			.unwrap_or_default()
+	}
+}
+
+pub fn set_settings(db: &mut Database, guild_id: GuildId, settings: &Settings) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "settings", "levels"], serde_json::json!({
+		"enabled": settings.enabled,
+		"xp": [settings.xp.0, settings.xp.1],
+		"cooldown": settings.cooldown,
+		"announce": match settings.announce {
+			Announce::Off => Some("off".to_string()),
+			Announce::Here => None,
+			Announce::Channel(channel_id) => Some(channel_id.to_string())
+		},
+		"message": settings.message,
+		"rewards": settings.rewards.iter().map(|(level, role_id)| (level.to_string(), Value::String(role_id.to_string()))).collect::<serde_json::Map<String, Value>>(),
+		"channel_multipliers": settings.channel_multipliers.iter().map(|(id, m)| (id.to_string(), Value::from(*m))).collect::<serde_json::Map<String, Value>>(),
+		"role_multipliers": settings.role_multipliers.iter().map(|(id, m)| (id.to_string(), Value::from(*m))).collect::<serde_json::Map<String, Value>>(),
+		"ignored_channels": settings.ignored_channels.iter().map(|v| v.to_string()).collect::<Vec<String>>()
+	}))
+}
+
+/// XP needed to go from a level to the next one
+pub fn level_xp(level: u64) -> u64 {
+	5 * level * level + 50 * level + 100
+}
+
+/// Total XP needed to reach a level
+pub fn total_xp(level: u64) -> u64 {
+	(0..level).map(level_xp).sum()
+}
+
+/// Level of a member with some XP
+pub fn level(xp: u64) -> u64 {
+	let mut level = 0;
+	let mut needed = level_xp(0);
+	let mut xp = xp;
+	while xp >= needed {
+		xp -= needed;
+		level += 1;
+		needed = level_xp(level);
+	}
+	level
+}
+
+pub fn xp(db: &Database, guild_id: GuildId, user_id: UserId) -> u64 {
+	db.get(&["guilds", guild_id.to_string().as_str(), "xp", user_id.to_string().as_str()]).as_u64().unwrap_or(0)
+}
+
+/// Sets the member's XP, forgetting them if it's 0
+pub fn set_xp(db: &mut Database, guild_id: GuildId, user_id: UserId, xp: u64) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "xp", user_id.to_string().as_str()], match xp {
+		0 => Value::Null,
+		xp => xp.into()
+	})
+}
+
+/// Members that have XP with their XP, from the most
+pub fn leaderboard(db: &Database, guild_id: GuildId) -> Vec<(UserId, u64)> {
+	let mut members: Vec<(UserId, u64)> = db.entries(&["guilds", guild_id.to_string().as_str(), "xp"])
+		.filter_map(|(id, xp)| Some((UserId(id.parse().ok()?), xp.as_u64()?)))
+		.collect();
+	members.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+	members
+}
+
+/// Position of the member in the leaderboard, starting at 1, and how many
+/// members it has. The position is None for members without XP
+pub fn rank(db: &Database, guild_id: GuildId, user_id: UserId) -> (Option<usize>, usize) {
+	let guild = guild_id.to_string();
+	let path = ["guilds", guild.as_str(), "xp"];
+	let total = db.count(&path);
+	let xp = xp(db, guild_id, user_id);
+	if xp == 0 {
+		return (None, total);
+	}
+	// Ties go to whoever has the lowest ID, like in the leaderboard
+	let ahead = db.entries(&path).filter(|(id, v)| {
+		let other = v.as_u64().unwrap_or(0);
+		other > xp || (other == xp && id.parse::<u64>().is_ok_and(|id| id < user_id.0))
+	}).count();
+	(Some(ahead + 1), total)
+}
+
+/// Random number from `min` to `max`
+fn random_between(min: u64, max: u64) -> u64 {
+	let mut hasher = RandomState::new().build_hasher();
+	hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos());
+	min + hasher.finish() % (max - min + 1)
+}
+
+/// When members last got XP
+#[derive(Default)]
+pub struct Levels {
+	last_xp: Mutex<HashMap<(GuildId, UserId), Instant>>
+}
+
+impl Levels {
+	/// Whether the member can get XP again, in which case their cooldown starts
+	fn take_cooldown(&self, guild_id: GuildId, user_id: UserId, cooldown: Duration) -> bool {
+		let mut last_xp = self.last_xp.lock().unwrap();
+		if last_xp.get(&(guild_id, user_id)).is_some_and(|v| v.elapsed() < cooldown) {
+			return false;
+		}
+		if last_xp.len() > 100000 {
+			last_xp.retain(|_, v| v.elapsed() < Duration::from_secs(MAX_COOLDOWN));
+		}
+		last_xp.insert((guild_id, user_id), Instant::now());
+		true
+	}
+}
+
+/// Gives the member roles for the levels they reached
+pub async fn give_rewards(ctx: &Context, guild_id: GuildId, user_id: UserId, roles: &[RoleId]) {
+	for role_id in roles {
+		if let Err(e) = ctx.http.add_member_role(guild_id.0, user_id.0, role_id.0, Some("Level reward")).await {
+			tracing::warn!(guild = %guild_id, role = %role_id, error = %e, "Unable to give a level reward");
+		}
+	}
+}
+
+/// Gives XP for the message, and announces it if its author leveled up
+pub async fn message(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, msg: &Message) {
+	let guild_id = match msg.guild_id {
+		Some(v) if !msg.author.bot => v,
+		_ => return
+	};
+	let settings = settings(&chloe.config, &db.read().unwrap(), guild_id);
+	if !settings.enabled || settings.ignored_channels.contains(&msg.channel_id) {
+		return;
+	}
+	if !chloe.levels.take_cooldown(guild_id, msg.author.id, Duration::from_secs(settings.cooldown)) {
+		return;
+	}
+	let roles = msg.member.as_ref().map(|m| m.roles.as_slice()).unwrap_or_default();
+	let gained = (random_between(settings.xp.0, settings.xp.1) as f64 * settings.multiplier(msg.channel_id, roles)).round() as u64;
+	if gained == 0 {
+		return;
+	}
+	let (old, new) = {
+		let mut db = db.write().unwrap();
+		let old = xp(&db, guild_id, msg.author.id);
+		if let Err(e) = set_xp(&mut db, guild_id, msg.author.id, old + gained) {
+			tracing::warn!(guild = %guild_id, error = %e, "Unable to give XP");
+			return;
+		}
+		(level(old), level(old + gained))
+	};
+	if new <= old {
+		return;
+	}
+	give_rewards(ctx, guild_id, msg.author.id, &settings.rewards_between(old, new)).await;
+	let channel_id = match settings.announce {
+		Announce::Off => return,
+		Announce::Here => msg.channel_id,
+		Announce::Channel(v) => v
+	};
+	let text = settings.message()
+		.replace("{user}", &msg.author.tag())
+		.replace("{mention}", &format!("<@{}>", msg.author.id))
+		.replace("{level}", &new.to_string());
+	let author_id = msg.author.id;
+	let sent = channel_id.send_message(&ctx.http, |m| {
+		m.content(text)
+		.allowed_mentions(|a| a.users(vec![author_id]))
+	}).await;
+	if let Err(e) = sent {
+		tracing::warn!(guild = %guild_id, channel = %channel_id, error = %e, "Unable to announce a level-up");
+	}
+}
@@ -0,0 +1,60 @@
+use serde_json::Value;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, filter, EnvFilter, Layer};
+use super::metrics::HttpTimer;
+
+/// Sets up logging according to the "log" entry of the config, along with
+/// `http_timer`, which sees serenity's requests whatever the log level is.
+/// The returned guard flushes the log file when dropped, so it must be kept
+/// alive for as long as the bot runs
+pub fn init(config: &Value, http_timer: HttpTimer) -> Result<Option<WorkerGuard>, String> {
+	let level = config["level"].as_str().unwrap_or("info");
+	// Plain levels only apply to the bot, so that its dependencies don't flood the logs
+	let directives = match level.contains('=') || level.contains(',') {
+		true => level.to_string(),
+		false => format!("warn,chloebot={}", level)
+	};
+	let filter = match EnvFilter::try_from_default_env() {
+		Ok(v) => v,
+		Err(..) => match EnvFilter::try_new(&directives) {
+			Ok(v) => v,
+			Err(e) => return Err(format!("Invalid log level \"{level}\": {e}"))
+		}
+	};
+	let stdout_layer = fmt::layer().with_target(false);
+	let (file_layer, guard) = match config["file"].as_str() {
+		Some(dir) => {
+			let rotation = match config["rotation"].as_str().unwrap_or("daily") {
+				"minutely" => Rotation::MINUTELY,
+				"hourly" => Rotation::HOURLY,
+				"daily" => Rotation::DAILY,
+				"never" => Rotation::NEVER,
+				v => return Err(format!("Invalid log rotation \"{v}\". It must be one of minutely, hourly, daily or never"))
+			};
+			let appender = match RollingFileAppender::builder()
+				.rotation(rotation)
+				.filename_prefix("chloebot")
+				.filename_suffix("log")
+				.build(dir) {
+				Ok(v) => v,
+				Err(e) => return Err(format!("Unable to open the log directory \"{dir}\": {e}"))
+			};
+			let (writer, guard) = tracing_appender::non_blocking(appender);
+			let layer = match config["json"].as_bool().unwrap_or(false) {
+				true => fmt::layer().json().with_current_span(true).with_span_list(false).with_writer(writer).boxed(),
+				false => fmt::layer().with_ansi(false).with_writer(writer).boxed()
+			};
+			(Some(layer), Some(guard))
+		},
+		None => (None, None)
+	};
+	if let Err(e) = tracing_subscriber::registry()
+		.with(stdout_layer.and_then(file_layer).with_filter(filter))
+		.with(http_timer.with_filter(filter::filter_fn(HttpTimer::is_request)))
+		.try_init() {
+		return Err(format!("Unable to set up logging: {e}"));
+	}
+	Ok(guard)
+}
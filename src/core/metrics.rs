@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::pin::Pin;
+use std::future::Future;
+use std::sync::{RwLock, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::Subscriber;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use super::db::Database;
+use super::hooks::{CommandInfo, Source};
+use super::{ChloeManager, CommRes, CommErr};
+
+/// Upper bounds of the latency histograms' buckets, in seconds
+const BUCKETS: [f64; 12] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+#[derive(Default)]
+struct Histogram {
+	/// How many observations fit in each bucket, cumulatively
+	buckets: [u64; BUCKETS.len()],
+	sum: f64,
+	count: u64
+}
+
+impl Histogram {
+	fn observe(&mut self, elapsed: Duration) {
+		let secs = elapsed.as_secs_f64();
+		for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+			if secs <= bound {
+				*bucket += 1;
+			}
+		}
+		self.sum += secs;
+		self.count += 1;
+	}
+	fn render(&self, out: &mut String, name: &str, labels: &str) {
+		for (bucket, bound) in self.buckets.iter().zip(BUCKETS) {
+			writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {bucket}").unwrap();
+		}
+		writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count).unwrap();
+		writeln!(out, "{name}_sum{{{labels}}} {}", self.sum).unwrap();
+		writeln!(out, "{name}_count{{{labels}}} {}", self.count).unwrap();
+	}
+}
+
+/// Numbers about the bot that are served in Prometheus' text format
+#[derive(Default)]
+pub struct Metrics {
+	commands: Mutex<HashMap<(String, Source), u64>>,
+	errors: Mutex<HashMap<(String, &'static str), u64>>,
+	command_latency: Mutex<HashMap<String, Histogram>>,
+	http_latency: Mutex<HashMap<String, Histogram>>,
+	/// Whether each shard is connected to the gateway, by shard ID
+	shards: Mutex<HashMap<u64, bool>>,
+	ready: AtomicBool
+}
+
+impl Metrics {
+	/// Counts a command that was run, or that failed before it could run
+	pub fn record_command(&self, command: &str, source: Source) {
+		*self.commands.lock().unwrap().entry((command.to_string(), source)).or_insert(0) += 1;
+	}
+	/// Counts an error. Errors of unknown commands are counted with an empty
+	/// command name, since users can type anything there
+	pub fn record_error(&self, command: &str, e: &CommErr) {
+		*self.errors.lock().unwrap().entry((command.to_string(), e.kind())).or_insert(0) += 1;
+	}
+	pub fn set_shard_connected(&self, shard_id: u64, connected: bool) {
+		self.shards.lock().unwrap().insert(shard_id, connected);
+	}
+	pub fn set_ready(&self) {
+		self.ready.store(true, Ordering::Relaxed);
+	}
+	/// Whether `ready` has fired
+	pub fn is_ready(&self) -> bool {
+		self.ready.load(Ordering::Relaxed)
+	}
+	fn render(&self, chloe: &ChloeManager, db: &Database) -> String {
+		let mut out = String::new();
+		writeln!(out, "# HELP chloebot_commands_total Commands run, including the ones that failed").unwrap();
+		writeln!(out, "# TYPE chloebot_commands_total counter").unwrap();
+		for ((command, source), count) in self.commands.lock().unwrap().iter() {
+			writeln!(out, "chloebot_commands_total{{command=\"{}\",source=\"{}\"}} {}", escape(command), source, count).unwrap();
+		}
+		writeln!(out, "# HELP chloebot_command_errors_total Command errors, by kind").unwrap();
+		writeln!(out, "# TYPE chloebot_command_errors_total counter").unwrap();
+		for ((command, kind), count) in self.errors.lock().unwrap().iter() {
+			writeln!(out, "chloebot_command_errors_total{{command=\"{}\",kind=\"{}\"}} {}", escape(command), kind, count).unwrap();
+		}
+		writeln!(out, "# HELP chloebot_command_duration_seconds How long commands took, including their hooks").unwrap();
+		writeln!(out, "# TYPE chloebot_command_duration_seconds histogram").unwrap();
+		for (command, histogram) in self.command_latency.lock().unwrap().iter() {
+			histogram.render(&mut out, "chloebot_command_duration_seconds", &format!("command=\"{}\"", escape(command)));
+		}
+		writeln!(out, "# HELP chloebot_discord_http_duration_seconds How long requests to Discord's HTTP API took").unwrap();
+		writeln!(out, "# TYPE chloebot_discord_http_duration_seconds histogram").unwrap();
+		for (route, histogram) in self.http_latency.lock().unwrap().iter() {
+			histogram.render(&mut out, "chloebot_discord_http_duration_seconds", &format!("route=\"{}\"", route));
+		}
+		writeln!(out, "# HELP chloebot_guilds Servers I'm in").unwrap();
+		writeln!(out, "# TYPE chloebot_guilds gauge").unwrap();
+		writeln!(out, "chloebot_guilds {}", chloe.guild_count()).unwrap();
+		writeln!(out, "# HELP chloebot_database_log_bytes Size of the database changes that haven't been applied to database.json yet").unwrap();
+		writeln!(out, "# TYPE chloebot_database_log_bytes gauge").unwrap();
+		writeln!(out, "chloebot_database_log_bytes {}", db.log_size()).unwrap();
+		writeln!(out, "# HELP chloebot_database_pending_writes Database changes that haven't been applied to database.json yet").unwrap();
+		writeln!(out, "# TYPE chloebot_database_pending_writes gauge").unwrap();
+		writeln!(out, "chloebot_database_pending_writes {}", db.pending_writes()).unwrap();
+		writeln!(out, "# HELP chloebot_gateway_connected Whether each shard is connected to the gateway").unwrap();
+		writeln!(out, "# TYPE chloebot_gateway_connected gauge").unwrap();
+		for (shard_id, connected) in self.shards.lock().unwrap().iter() {
+			writeln!(out, "chloebot_gateway_connected{{shard=\"{}\"}} {}", shard_id, *connected as u8).unwrap();
+		}
+		writeln!(out, "# HELP chloebot_ready Whether I have received the ready event").unwrap();
+		writeln!(out, "# TYPE chloebot_ready gauge").unwrap();
+		writeln!(out, "chloebot_ready {}", self.is_ready() as u8).unwrap();
+		writeln!(out, "# HELP chloebot_uptime_seconds How long I have been running for").unwrap();
+		writeln!(out, "# TYPE chloebot_uptime_seconds gauge").unwrap();
+		writeln!(out, "chloebot_uptime_seconds {}", chloe.uptime().as_secs()).unwrap();
+		out
+	}
+}
+
+/// Escapes a label value
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Counts commands, their errors and how long they took
+pub fn record_metrics<'a, 'b>(chloe: &'b ChloeManager<'a>, info: &'b CommandInfo, result: &'b Result<CommRes<'a>, CommErr>, elapsed: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + 'b>> {
+	Box::pin(async move {
+		let metrics = &chloe.metrics;
+		metrics.record_command(&info.name, info.source);
+		if let Err(e) = result {
+			metrics.record_error(&info.name, e);
+		}
+		metrics.command_latency.lock().unwrap().entry(info.name.clone()).or_default().observe(elapsed);
+	})
+}
+
+/// Times every request to Discord's HTTP API, wherever it's made, through
+/// the span that serenity opens for it
+pub struct HttpTimer(pub &'static Metrics);
+
+/// When a request started, and its route
+struct HttpRequest(Instant, String);
+
+impl HttpTimer {
+	/// Whether the span is the one serenity opens for a request
+	pub fn is_request(metadata: &tracing::Metadata) -> bool {
+		metadata.is_span() && metadata.target() == "serenity::http::client" && metadata.name() == "request"
+	}
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for HttpTimer {
+	fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+		let mut route = RouteName::default();
+		attrs.record(&mut route);
+		if let Some(span) = ctx.span(id) {
+			span.extensions_mut().insert(HttpRequest(Instant::now(), route.name));
+		}
+	}
+	fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+		let span = match ctx.span(&id) {
+			Some(v) => v,
+			None => return
+		};
+		let request = span.extensions_mut().remove::<HttpRequest>();
+		if let Some(HttpRequest(start, route)) = request {
+			self.0.http_latency.lock().unwrap().entry(route).or_default().observe(start.elapsed());
+		}
+	}
+}
+
+/// Picks the route's name, like CreateMessage, out of the debug output of the
+/// request, which ends with "route: CreateMessage { channel_id: ... } }". The
+/// rest isn't kept, since it has the whole body
+#[derive(Default)]
+struct RouteName {
+	/// How much of "route: " was just written
+	matched: usize,
+	name: String
+}
+
+impl Visit for RouteName {
+	fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+		if field.name() == "req" {
+			write!(self, "{:?}", value).ok();
+		}
+	}
+}
+
+impl Write for RouteName {
+	fn write_str(&mut self, s: &str) -> fmt::Result {
+		const MARKER: &str = "route: ";
+		for c in s.chars() {
+			if self.matched == MARKER.len() {
+				if c.is_ascii_alphanumeric() {
+					self.name.push(c);
+					continue;
+				}
+				self.matched = 0;
+			}
+			// The last match wins, since the route comes after the headers,
+			// which could have the marker in them too
+			if MARKER[self.matched..].starts_with(c) {
+				self.matched += 1;
+				if self.matched == MARKER.len() {
+					self.name.clear();
+				}
+			}
+			else {
+				self.matched = usize::from(c == 'r');
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Serves /metrics, /healthz and /readyz on `address`, like "127.0.0.1:9100".
+/// /readyz stops being ready once shutting down starts
+pub async fn serve(address: &str, chloe: &'static ChloeManager<'static>, db: Arc<RwLock<Database>>) -> std::io::Result<()> {
+	let listener = TcpListener::bind(address).await?;
+	tracing::info!(address, "Serving metrics");
+	loop {
+		let (stream, _) = match listener.accept().await {
+			Ok(v) => v,
+			Err(e) => {
+				tracing::warn!(error = %e, "Unable to accept a metrics connection");
+				continue;
+			}
+		};
+		let db = db.clone();
+		tokio::spawn(async move {
+			if let Err(e) = respond(stream, chloe, db).await {
+				tracing::debug!(error = %e, "Unable to answer a metrics request");
+			}
+		});
+	}
+}
+
+async fn respond(mut stream: TcpStream, chloe: &ChloeManager<'_>, db: Arc<RwLock<Database>>) -> std::io::Result<()> {
+	// Only the request line matters, so there's no need to read a big request
+	let mut request = Vec::new();
+	let mut buf = [0; 1024];
+	while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+		match tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf)).await {
+			Ok(Ok(0)) | Err(..) => break,
+			Ok(Ok(n)) => request.extend_from_slice(&buf[..n]),
+			Ok(Err(e)) => return Err(e)
+		}
+	}
+	let request = String::from_utf8_lossy(&request);
+	let mut parts = request.split_whitespace();
+	let method = parts.next().unwrap_or_default();
+	let path = parts.next().unwrap_or_default().split('?').next().unwrap_or_default();
+	let (status, content_type, body) = match (method, path) {
+		("GET" | "HEAD", "/metrics") => {
+			let body = chloe.metrics.render(chloe, &db.read().unwrap());
+			("200 OK", "text/plain; version=0.0.4; charset=utf-8", body)
+		},
+		("GET" | "HEAD", "/healthz") => ("200 OK", "text/plain; charset=utf-8", "ok\n".to_string()),
+		("GET" | "HEAD", "/readyz") => match chloe.metrics.is_ready() && !chloe.shutdown.is_requested() {
+			true => ("200 OK", "text/plain; charset=utf-8", "ready\n".to_string()),
+			false => ("503 Service Unavailable", "text/plain; charset=utf-8", "not ready\n".to_string())
+		},
+		("GET" | "HEAD", _) => ("404 Not Found", "text/plain; charset=utf-8", "not found\n".to_string()),
+		_ => ("405 Method Not Allowed", "text/plain; charset=utf-8", "method not allowed\n".to_string())
+	};
+	let mut response = format!("HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+	if method != "HEAD" {
+		response += &body;
+	}
+	stream.write_all(response.as_bytes()).await?;
+	stream.shutdown().await
+}
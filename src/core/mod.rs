@@ -1,21 +1,61 @@
 pub mod db;
+pub mod args;
+pub mod hooks;
+pub mod time;
+pub mod timezone;
+pub mod blocklist;
+pub mod automod;
+pub mod antiraid;
+pub mod infractions;
+pub mod logging;
+pub mod errors;
+pub mod stats;
+pub mod metrics;
+pub mod shutdown;
+pub mod shards;
+pub mod presence;
+pub mod scheduler;
+pub mod modlog;
+pub mod welcome;
+pub mod rolepanels;
+pub mod tags;
+pub mod levels;
+pub mod economy;
+pub mod polls;
 use std::fs;
 use std::io::Read;
 use std::path::Path;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use antiraid::AntiRaid;
+use automod::AutoMod;
 use db::Database;
-use std::sync::{RwLock, Arc};
+use hooks::{BeforeHook, AfterHook, CommandInfo, Source};
+use levels::Levels;
+use metrics::Metrics;
+use modlog::ModLog;
+use shutdown::Shutdown;
+use shards::ShardStatus;
+use scheduler::JobFn;
+use std::sync::{RwLock, Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use std::pin::Pin;
 use std::future::Future;
 use serde_json::Value;
-use serenity::builder::{CreateMessage, CreateComponents, CreateApplicationCommandOption, CreateInteractionResponseFollowup};
+use tracing::Instrument;
+use serenity::builder::{CreateMessage, EditMessage, CreateComponents, CreateApplicationCommandOption, CreateInteractionResponseFollowup};
 use serenity::model::channel::Message;
+use serenity::model::gateway::Activity;
 use serenity::model::guild::Member;
 use serenity::model::user::User;
+use serenity::model::Permissions;
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use serenity::model::event::MessageUpdateEvent;
+use serenity::client::bridge::gateway::ShardManager;
 use serenity::model::prelude::{Role, Attachment, PartialMember, PartialChannel};
 use serenity::model::prelude::command::CommandOptionType;
 use serenity::model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue};
-use super::CHLOE;
+use serenity::model::application::interaction::autocomplete::AutocompleteInteraction;
 
 pub struct CommOption {
 	pub name: String,
@@ -51,94 +91,84 @@ impl CommOptions {
 	}
 	pub fn get(&self, name: &str) -> Option<&CommandDataOptionValue> {
 		match self.0.get(name) {
-			Some(v) => match &v.resolved {
-				Some(v) => Some(v),
-				None => None
-			},
+			Some(v) => v.resolved.as_ref(),
 			None => None
 		}
 	}
 	pub fn get_string(&self, name: &str) -> Option<String> {
 		match self.get(name) {
-			Some(v) => match v {
-				CommandDataOptionValue::String(v) => Some(v.clone()),
-				_ => None
-			},
-			None => None
+			Some(CommandDataOptionValue::String(v)) => Some(v.clone()),
+			_ => None
 		}
 	}
 	pub fn get_int(&self, name: &str) -> Option<i64> {
 		match self.get(name) {
-			Some(v) => match v {
-				CommandDataOptionValue::Integer(v) => Some(*v),
-				_ => None
-			},
-			None => None
+			Some(CommandDataOptionValue::Integer(v)) => Some(*v),
+			_ => None
 		}
 	}
 	pub fn get_number(&self, name: &str) -> Option<f64> {
 		match self.get(name) {
-			Some(v) => match v {
-				CommandDataOptionValue::Number(v) => Some(*v),
-				_ => None
-			},
-			None => None
+			Some(CommandDataOptionValue::Number(v)) => Some(*v),
+			_ => None
 		}
 	}
 	pub fn get_bool(&self, name: &str) -> Option<bool> {
 		match self.get(name) {
-			Some(v) => match v {
-				CommandDataOptionValue::Boolean(v) => Some(*v),
-				_ => None
-			},
-			None => None
+			Some(CommandDataOptionValue::Boolean(v)) => Some(*v),
+			_ => None
 		}
 	}
 	pub fn get_user(&self, name: &str) -> Option<(&User, Option<&PartialMember>)> {
 		match self.get(name) {
-			Some(v) => match v {
-				CommandDataOptionValue::User(v0, v1) => Some((v0, v1.as_ref())),
-				_ => None
-			},
-			None => None
+			Some(CommandDataOptionValue::User(v0, v1)) => Some((v0, v1.as_ref())),
+			_ => None
 		}
 	}
 	pub fn get_channel(&self, name: &str) -> Option<&PartialChannel> {
 		match self.get(name) {
-			Some(v) => match v {
-				CommandDataOptionValue::Channel(v) => Some(v),
-				_ => None
-			},
-			None => None
+			Some(CommandDataOptionValue::Channel(v)) => Some(v),
+			_ => None
 		}
 	}
 	pub fn get_role(&self, name: &str) -> Option<&Role> {
 		match self.get(name) {
-			Some(v) => match v {
-				CommandDataOptionValue::Role(v) => Some(v),
-				_ => None
-			},
-			None => None
+			Some(CommandDataOptionValue::Role(v)) => Some(v),
+			_ => None
 		}
 	}
 	pub fn get_attachment(&self, name: &str) -> Option<&Attachment> {
 		match self.get(name) {
-			Some(v) => match v {
-				CommandDataOptionValue::Attachment(v) => Some(v),
-				_ => None
-			},
-			None => None
+			Some(CommandDataOptionValue::Attachment(v)) => Some(v),
+			_ => None
 		}
 	}
 	pub fn get_options(&self, name: &str) -> Option<&CommOptions> {
-		match self.0.get(name) {
-			Some(v) => Some(&v.options),
-			None => None
+		self.0.get(name).map(|v| &v.options)
+	}
+	/// Returns the name and options of the subcommand (or subcommand group) that was used
+	pub fn subcommand(&self) -> Option<(&str, &CommOptions)> {
+		self.0.values()
+			.find(|v| matches!(v.kind, CommandOptionType::SubCommand | CommandOptionType::SubCommandGroup))
+			.map(|v| (v.name.as_str(), &v.options))
+	}
+	/// Returns the option the user is currently typing in, when autocompleting
+	pub fn focused(&self) -> Option<&CommOption> {
+		for option in self.0.values() {
+			if option.focused {
+				return Some(option);
+			}
+			if let Some(v) = option.options.focused() {
+				return Some(v);
+			}
 		}
+		None
 	}
 }
 
 pub struct CommandParams {
+	/// Unique ID of this invocation, which is also the reference ID of its errors
+	pub id: String,
 	pub prefix: String,
 	pub db: Arc<RwLock<Database>>,
 	pub ctx: serenity::client::Context,
@@ -193,16 +223,50 @@ pub enum CommErr {
 	UnknownCommand
 }
 
+impl CommErr {
+	/// Name of the variant, for logs and metrics
+	pub fn kind(&self) -> &'static str {
+		match self {
+			CommErr::Error(..) => "error",
+			CommErr::UnknownError => "unknown_error",
+			CommErr::SyntaxError => "syntax_error",
+			CommErr::UnknownCommand => "unknown_command"
+		}
+	}
+}
+
+pub type OptionFn = fn(&mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption;
+pub type CommandFn<'a> = fn(params: CommandParams) -> Pin<Box<dyn Future<Output = Result<CommRes<'a>, CommErr>> + std::marker::Send>>;
+pub type AutocompleteFn = fn(params: CommandParams) -> Pin<Box<dyn Future<Output = Vec<String>> + std::marker::Send>>;
+
 pub struct Command<'a> {
 	pub names: Vec<String>,
 	pub desc: String,
-	pub options: Vec<fn(&mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption>,
+	pub options: Vec<OptionFn>,
 	pub cat: String,
-	pub func: fn(params: CommandParams) -> Pin<Box<dyn Future<Output = Result<CommRes<'a>, CommErr>> + std::marker::Send>>,
-	pub args: String
+	pub func: CommandFn<'a>,
+	pub args: String,
+	/// Guild permissions the member needs in order to use the command
+	pub perms: Permissions,
+	/// How long a user has to wait before using the command again
+	pub cooldown: Duration,
+	/// Suggests values for the option that is being typed in the slash command
+	pub autocomplete: Option<AutocompleteFn>,
+	/// Whether the command runs again when the message that invoked it is
+	/// edited. Only for commands that do nothing but reply, since anything
+	/// else they do would be done again
+	pub rerun_on_edit: bool,
+	/// Whether only the bot's owners can use the command
+	pub owner_only: bool
 }
 
-impl Command<'_> {
+impl Default for Command<'_> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<'a> Command<'a> {
 	pub fn new() -> Self {
 		Self {
 			names: Vec::new(),
@@ -210,36 +274,91 @@ impl Command<'_> {
 			options: Vec::new(),
 			cat: String::new(),
 			func: |_| Box::pin(async { Ok(CommRes::None) }),
-			args: String::new()
+			args: String::new(),
+			perms: Permissions::empty(),
+			cooldown: Duration::ZERO,
+			autocomplete: None,
+			rerun_on_edit: false,
+			owner_only: false
 		}
 	}
-	pub async fn run(&self, params: CommandParams) -> Result<CommRes, CommErr> {
+	pub async fn run(&self, params: CommandParams) -> Result<CommRes<'a>, CommErr> {
 		(self.func)(params).await
 	}
+	/// Builds the command's options and returns them as JSON
+	pub fn option_data(&self) -> Vec<Value> {
+		self.options.iter().map(|option_fn| {
+			let mut option = CreateApplicationCommandOption::default();
+			option_fn(&mut option);
+			Value::Object(option.0.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+		}).collect()
+	}
+	pub fn syntax(&self, prefix: &str) -> String {
+		format!("{}{} {}", prefix, self.names[0], self.args).trim_end().to_string()
+	}
+}
+
+/// My reply to a prefix command
+struct TrackedReply {
+	reply: Option<MessageId>,
+	/// Content of the message that invoked the command
+	content: String,
+	/// When the command was first run
+	since: Instant
 }
 
 pub struct ChloeManager<'a> {
 	pub config: Value,
-	pub commands: Vec<Command<'a>>
+	pub commands: Vec<Command<'a>>,
+	pub metrics: Metrics,
+	pub shutdown: Shutdown,
+	pub modlog: ModLog,
+	pub automod: AutoMod,
+	pub antiraid: AntiRaid,
+	pub levels: Levels,
+	before_hooks: Vec<BeforeHook<'a>>,
+	after_hooks: Vec<AfterHook<'a>>,
+	/// Handlers of scheduled jobs, by kind
+	jobs: HashMap<String, JobFn>,
+	cooldowns: Mutex<HashMap<(String, UserId), Instant>>,
+	/// My replies to prefix commands, by the ID of the message that invoked them
+	replies: Mutex<HashMap<MessageId, TrackedReply>>,
+	started: Instant,
+	guilds: Mutex<HashSet<GuildId>>,
+	/// How many shards the bot has, across every process
+	shard_count: AtomicU64,
+	shard_manager: Mutex<Option<Arc<serenity::prelude::Mutex<ShardManager>>>>
 }
 
 impl<'a> ChloeManager<'a> {
 	pub fn new(config: Option<&str>, commands: Vec<Command<'a>>) -> Result<Self, String> {
 		Ok(Self {
 			config: if let Some(p) = config {
-				match parse_config_file(p) {
-					Ok(v) => v,
-					Err(e) => return Err(e)
-				}
+				parse_config_file(p)?
 			}
 			else { serde_json::json!({}) },
-			commands: commands
+			commands,
+			metrics: Metrics::default(),
+			shutdown: Shutdown::default(),
+			modlog: ModLog::default(),
+			automod: AutoMod::default(),
+			antiraid: AntiRaid::default(),
+			levels: Levels::default(),
+			before_hooks: Vec::new(),
+			after_hooks: Vec::new(),
+			jobs: HashMap::new(),
+			cooldowns: Mutex::new(HashMap::new()),
+			replies: Mutex::new(HashMap::new()),
+			started: Instant::now(),
+			guilds: Mutex::new(HashSet::new()),
+			shard_count: AtomicU64::new(1),
+			shard_manager: Mutex::new(None)
 		})
 	}
 	pub fn command(&self, name: &str) -> Option<&Command<'a>> {
 		for command_ in self.commands.iter() {
 			for name_ in command_.names.iter() {
-				if name_ == name {
+				if name_.eq_ignore_ascii_case(name) {
 					return Some(command_);
 				}
 			}
@@ -247,204 +366,460 @@ impl<'a> ChloeManager<'a> {
 		None
 	}
 	pub fn command_from_msg(&self, content: &str, prefix: &str) -> Option<&Command<'a>> {
-		if let Some(arg_str) = content.strip_prefix(prefix) {
-			let args: Vec<&str> = arg_str.split(' ').collect();
-			return self.command(args[0]);
+		let arg_str = content.strip_prefix(prefix)?;
+		let args: Vec<&str> = arg_str.split(' ').collect();
+		self.command(args[0])
+	}
+	/// Returns the command name that is the closest to `name`, if any is close enough
+	pub fn suggest(&self, name: &str) -> Option<&str> {
+		let name = name.to_lowercase();
+		let max_distance = (name.chars().count() / 3).clamp(1, 3);
+		self.commands.iter()
+			.flat_map(|c| c.names.iter())
+			.map(|n| (n.as_str(), edit_distance(&name, &n.to_lowercase())))
+			.filter(|(_, d)| *d <= max_distance)
+			.min_by_key(|(_, d)| *d)
+			.map(|(n, _)| n)
+	}
+	pub fn uptime(&self) -> Duration {
+		self.started.elapsed()
+	}
+	/// Keeps track of the guilds I'm in, since there is no cache to ask.
+	/// `shard` is the ID of the shard that got them and the shard count, and
+	/// only that shard's guilds are replaced
+	pub fn set_guilds(&self, shard: [u64; 2], guilds: impl IntoIterator<Item = GuildId>) {
+		self.shard_count.store(shard[1], Ordering::Relaxed);
+		let mut current = self.guilds.lock().unwrap();
+		current.retain(|v| shards::shard_of(*v, shard[1]) != shard[0]);
+		current.extend(guilds);
+	}
+	pub fn add_guild(&self, guild_id: GuildId) {
+		self.guilds.lock().unwrap().insert(guild_id);
+	}
+	pub fn remove_guild(&self, guild_id: GuildId) {
+		self.guilds.lock().unwrap().remove(&guild_id);
+	}
+	pub fn guild_count(&self) -> usize {
+		self.guilds.lock().unwrap().len()
+	}
+	pub fn shard_guild_count(&self, shard_id: u64) -> usize {
+		let shard_count = self.shard_count();
+		self.guilds.lock().unwrap().iter().filter(|v| shards::shard_of(**v, shard_count) == shard_id).count()
+	}
+	pub fn shard_count(&self) -> u64 {
+		self.shard_count.load(Ordering::Relaxed)
+	}
+	pub fn set_shard_manager(&self, shard_manager: Arc<serenity::prelude::Mutex<ShardManager>>) {
+		*self.shard_manager.lock().unwrap() = Some(shard_manager);
+	}
+	/// Status of the shards that this process runs
+	pub async fn shards(&self) -> Vec<ShardStatus> {
+		let shard_manager = self.shard_manager.lock().unwrap().clone();
+		match shard_manager {
+			Some(v) => shards::statuses(&v).await,
+			None => Vec::new()
 		}
-		else {
-			None
+	}
+	/// Shows the activity on every shard that this process runs
+	pub async fn set_activity(&self, activity: Option<Activity>) {
+		let shard_manager = self.shard_manager.lock().unwrap().clone();
+		if let Some(v) = shard_manager {
+			shards::set_activity(&v, activity).await;
+		}
+	}
+	/// Whether the user is one of the bot's owners, as listed in the config
+	pub fn is_owner(&self, user_id: UserId) -> bool {
+		match &self.config["owners"] {
+			Value::Array(owners) => owners.iter().any(|owner| match owner {
+				Value::String(v) => v == &user_id.to_string(),
+				Value::Number(v) => v.as_u64() == Some(user_id.0),
+				_ => false
+			}),
+			_ => false
+		}
+	}
+	/// Whether unknown prefix commands should get a reply in the given guild
+	pub fn reply_unknown(&self, db: &Arc<RwLock<Database>>, guild_id: Option<GuildId>) -> bool {
+		let guild_id = match guild_id {
+			Some(v) => v.to_string(),
+			None => return true
+		};
+		let db = db.read().unwrap();
+		match db.get(&["guilds", guild_id.as_str(), "settings", "unknown_commands"]) {
+			Value::Bool(v) => *v,
+			_ => self.config["unknown_commands"].as_bool().unwrap_or(true)
+		}
+	}
+	/// Returns the categories of the registered commands, in the order they were registered in
+	pub fn categories(&self) -> Vec<&str> {
+		let mut categories: Vec<&str> = Vec::new();
+		for command in self.commands.iter() {
+			if !command.cat.is_empty() && !categories.contains(&command.cat.as_str()) {
+				categories.push(command.cat.as_str());
+			}
 		}
+		categories
 	}
-	pub async fn run_command(&self, name: &str, params: CommandParams) -> Option<Result<CommRes, CommErr>> {
+	pub fn before(mut self, hook: BeforeHook<'a>) -> Self {
+		self.before_hooks.push(hook);
+		self
+	}
+	pub fn after(mut self, hook: AfterHook<'a>) -> Self {
+		self.after_hooks.push(hook);
+		self
+	}
+	/// Registers the handler that runs the scheduled jobs of a kind
+	pub fn job(mut self, kind: &str, handler: JobFn) -> Self {
+		self.jobs.insert(kind.to_string(), handler);
+		self
+	}
+	pub fn job_handler(&self, kind: &str) -> Option<JobFn> {
+		self.jobs.get(kind).copied()
+	}
+	/// Runs a command through the hook pipeline. Both prefix and slash commands go through here
+	pub async fn execute(&self, command: &Command<'a>, params: CommandParams) -> Result<CommRes<'a>, CommErr> {
+		let info = CommandInfo::new(command, &params);
+		let span = tracing::info_span!("command",
+			id = %info.id,
+			name = %info.name,
+			source = %info.source,
+			user_id = info.author.id.0,
+			guild_id = info.guild_id.map(|v| v.0),
+			channel_id = info.channel_id.0,
+			interaction_id = params.inter.as_ref().map(|v| v.id.0),
+			message_id = params.msg.as_ref().map(|v| v.id.0),
+			duration_ms = tracing::field::Empty
+		);
+		async move {
+			let start = Instant::now();
+			let mut result = Ok(());
+			for hook in self.before_hooks.iter() {
+				result = hook(self, command, &params).await;
+				if result.is_err() {
+					break;
+				}
+			}
+			let result = match result {
+				Ok(()) => command.run(params).await,
+				Err(e) => Err(e)
+			};
+			let elapsed = start.elapsed();
+			tracing::Span::current().record("duration_ms", elapsed.as_millis() as u64);
+			tracing::info!(ok = result.is_ok(), "Command finished");
+			for hook in self.after_hooks.iter() {
+				hook(self, &info, &result, elapsed).await;
+			}
+			result
+		}.instrument(span).await
+	}
+	pub async fn run_command(&self, name: &str, params: CommandParams) -> Option<Result<CommRes<'a>, CommErr>> {
 		match self.command(name) {
-			Some(command) => Some(command.run(params).await),
+			Some(command) => Some(self.execute(command, params).await),
 			None => None
 		}
 	}
+	/// The message that tells the user about an error. `id` is the invocation's
+	/// ID, which is shown as a reference for errors that the owners might need to look into
+	pub fn error_message(&self, e: &CommErr, command: &Command<'a>, prefix: &str, id: &str) -> CreateMessage<'a> {
+		make_message(|m| {
+			match e {
+				CommErr::Error(e1, e2) => m.add_embed(|e| {
+					let e = e.title("Error")
+					.color(self.config["bad_color"].as_i64().unwrap() as i32);
+					if !e2.is_empty() {
+						e.footer(|f| f.text(format!("Reference: {}", id)));
+					}
+					if e1.is_empty() {
+						e.description("An error has occurred")
+					}
+					else {
+						e.description(e1)
+					}
+				}),
+				CommErr::UnknownError => m.add_embed(|e| {
+					e.title("Error")
+					.color(self.config["bad_color"].as_i64().unwrap() as i32)
+					.description("An error has occurred")
+					.footer(|f| f.text(format!("Reference: {}", id)))
+				}),
+				CommErr::SyntaxError => m.add_embed(|e| {
+					e.title(format!("{}{}", prefix, command.names[0]))
+					.description(command.desc.clone())
+					.fields(vec![("Syntax", command.syntax(prefix), false)])
+					.color(self.config["embed_color"].as_i64().unwrap() as i32)
+				}),
+				CommErr::UnknownCommand => m.content("Unknown command")
+			}
+		})
+	}
 	pub async fn process_msg(&self, msg: Message, ctx: serenity::client::Context, db: Arc<RwLock<Database>>, prefix: &str) -> Option<Result<(), CommErr>> {
 		let content = msg.content.clone();
-		if content.len() < prefix.len() || prefix != &content.as_str()[0..prefix.len()].to_lowercase() {
+		if content.len() < prefix.len() || prefix != content.as_str()[0..prefix.len()].to_lowercase() {
 			return None;
 		}
 		let arg_str = &content.as_str()[prefix.len()..];
-		let args: Vec<&str> = arg_str.split(' ').collect();
+		let (name, arg_str) = arg_str.split_once(char::is_whitespace).unwrap_or((arg_str, ""));
+		let channel_id = msg.channel_id;
+		let msg_id = msg.id;
+		let previous = self.tracked_reply(msg_id);
+		let command = match self.command(name) {
+			Some(v) => v,
+			None => {
+				// Servers' tags work like commands, unless a command has the same name
+				let tag = msg.guild_id.and_then(|guild_id| tags::use_tag(&mut db.write().unwrap(), guild_id, name));
+				if let Some(tag) = tag {
+					let reply = self.reply(&ctx, channel_id, previous, tag.message(self, msg.author.id, channel_id, arg_str)).await.ok().or(previous);
+					self.track_reply(msg_id, &content, reply);
+					return Some(Ok(()));
+				}
+				let mut reply = previous;
+				if self.reply_unknown(&db, msg.guild_id) {
+					let text = match self.suggest(name) {
+						Some(suggestion) => format!("Unknown command. Did you mean `{}{}`?", prefix, suggestion),
+						None => "Unknown command".to_string()
+					};
+					reply = self.reply(&ctx, channel_id, previous, make_message(|m| m.content(text))).await.ok().or(reply);
+				}
+				self.track_reply(msg_id, &content, reply);
+				self.metrics.record_command("", Source::Prefix);
+				self.metrics.record_error("", &CommErr::UnknownCommand);
+				return Some(Err(CommErr::UnknownCommand));
+			}
+		};
 		let member = match msg.member {
 			Some(..) => {
-				msg.guild_id.clone().unwrap().member(ctx.http.as_ref(), msg.author.id).await.ok()
+				msg.guild_id.unwrap().member(ctx.http.as_ref(), msg.author.id).await.ok()
 			},
 			None => None
 		};
 		let author = msg.author.clone();
-		let channel_id = msg.channel_id.clone();
-		let guild_id = msg.guild_id.clone();
-		match self.run_command(args[0], CommandParams {
-			prefix: prefix.to_string(),
-			db: db,
-			ctx: ctx.clone(),
-			options: CommOptions(HashMap::new()),
-			msg: Some(msg),
-			inter: None,
-			author: author,
-			member: member,
-			channel_id: channel_id.clone(),
-			guild_id: guild_id
-		}).await {
-			Some(v) => Some(match v {
-				Ok(v) => match v {
-					CommRes::Text(text) => match channel_id.say(ctx.http, text).await {
-						Ok(..) => Ok(()),
-						Err(e) => Err(CommErr::Error(String::new(), format!("{e}")))
-					},
-					CommRes::Msg(msg) => match channel_id.send_message(ctx.http.as_ref(), |m| { *m = msg; m }).await {
-						Ok(..) => Ok(()),
-						Err(e) => Err(CommErr::Error(String::new(), format!("{e}")))
-					},
-					_ => Ok(())
+		let guild_id = msg.guild_id;
+		let id = errors::new_id();
+		// Kept until the reply is sent, so that shutting down waits for it
+		let in_flight = self.shutdown.start_command();
+		let result = match CommOptions::from_args(arg_str, &command.option_data(), &msg, &ctx).await {
+			Ok(..) if in_flight.is_none() => Err(self.shutdown.error()),
+			Ok(options) => {
+				let params = CommandParams {
+					id: id.clone(),
+					prefix: prefix.to_string(),
+					db,
+					ctx: ctx.clone(),
+					options,
+					msg: Some(msg.clone()),
+					inter: None,
+					author,
+					member,
+					channel_id,
+					guild_id
+				};
+				self.execute(command, params).await
+			},
+			Err(e) => {
+				// The arguments didn't parse, so the command never made it to the hooks
+				self.metrics.record_command(&command.names[0], Source::Prefix);
+				self.metrics.record_error(&command.names[0], &e);
+				Err(e)
+			}
+		};
+		let (message, result) = match result {
+			Ok(v) => match v {
+				CommRes::Text(text) => (Some(make_message(|m| m.content(text))), Ok(())),
+				CommRes::Msg(msg) => (Some(msg), Ok(())),
+				CommRes::None => (None, Ok(()))
+			},
+			Err(e) => (Some(self.error_message(&e, command, prefix, &id)), Err(e))
+		};
+		let result = match message {
+			Some(message) => match self.reply(&ctx, channel_id, previous, message).await {
+				Ok(reply) => {
+					self.track_reply(msg_id, &content, Some(reply));
+					result
 				},
 				Err(e) => {
-					channel_id.send_message(ctx.http.as_ref(), |m| {
-						let command = self.command(args[0]).unwrap();
-						match &e {
-							CommErr::Error(e1, e2) => {
-								if !e2.is_empty() {
-									eprintln!("Error processing message: {}", e2);
-								}
-								m.add_embed(|e| {
-									let e = e.title("Error")
-									.color(CHLOE.config["bad_color"].as_i64().unwrap() as i32);
-									if e1.is_empty() {
-										e.description("An error has occurred")
-									}
-									else {
-										e.description(e1)
-									}
-								})
-							},
-							CommErr::UnknownError => m.add_embed(|e| {
-								e.title("Error")
-								.color(CHLOE.config["bad_color"].as_i64().unwrap() as i32)
-								.description("An error has occurred")
-							}),
-							CommErr::SyntaxError => 
-								m.add_embed(|e| {
-								e.title(format!("{}{}", prefix, command.names[0]))
-								.description(command.desc.clone())
-								.fields(vec![("Syntax", format!("{}{} {}", prefix, command.names[0], command.args), false)])
-								.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
-							}),
-							CommErr::UnknownCommand => m.content("Unknown command")
-						}
-					}).await.ok();
-					Err(e)
+					self.track_reply(msg_id, &content, previous);
+					result.and(Err(CommErr::Error(String::new(), format!("{e}"))))
 				}
-			}),
+			},
 			None => {
-				channel_id.say(ctx.http.as_ref(), "Unknown command").await.ok();
-				Some(Err(CommErr::UnknownCommand))
+				self.track_reply(msg_id, &content, previous);
+				result
+			}
+		};
+		Some(result)
+	}
+	/// Sends a reply to a prefix command, or edits the previous reply if the
+	/// command is being run again
+	async fn reply(&self, ctx: &serenity::client::Context, channel_id: ChannelId, previous: Option<MessageId>, message: CreateMessage<'_>) -> serenity::Result<MessageId> {
+		match previous {
+			Some(previous) => {
+				let mut data = message.0;
+				// Clear whatever the previous reply had that the new one doesn't
+				data.entry("content").or_insert_with(|| Value::String(String::new()));
+				data.entry("embeds").or_insert_with(|| Value::Array(Vec::new()));
+				data.entry("components").or_insert_with(|| Value::Array(Vec::new()));
+				channel_id.edit_message(ctx.http.as_ref(), previous, |m| {
+					*m = EditMessage(data, message.2);
+					m
+				}).await.map(|m| m.id)
+			},
+			None => channel_id.send_message(ctx.http.as_ref(), |m| { *m = message; m }).await.map(|m| m.id)
+		}
+	}
+	/// How long prefix commands can be edited (to run them again) or deleted
+	/// (to delete my reply) for
+	pub fn reply_window(&self) -> Duration {
+		Duration::from_secs(self.config["reply_window"].as_u64().unwrap_or(300))
+	}
+	/// Returns my reply to a message that invoked a prefix command, if that
+	/// message is still being tracked
+	fn tracked_reply(&self, msg_id: MessageId) -> Option<MessageId> {
+		let replies = self.replies.lock().unwrap();
+		match replies.get(&msg_id) {
+			Some(tracked) if tracked.since.elapsed() < self.reply_window() => tracked.reply,
+			_ => None
+		}
+	}
+	fn track_reply(&self, msg_id: MessageId, content: &str, reply: Option<MessageId>) {
+		let window = self.reply_window();
+		let mut replies = self.replies.lock().unwrap();
+		replies.retain(|_, tracked| tracked.since.elapsed() < window);
+		let since = match replies.get(&msg_id) {
+			Some(tracked) => tracked.since,
+			None => Instant::now()
+		};
+		replies.insert(msg_id, TrackedReply { reply, content: content.to_string(), since });
+	}
+	/// Runs a prefix command again after the message that invoked it was
+	/// edited, if its content changed and the command can be run again
+	pub async fn process_msg_edit(&self, event: MessageUpdateEvent, ctx: serenity::client::Context, db: Arc<RwLock<Database>>, prefix: &str) -> Option<Result<(), CommErr>> {
+		let content = event.content.as_ref()?;
+		// Discord also sends the whole message when it's pinned or a link
+		// in it gets an embed, which aren't edits
+		let is_edited = match self.replies.lock().unwrap().get(&event.id) {
+			Some(tracked) => tracked.since.elapsed() < self.reply_window() && tracked.content != *content,
+			None => false
+		};
+		if !is_edited {
+			return None;
+		}
+		let name = content.get(..prefix.len())
+			.filter(|v| v.to_lowercase() == prefix)
+			.and_then(|_| content[prefix.len()..].split_whitespace().next());
+		if name.and_then(|v| self.command(v)).is_some_and(|c| !c.rerun_on_edit) {
+			return None;
+		}
+		let msg = event.channel_id.message(ctx.http.as_ref(), event.id).await.ok()?;
+		self.process_msg(msg, ctx, db, prefix).await
+	}
+	/// Deletes my reply to a prefix command after the message that invoked it was deleted
+	pub async fn process_msg_delete(&self, channel_id: ChannelId, msg_id: MessageId, ctx: serenity::client::Context) {
+		let reply = self.tracked_reply(msg_id);
+		self.replies.lock().unwrap().remove(&msg_id);
+		if let Some(reply) = reply {
+			if self.config["delete_replies"].as_bool().unwrap_or(true) {
+				channel_id.delete_message(ctx.http.as_ref(), reply).await.ok();
 			}
 		}
 	}
 	pub async fn process_inter(&self, inter: ApplicationCommandInteraction, ctx: serenity::client::Context, db: Arc<RwLock<Database>>) -> Option<Result<(), CommErr>> {
 		inter.defer(ctx.http.as_ref()).await.unwrap();
+		let command = match self.command(inter.data.name.as_str()) {
+			Some(v) => v,
+			None => {
+				inter.create_followup_message(ctx.http.as_ref(), |m| {
+					m.content("Unknown command")
+				}).await.ok();
+				self.metrics.record_command("", Source::Slash);
+				self.metrics.record_error("", &CommErr::UnknownCommand);
+				return Some(Err(CommErr::UnknownCommand));
+			}
+		};
 		let member = match inter.member {
 			Some(..) => {
-				inter.guild_id.clone().unwrap().member(ctx.http.as_ref(), inter.user.id).await.ok()
+				inter.guild_id.unwrap().member(ctx.http.as_ref(), inter.user.id).await.ok()
 			},
 			None => None
 		};
 		let author = inter.user.clone();
-		let channel_id = inter.channel_id.clone();
-		let guild_id = inter.guild_id.clone();
-		match CHLOE.run_command(inter.data.name.clone().as_str(), CommandParams {
+		let channel_id = inter.channel_id;
+		let guild_id = inter.guild_id;
+		let id = errors::new_id();
+		let in_flight = self.shutdown.start_command();
+		if in_flight.is_none() {
+			let message = self.error_message(&self.shutdown.error(), command, "/", &id);
+			inter.create_followup_message(ctx.http.as_ref(), |m| { *m = CreateInteractionResponseFollowup(message.0, message.2); m }).await.ok();
+			return Some(Err(self.shutdown.error()));
+		}
+		let result = self.execute(command, CommandParams {
+			id: id.clone(),
 			prefix: "/".to_string(),
-			db: db,
+			db,
 			ctx: ctx.clone(),
 			options: CommOptions::new(inter.data.options.clone()),
 			msg: None,
 			inter: Some(inter.clone()),
-			author: author,
-			member: member,
-			channel_id: channel_id,
-			guild_id: guild_id
-		}).await {
-			Some(v) => Some(match v {
-				Ok(v) => {
-					match v {
-						CommRes::Text(text) => match inter.create_followup_message(ctx.http.as_ref(), |m| { m.content(text) }).await {
-							Ok(..) => Ok(()),
-							Err(e) => Err(CommErr::Error(String::new(), format!("{e}")))
-						},
-						CommRes::Msg(msg) => match inter.create_followup_message(ctx.http.as_ref(), |m| { *m = CreateInteractionResponseFollowup(msg.0, msg.2); m }).await {
-							Ok(..) => Ok(()),
-							Err(e) => Err(CommErr::Error(String::new(), format!("{e}")))
-						},
-						CommRes::None => {
-							Ok(())
-						}
-					}
-				},
-				Err(e) => {
-						inter.create_followup_message(ctx.http.as_ref(), |m| {
-							let prefix = "/";
-							let command = self.command(inter.data.name.clone().as_str()).unwrap();
-							match &e {
-								CommErr::Error(e1, e2) => {
-									if !e2.is_empty() {
-										eprintln!("Error processing message: {}", e2);
-									}
-									m.embed(|e| {
-										let e = e.title("Error")
-										.color(CHLOE.config["bad_color"].as_i64().unwrap() as i32);
-										if e1.is_empty() {
-											e.description("An error has occurred")
-										}
-										else {
-											e.description(e1)
-										}
-									})
-								},
-								CommErr::UnknownError => m.embed(|e| {
-									e.title("Error")
-									.color(CHLOE.config["bad_color"].as_i64().unwrap() as i32)
-									.description("An error has occurred")
-								}),
-								CommErr::SyntaxError => 
-									m.embed(|e| {
-									e.title(format!("{}{}", prefix, command.names[0]))
-									.description(command.desc.clone())
-									.fields(vec![("Syntax", format!("{}{} {}", prefix, command.names[0], command.args), false)])
-									.color(CHLOE.config["embed_color"].as_i64().unwrap() as i32)
-								}),
-								CommErr::UnknownCommand => m.content("Unknown command")
-							}
-						}).await.ok();
-						Err(e)
-					}
-			}),
-			None => {
-				inter.create_followup_message(ctx.http.as_ref(), |m| {
-					m.content("Unknown command")
-				}).await.ok();
-				Some(Err(CommErr::UnknownCommand))
+			author,
+			member,
+			channel_id,
+			guild_id
+		}).await;
+		let (message, result) = match result {
+			Ok(v) => match v {
+				CommRes::Text(text) => (Some(make_message(|m| m.content(text))), Ok(())),
+				CommRes::Msg(msg) => (Some(msg), Ok(())),
+				CommRes::None => (None, Ok(()))
+			},
+			Err(e) => (Some(self.error_message(&e, command, "/", &id)), Err(e))
+		};
+		Some(match message {
+			Some(msg) => match inter.create_followup_message(ctx.http.as_ref(), |m| { *m = CreateInteractionResponseFollowup(msg.0, msg.2); m }).await {
+				Ok(..) => result,
+				Err(e) => result.and(Err(CommErr::Error(String::new(), format!("{e}"))))
+			},
+			None => result
+		})
+	}
+	pub async fn process_autocomplete(&self, inter: AutocompleteInteraction, ctx: serenity::client::Context, db: Arc<RwLock<Database>>) {
+		let autocomplete = match self.command(inter.data.name.as_str()).and_then(|c| c.autocomplete) {
+			Some(v) => v,
+			None => return
+		};
+		let choices = autocomplete(CommandParams {
+			id: errors::new_id(),
+			prefix: "/".to_string(),
+			db,
+			ctx: ctx.clone(),
+			options: CommOptions::new(inter.data.options.clone()),
+			msg: None,
+			inter: None,
+			author: inter.user.clone(),
+			member: inter.member.clone(),
+			channel_id: inter.channel_id,
+			guild_id: inter.guild_id
+		}).await;
+		inter.create_autocomplete_response(ctx.http.as_ref(), |r| {
+			for choice in choices.iter().take(25) {
+				r.add_string_choice(choice, choice);
 			}
-		}
+			r
+		}).await.ok();
 	}
 }
 
 pub fn parse_config_file(config_path_str: &str) -> Result<Value, String> {
-	match {
-		match fs::File::open(config_path_str) {
-			Ok(mut config_file) => {
-				let mut config_file_content = String::new();
-				config_file.read_to_string(&mut config_file_content).unwrap();
-				match json5::from_str(config_file_content.as_str()) {
-					Ok(v) => Ok(v),
-					Err(e) => Err(format!("Unable to parse the config file \"{config_path_str}\" as JSON5 or JSON: {e}"))
-				}
-			},
-			Err(e) => Err(format!("Unable to open the config file \"{config_path_str}\": {e}"))
-		}
-	} {
+	let config = match fs::File::open(config_path_str) {
+		Ok(mut config_file) => {
+			let mut config_file_content = String::new();
+			config_file.read_to_string(&mut config_file_content).unwrap();
+			match json5::from_str(config_file_content.as_str()) {
+				Ok(v) => Ok(v),
+				Err(e) => Err(format!("Unable to parse the config file \"{config_path_str}\" as JSON5 or JSON: {e}"))
+			}
+		},
+		Err(e) => Err(format!("Unable to open the config file \"{config_path_str}\": {e}"))
+	};
+	match config {
 		Ok(v) => Ok(v),
 		Err(e) => {
 			let config_path = Path::new(config_path_str);
@@ -455,6 +830,7 @@ pub fn parse_config_file(config_path_str: &str) -> Result<Value, String> {
 						return Err(format!("{e}.         The example config file \"{def_config_path_str}\" might come in handy"));
 					}
 					else {
+						// Logging isn't set up until the config is read, so this goes straight to stdout
 						println!("The config file \"{config_path_str}\" doesn't exist. I will attempt to copy the example config file \"{def_config_path_str}\" to \"{config_path_str}\".");
 						if let Err(e) = fs::copy(def_config_path_str, config_path_str) {
 							return Err(format!("Unable to copy \"{def_config_path_str}\" to \"{config_path_str}\": {e}"));
@@ -464,7 +840,7 @@ pub fn parse_config_file(config_path_str: &str) -> Result<Value, String> {
 					}
 				}
 			}
-			return Err(e);
+			Err(e)
 		}
 	}
 }
@@ -472,7 +848,8 @@ pub fn parse_config_file(config_path_str: &str) -> Result<Value, String> {
 pub fn disable_all_components(c: &mut CreateComponents) {
 	for row in c.0.iter_mut() {
 		for component in row["components"].as_array_mut().unwrap().iter_mut() {
-			if component["type"].as_u64().unwrap() == 2 {
+			// Buttons and select menus
+			if matches!(component["type"].as_u64().unwrap(), 2 | 3) {
 				component["disabled"] = serde_json::json!(true);
 			}
 		}
@@ -489,3 +866,62 @@ pub fn make_message<'a, F>(msg_fn: F) -> CreateMessage<'a>
 	msg_fn(&mut msg);
 	msg
 }
+
+/// Levenshtein distance between two strings
+pub fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut prev = row[0];
+		row[0] = i + 1;
+		for (j, cb) in b.iter().enumerate() {
+			let current = row[j + 1];
+			row[j + 1] = if ca == *cb { prev } else { 1 + prev.min(row[j]).min(row[j + 1]) };
+			prev = current;
+		}
+	}
+	row[b.len()]
+}
+
+/// Calculates a member's permissions in a guild from the guild's roles
+pub async fn member_permissions(ctx: &serenity::client::Context, guild_id: GuildId, member: &Member) -> serenity::Result<Permissions> {
+	let guild = guild_id.to_partial_guild(ctx.http.as_ref()).await?;
+	if member.user.id == guild.owner_id {
+		return Ok(Permissions::all());
+	}
+	let mut perms = match guild.roles.get(&RoleId(guild_id.0)) {
+		Some(everyone) => everyone.permissions,
+		None => Permissions::empty()
+	};
+	for role in member.roles.iter() {
+		if let Some(role) = guild.roles.get(role) {
+			perms |= role.permissions;
+		}
+	}
+	if perms.contains(Permissions::ADMINISTRATOR) {
+		return Ok(Permissions::all());
+	}
+	Ok(perms)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::edit_distance;
+
+	#[test]
+	fn edit_distance_counts_edits() {
+		assert_eq!(edit_distance("help", "help"), 0);
+		assert_eq!(edit_distance("", "ping"), 4);
+		assert_eq!(edit_distance("ping", ""), 4);
+		assert_eq!(edit_distance("hepl", "help"), 2);
+		assert_eq!(edit_distance("pnig", "ping"), 2);
+		assert_eq!(edit_distance("stat", "stats"), 1);
+		assert_eq!(edit_distance("kitten", "sitting"), 3);
+	}
+
+	#[test]
+	fn edit_distance_counts_characters_not_bytes() {
+		assert_eq!(edit_distance("café", "cafe"), 1);
+		assert_eq!(edit_distance("ñ", "n"), 1);
+	}
+}
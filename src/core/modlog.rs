@@ -0,0 +1,458 @@
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::{RwLock, Arc, Mutex};
+use serde_json::Value;
+use serenity::builder::CreateEmbed;
+use serenity::client::Context;
+use serenity::model::Timestamp;
+use serenity::model::channel::Message;
+use serenity::model::event::{GuildMemberUpdateEvent, MessageUpdateEvent};
+use serenity::model::guild::Member;
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use serenity::model::user::User;
+use super::db::Database;
+use super::{ChloeManager, time};
+
+/// Something that can be posted in a server's moderation log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogEvent {
+	MessageEdit,
+	MessageDelete,
+	MemberJoin,
+	MemberLeave,
+	RoleChange,
+	NicknameChange,
+	Ban,
+	Unban,
+	Automod,
+	Raid
+}
+
+impl LogEvent {
+	pub const ALL: [LogEvent; 10] = [
+		LogEvent::MessageEdit,
+		LogEvent::MessageDelete,
+		LogEvent::MemberJoin,
+		LogEvent::MemberLeave,
+		LogEvent::RoleChange,
+		LogEvent::NicknameChange,
+		LogEvent::Ban,
+		LogEvent::Unban,
+		LogEvent::Automod,
+		LogEvent::Raid
+	];
+	pub fn from_key(key: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|v| v.key() == key)
+	}
+	/// Key of this event in the database
+	pub fn key(&self) -> &'static str {
+		match self {
+			LogEvent::MessageEdit => "message_edit",
+			LogEvent::MessageDelete => "message_delete",
+			LogEvent::MemberJoin => "member_join",
+			LogEvent::MemberLeave => "member_leave",
+			LogEvent::RoleChange => "role_change",
+			LogEvent::NicknameChange => "nickname_change",
+			LogEvent::Ban => "ban",
+			LogEvent::Unban => "unban",
+			LogEvent::Automod => "automod",
+			LogEvent::Raid => "raid"
+		}
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			LogEvent::MessageEdit => "Message edits",
+			LogEvent::MessageDelete => "Message deletions",
+			LogEvent::MemberJoin => "Members joining",
+			LogEvent::MemberLeave => "Members leaving",
+			LogEvent::RoleChange => "Role changes",
+			LogEvent::NicknameChange => "Nickname changes",
+			LogEvent::Ban => "Bans",
+			LogEvent::Unban => "Unbans",
+			LogEvent::Automod => "Automod actions",
+			LogEvent::Raid => "Raid alerts"
+		}
+	}
+}
+
+/// A message as it was before being edited or deleted
+struct CachedMessage {
+	guild_id: GuildId,
+	author_id: UserId,
+	bot: bool,
+	content: String,
+	attachments: Vec<String>
+}
+
+struct CachedMember {
+	nick: Option<String>,
+	roles: Vec<RoleId>
+}
+
+/// Map that forgets its oldest entries once it's full
+struct Bounded<K, V> {
+	entries: HashMap<K, V>,
+	order: VecDeque<K>
+}
+
+impl<K, V> Default for Bounded<K, V> {
+	fn default() -> Self {
+		Self {
+			entries: HashMap::new(),
+			order: VecDeque::new()
+		}
+	}
+}
+
+impl<K: Hash + Eq + Clone, V> Bounded<K, V> {
+	/// Returns the entry that was replaced
+	fn insert(&mut self, key: K, value: V, capacity: usize) -> Option<V> {
+		let old = self.entries.insert(key.clone(), value);
+		if old.is_none() {
+			self.order.push_back(key);
+		}
+		while self.order.len() > capacity {
+			if let Some(oldest) = self.order.pop_front() {
+				self.entries.remove(&oldest);
+			}
+		}
+		old
+	}
+	fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+		self.entries.get_mut(key)
+	}
+	fn remove(&mut self, key: &K) -> Option<V> {
+		let value = self.entries.remove(key)?;
+		self.order.retain(|k| k != key);
+		Some(value)
+	}
+}
+
+/// What I remember about recent messages and members, since Discord doesn't
+/// say what they were like before they changed. Only servers with a log
+/// channel are remembered
+#[derive(Default)]
+pub struct ModLog {
+	messages: Mutex<Bounded<MessageId, CachedMessage>>,
+	members: Mutex<Bounded<(GuildId, UserId), CachedMember>>
+}
+
+impl ModLog {
+	pub fn cache_message(&self, chloe: &ChloeManager, db: &Database, msg: &Message) {
+		let guild_id = match msg.guild_id {
+			Some(v) => v,
+			None => return
+		};
+		if target(db, guild_id, LogEvent::MessageEdit).is_none() && target(db, guild_id, LogEvent::MessageDelete).is_none() {
+			return;
+		}
+		let capacity = chloe.config["modlog"]["message_cache"].as_u64().unwrap_or(5000) as usize;
+		self.messages.lock().unwrap().insert(msg.id, CachedMessage {
+			guild_id,
+			author_id: msg.author.id,
+			bot: msg.author.bot,
+			content: msg.content.clone(),
+			attachments: msg.attachments.iter().map(|a| a.url.clone()).collect()
+		}, capacity);
+	}
+	pub fn cache_members<'a>(&self, chloe: &ChloeManager, db: &Database, guild_id: GuildId, members: impl Iterator<Item = &'a Member>) {
+		if target(db, guild_id, LogEvent::RoleChange).is_none() && target(db, guild_id, LogEvent::NicknameChange).is_none() {
+			return;
+		}
+		let capacity = chloe.config["modlog"]["member_cache"].as_u64().unwrap_or(50000) as usize;
+		let mut cache = self.members.lock().unwrap();
+		for member in members {
+			cache.insert((guild_id, member.user.id), CachedMember {
+				nick: member.nick.clone(),
+				roles: member.roles.clone()
+			}, capacity);
+		}
+	}
+}
+
+/// The server's log channel, if it has one
+pub fn channel(db: &Database, guild_id: GuildId) -> Option<ChannelId> {
+	db.get(&["guilds", guild_id.to_string().as_str(), "settings", "modlog", "channel"])
+		.as_str()
+		.and_then(|v| v.parse().ok())
+		.map(ChannelId)
+}
+
+/// Sets the server's log channel, or stops logging with None
+pub fn set_channel(db: &mut Database, guild_id: GuildId, channel_id: Option<ChannelId>) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "settings", "modlog", "channel"], match channel_id {
+		Some(v) => Value::String(v.to_string()),
+		None => Value::Null
+	})
+}
+
+/// Whether the event is logged in the server. Every event is by default
+pub fn is_enabled(db: &Database, guild_id: GuildId, event: LogEvent) -> bool {
+	db.get(&["guilds", guild_id.to_string().as_str(), "settings", "modlog", "events", event.key()])
+		.as_bool()
+		.unwrap_or(true)
+}
+
+pub fn set_enabled(db: &mut Database, guild_id: GuildId, event: LogEvent, enabled: bool) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "settings", "modlog", "events", event.key()], enabled.into())
+}
+
+/// Where the event should be posted in the server, if anywhere
+fn target(db: &Database, guild_id: GuildId, event: LogEvent) -> Option<ChannelId> {
+	match is_enabled(db, guild_id, event) {
+		true => channel(db, guild_id),
+		false => None
+	}
+}
+
+/// Shortens text to fit in an embed field
+fn clip(text: &str, max: usize) -> String {
+	match text.chars().count() > max {
+		true => format!("{}...", text.chars().take(max - 3).collect::<String>()),
+		false => text.to_string()
+	}
+}
+
+fn embed(chloe: &ChloeManager, title: &str, color: &str) -> CreateEmbed {
+	let mut embed = CreateEmbed::default();
+	embed.title(title)
+		.color(chloe.config[color].as_i64().unwrap() as i32)
+		.timestamp(Timestamp::now());
+	embed
+}
+
+async fn post(ctx: &Context, channel_id: ChannelId, embed: CreateEmbed) {
+	let result = channel_id.send_message(&ctx.http, |m| m.set_embed(embed)).await;
+	if let Err(e) = result {
+		tracing::warn!(channel = %channel_id, error = %e, "Unable to post in a moderation log");
+	}
+}
+
+pub async fn message_edited(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, event: &MessageUpdateEvent) {
+	// Updates without content are things like links getting their embeds
+	let (guild_id, content) = match (event.guild_id, &event.content) {
+		(Some(guild_id), Some(content)) => (guild_id, content),
+		_ => return
+	};
+	let old = chloe.modlog.messages.lock().unwrap().get_mut(&event.id).map(|m| {
+		(std::mem::replace(&mut m.content, content.clone()), m.author_id, m.bot)
+	});
+	let (author_id, bot) = match (&event.author, &old) {
+		(Some(author), _) => (author.id, author.bot),
+		(None, Some((_, author_id, bot))) => (*author_id, *bot),
+		(None, None) => return
+	};
+	let old = old.map(|(content, ..)| content);
+	if bot || old.as_ref() == Some(content) {
+		return;
+	}
+	let channel_id = match target(&db.read().unwrap(), guild_id, LogEvent::MessageEdit) {
+		Some(v) if v != event.channel_id => v,
+		_ => return
+	};
+	let mut embed = embed(chloe, "Message edited", "embed_color");
+	embed.description(format!("<@{}> edited [a message]({}) in <#{}>", author_id, event.id.link(event.channel_id, Some(guild_id)), event.channel_id))
+		.field("Before", match &old {
+			Some(old) if !old.is_empty() => clip(old, 1024),
+			Some(..) => "*Nothing*".to_string(),
+			None => "*I don't remember it*".to_string()
+		}, false)
+		.field("After", if content.is_empty() { "*Nothing*".to_string() } else { clip(content, 1024) }, false)
+		.footer(|f| f.text(format!("User ID: {}", author_id)));
+	post(ctx, channel_id, embed).await;
+}
+
+pub async fn message_deleted(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, channel_id: ChannelId, message_id: MessageId, guild_id: Option<GuildId>) {
+	let cached = chloe.modlog.messages.lock().unwrap().remove(&message_id);
+	let guild_id = match guild_id.or(cached.as_ref().map(|m| m.guild_id)) {
+		Some(v) => v,
+		None => return
+	};
+	if cached.as_ref().is_some_and(|m| m.bot) {
+		return;
+	}
+	let log_channel = match target(&db.read().unwrap(), guild_id, LogEvent::MessageDelete) {
+		Some(v) if v != channel_id => v,
+		_ => return
+	};
+	let mut embed = embed(chloe, "Message deleted", "bad_color");
+	match cached {
+		Some(message) => {
+			embed.description(format!("A message by <@{}> was deleted in <#{}>", message.author_id, channel_id))
+				.field("Content", if message.content.is_empty() { "*Nothing*".to_string() } else { clip(&message.content, 1024) }, false)
+				.footer(|f| f.text(format!("User ID: {}", message.author_id)));
+			if !message.attachments.is_empty() {
+				embed.field("Attachments", clip(&message.attachments.join("\n"), 1024), false);
+			}
+		},
+		None => {
+			embed.description(format!("A message I don't remember was deleted in <#{}>", channel_id));
+		}
+	}
+	post(ctx, log_channel, embed).await;
+}
+
+pub async fn messages_deleted(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, channel_id: ChannelId, message_ids: &[MessageId], guild_id: Option<GuildId>) {
+	let cached: Vec<CachedMessage> = {
+		let mut cache = chloe.modlog.messages.lock().unwrap();
+		message_ids.iter().filter_map(|id| cache.remove(id)).collect()
+	};
+	let guild_id = match guild_id.or(cached.first().map(|m| m.guild_id)) {
+		Some(v) => v,
+		None => return
+	};
+	let log_channel = match target(&db.read().unwrap(), guild_id, LogEvent::MessageDelete) {
+		Some(v) if v != channel_id => v,
+		_ => return
+	};
+	let mut lines = Vec::new();
+	let mut length = 0;
+	for message in cached.iter().filter(|m| !m.bot) {
+		let line = format!("<@{}>: {}", message.author_id, clip(&message.content, 100));
+		length += line.chars().count() + 1;
+		if length > 3500 {
+			lines.push("...".to_string());
+			break;
+		}
+		lines.push(line);
+	}
+	let mut embed = embed(chloe, "Messages deleted", "bad_color");
+	let mut description = format!("{} messages were deleted in <#{}>", message_ids.len(), channel_id);
+	if !lines.is_empty() {
+		description += &format!("\n\n{}", lines.join("\n"));
+	}
+	embed.description(description);
+	post(ctx, log_channel, embed).await;
+}
+
+pub async fn member_joined(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, member: &Member) {
+	let log_channel = {
+		let db = db.read().unwrap();
+		chloe.modlog.cache_members(chloe, &db, member.guild_id, std::iter::once(member));
+		match target(&db, member.guild_id, LogEvent::MemberJoin) {
+			Some(v) => v,
+			None => return
+		}
+	};
+	let created = member.user.created_at().unix_timestamp() as u64;
+	let mut embed = embed(chloe, "Member joined", "embed_color");
+	embed.description(format!("<@{}> ({})", member.user.id, member.user.tag()))
+		.field("Account created", format!("{} ({})", time::discord_timestamp(created, "f"), time::discord_timestamp(created, "R")), false)
+		.thumbnail(member.user.face())
+		.footer(|f| f.text(format!("User ID: {}", member.user.id)));
+	post(ctx, log_channel, embed).await;
+}
+
+pub async fn member_left(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, guild_id: GuildId, user: &User) {
+	let cached = chloe.modlog.members.lock().unwrap().remove(&(guild_id, user.id));
+	let log_channel = match target(&db.read().unwrap(), guild_id, LogEvent::MemberLeave) {
+		Some(v) => v,
+		None => return
+	};
+	let mut embed = embed(chloe, "Member left", "bad_color");
+	embed.description(format!("<@{}> ({})", user.id, user.tag()))
+		.thumbnail(user.face())
+		.footer(|f| f.text(format!("User ID: {}", user.id)));
+	if let Some(member) = cached.filter(|m| !m.roles.is_empty()) {
+		let roles: Vec<String> = member.roles.iter().map(|id| format!("<@&{}>", id)).collect();
+		embed.field("Roles", clip(&roles.join(" "), 1024), false);
+	}
+	post(ctx, log_channel, embed).await;
+}
+
+pub async fn member_updated(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, event: &GuildMemberUpdateEvent) {
+	let (roles_channel, nick_channel) = {
+		let db = db.read().unwrap();
+		(target(&db, event.guild_id, LogEvent::RoleChange), target(&db, event.guild_id, LogEvent::NicknameChange))
+	};
+	if roles_channel.is_none() && nick_channel.is_none() {
+		return;
+	}
+	let capacity = chloe.config["modlog"]["member_cache"].as_u64().unwrap_or(50000) as usize;
+	let old = chloe.modlog.members.lock().unwrap().insert((event.guild_id, event.user.id), CachedMember {
+		nick: event.nick.clone(),
+		roles: event.roles.clone()
+	}, capacity);
+	// Without knowing what they were like before, there's nothing to compare
+	let old = match old {
+		Some(v) => v,
+		None => return
+	};
+	if let Some(log_channel) = roles_channel {
+		let added: Vec<String> = event.roles.iter().filter(|id| !old.roles.contains(id)).map(|id| format!("<@&{}>", id)).collect();
+		let removed: Vec<String> = old.roles.iter().filter(|id| !event.roles.contains(id)).map(|id| format!("<@&{}>", id)).collect();
+		if !added.is_empty() || !removed.is_empty() {
+			let mut embed = embed(chloe, "Roles changed", "embed_color");
+			embed.description(format!("<@{}> ({})", event.user.id, event.user.tag()))
+				.footer(|f| f.text(format!("User ID: {}", event.user.id)));
+			if !added.is_empty() {
+				embed.field("Added", clip(&added.join(" "), 1024), false);
+			}
+			if !removed.is_empty() {
+				embed.field("Removed", clip(&removed.join(" "), 1024), false);
+			}
+			post(ctx, log_channel, embed).await;
+		}
+	}
+	if let Some(log_channel) = nick_channel {
+		if old.nick != event.nick {
+			let mut embed = embed(chloe, "Nickname changed", "embed_color");
+			embed.description(format!("<@{}> ({})", event.user.id, event.user.tag()))
+				.field("Before", old.nick.as_deref().unwrap_or("*None*"), true)
+				.field("After", event.nick.as_deref().unwrap_or("*None*"), true)
+				.footer(|f| f.text(format!("User ID: {}", event.user.id)));
+			post(ctx, log_channel, embed).await;
+		}
+	}
+}
+
+/// Logs a ban, or an unban with `banned` being false
+pub async fn ban_changed(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, guild_id: GuildId, user: &User, banned: bool) {
+	let (event, title, color) = match banned {
+		true => (LogEvent::Ban, "Member banned", "bad_color"),
+		false => (LogEvent::Unban, "Member unbanned", "embed_color")
+	};
+	let log_channel = match target(&db.read().unwrap(), guild_id, event) {
+		Some(v) => v,
+		None => return
+	};
+	let mut embed = embed(chloe, title, color);
+	embed.description(format!("<@{}> ({})", user.id, user.tag()))
+		.thumbnail(user.face())
+		.footer(|f| f.text(format!("User ID: {}", user.id)));
+	post(ctx, log_channel, embed).await;
+}
+
+/// Logs a message that broke an automod rule, and what was done about it
+pub async fn automod_triggered(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, msg: &Message, rule: &str, reason: &str, action: &str) {
+	let log_channel = match msg.guild_id.and_then(|guild_id| target(&db.read().unwrap(), guild_id, LogEvent::Automod)) {
+		Some(v) => v,
+		None => return
+	};
+	let mut embed = embed(chloe, &format!("Automod: {}", rule), "bad_color");
+	embed.description(format!("<@{}> in <#{}>: {}", msg.author.id, msg.channel_id, reason))
+		.field("Message", if msg.content.is_empty() { "*Nothing*".to_string() } else { clip(&msg.content, 1024) }, false)
+		.field("Action", action, false)
+		.footer(|f| f.text(format!("User ID: {}", msg.author.id)));
+	post(ctx, log_channel, embed).await;
+}
+
+/// Alerts the server's moderators about a raid, pinging a role if there's one
+pub async fn raid_alert(chloe: &ChloeManager<'_>, ctx: &Context, db: &RwLock<Database>, guild_id: GuildId, title: &str, description: &str, ping: Option<RoleId>) {
+	let log_channel = match target(&db.read().unwrap(), guild_id, LogEvent::Raid) {
+		Some(v) => v,
+		None => return
+	};
+	let mut embed = embed(chloe, title, "bad_color");
+	embed.description(description);
+	let result = log_channel.send_message(&ctx.http, |m| {
+		if let Some(role_id) = ping {
+			m.content(format!("<@&{}>", role_id))
+				.allowed_mentions(|a| a.roles(vec![role_id]));
+		}
+		m.set_embed(embed)
+	}).await;
+	if let Err(e) = result {
+		tracing::warn!(channel = %log_channel, error = %e, "Unable to post in a moderation log");
+	}
+}
@@ -0,0 +1,376 @@
+use std::collections::HashMap;
+use std::sync::{RwLock, Arc};
+use serde_json::Value;
+use serenity::builder::{CreateActionRow, CreateButton, CreateComponents, CreateEmbed};
+use serenity::client::Context;
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+use super::db::Database;
+use super::scheduler::{self, Job};
+use super::{disable_all_components, errors, time, ChloeManager};
+
+/// Kind of the scheduled jobs that end polls when their time is up
+pub const END_JOB: &str = "poll_end";
+/// Start of the custom IDs of polls' buttons, which are followed by the poll's
+/// ID and the index of the choice
+const CUSTOM_ID_PREFIX: &str = "poll:";
+/// Most choices a poll can have, which is what fits in two rows of buttons
+pub const MAX_CHOICES: usize = 10;
+/// Longest a poll can last, in seconds
+pub const MAX_DURATION: u64 = 30 * 24 * 60 * 60;
+/// How many voters are listed below each choice of polls that aren't anonymous
+const MAX_LISTED_VOTERS: usize = 10;
+
+pub struct Poll {
+	pub id: String,
+	pub question: String,
+	pub choices: Vec<String>,
+	/// Whether who voted for what is hidden
+	pub anonymous: bool,
+	/// Whether members can vote for more than one choice
+	pub multiple: bool,
+	pub author_id: UserId,
+	/// Where it was posted, once it is
+	pub message: Option<(ChannelId, MessageId)>,
+	/// UNIX timestamp of when it ends
+	pub ends: u64,
+	/// Indexes of the choices each member voted for
+	pub votes: HashMap<UserId, Vec<usize>>
+}
+
+impl Poll {
+	pub fn new(question: String, choices: Vec<String>, anonymous: bool, multiple: bool, author_id: UserId, ends: u64) -> Self {
+		Self {
+			id: errors::new_id(),
+			question,
+			choices,
+			anonymous,
+			multiple,
+			author_id,
+			message: None,
+			ends,
+			votes: HashMap::new()
+		}
+	}
+	fn from_value(id: &str, value: &Value) -> Option<Self> {
+		let id_of = |key: &str| value[key].as_str().and_then(|v| v.parse().ok());
+		let choices: Vec<String> = value["choices"].as_array()?.iter().filter_map(|v| v.as_str().map(|v| v.to_string())).collect();
+		Some(Self {
+			id: id.to_string(),
+			question: value["question"].as_str()?.to_string(),
+			anonymous: value["anonymous"].as_bool().unwrap_or(false),
+			multiple: value["multiple"].as_bool().unwrap_or(false),
+			author_id: UserId(id_of("author_id")?),
+			message: match (id_of("channel_id"), id_of("message_id")) {
+				(Some(channel_id), Some(message_id)) => Some((ChannelId(channel_id), MessageId(message_id))),
+				_ => None
+			},
+			ends: value["ends"].as_u64()?,
+			votes: value["votes"].as_object().map(|v| v.iter().filter_map(|(user_id, picked)| {
+				let picked = picked.as_array()?.iter().filter_map(|v| v.as_u64()).map(|v| v as usize).filter(|v| *v < choices.len()).collect();
+				Some((UserId(user_id.parse().ok()?), picked))
+			}).collect()).unwrap_or_default(),
+			choices
+		})
+	}
+	fn to_value(&self) -> Value {
+		serde_json::json!({
+			"question": self.question,
+			"choices": self.choices,
+			"anonymous": self.anonymous,
+			"multiple": self.multiple,
+			"author_id": self.author_id.to_string(),
+			"channel_id": self.message.map(|v| v.0.to_string()),
+			"message_id": self.message.map(|v| v.1.to_string()),
+			"ends": self.ends,
+			"votes": self.votes.iter().map(|(user_id, picked)| (user_id.to_string(), serde_json::json!(picked))).collect::<serde_json::Map<String, Value>>()
+		})
+	}
+	/// How many votes each choice has
+	pub fn counts(&self) -> Vec<usize> {
+		let mut counts = vec![0; self.choices.len()];
+		for picked in self.votes.values() {
+			for i in picked {
+				counts[*i] += 1;
+			}
+		}
+		counts
+	}
+	/// Changes the member's vote for a choice. Clicking the choice they voted
+	/// for takes the vote back, and on polls with one choice voting for
+	/// another moves it there
+	fn vote(&mut self, user_id: UserId, choice: usize) {
+		let picked = self.votes.entry(user_id).or_default();
+		match (picked.contains(&choice), self.multiple) {
+			(true, _) => picked.retain(|v| *v != choice),
+			(false, true) => picked.push(choice),
+			(false, false) => *picked = vec![choice]
+		}
+		picked.sort();
+		if picked.is_empty() {
+			self.votes.remove(&user_id);
+		}
+	}
+}
+
+pub fn get(db: &Database, guild_id: GuildId, id: &str) -> Option<Poll> {
+	Poll::from_value(id, db.get(&["guilds", guild_id.to_string().as_str(), "polls", id]))
+}
+
+pub fn save(db: &mut Database, guild_id: GuildId, poll: &Poll) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "polls", poll.id.as_str()], poll.to_value())
+}
+
+pub fn remove(db: &mut Database, guild_id: GuildId, id: &str) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "polls", id], Value::Null)
+}
+
+/// The server's polls that haven't ended, from the oldest
+pub fn list(db: &Database, guild_id: GuildId) -> Vec<Poll> {
+	let mut polls: Vec<Poll> = db.entries(&["guilds", guild_id.to_string().as_str(), "polls"])
+		.filter_map(|(id, v)| Poll::from_value(id, v))
+		.collect();
+	polls.sort_by_key(|v| v.ends);
+	polls
+}
+
+/// Saves only the member's vote, which is much less to write than the poll
+fn save_vote(db: &mut Database, guild_id: GuildId, poll: &Poll, user_id: UserId) -> Result<(), String> {
+	let value = match poll.votes.get(&user_id) {
+		Some(picked) => serde_json::json!(picked),
+		None => Value::Null
+	};
+	db.set(&["guilds", guild_id.to_string().as_str(), "polls", poll.id.as_str(), "votes", user_id.to_string().as_str()], value)
+}
+
+/// Saves a new poll along with the job that ends it, so that it can't be
+/// saved without one. Returns the job's ID
+pub fn create(db: &mut Database, guild_id: GuildId, poll: &Poll) -> Result<String, String> {
+	let guild = guild_id.to_string();
+	let job = Job::once(END_JOB, serde_json::json!({
+		"guild_id": guild,
+		"poll_id": poll.id
+	}), poll.ends);
+	scheduler::schedule_with(db, &job, &[(&["guilds", guild.as_str(), "polls", poll.id.as_str()], poll.to_value())])?;
+	Ok(job.id)
+}
+
+/// Remembers where the poll was posted, unless it ended meanwhile
+pub fn set_message(db: &mut Database, guild_id: GuildId, id: &str, (channel_id, message_id): (ChannelId, MessageId)) -> Result<(), String> {
+	let guild = guild_id.to_string();
+	if db.get(&["guilds", guild.as_str(), "polls", id]).is_null() {
+		return Ok(());
+	}
+	db.set_all(&[
+		(&["guilds", guild.as_str(), "polls", id, "channel_id"], channel_id.to_string().into()),
+		(&["guilds", guild.as_str(), "polls", id, "message_id"], message_id.to_string().into())
+	])
+}
+
+/// The poll's embed with its results so far, and its buttons
+pub fn render(chloe: &ChloeManager, poll: &Poll, ended: bool) -> (CreateEmbed, CreateComponents) {
+	let counts = poll.counts();
+	let total: usize = poll.votes.len();
+	let lines: Vec<String> = poll.choices.iter().enumerate().map(|(i, choice)| {
+		// Percentages are of voters, so they add up to more than 100% when
+		// members can vote for several choices
+		let percent = (counts[i] * 100).checked_div(total).unwrap_or(0);
+		let filled = percent / 5;
+		let mut line = format!(
+			"**{}.** {}\n`{}{}` {}% ({} {})",
+			i + 1, choice, "█".repeat(filled), "░".repeat(20 - filled), percent, counts[i], if counts[i] == 1 { "vote" } else { "votes" }
+		);
+		if !poll.anonymous && counts[i] > 0 {
+			let mut voters: Vec<UserId> = poll.votes.iter().filter(|(_, picked)| picked.contains(&i)).map(|(user_id, _)| *user_id).collect();
+			voters.sort();
+			let mut mentions: Vec<String> = voters.iter().take(MAX_LISTED_VOTERS).map(|v| format!("<@{}>", v)).collect();
+			if voters.len() > MAX_LISTED_VOTERS {
+				mentions.push(format!("and {} more", voters.len() - MAX_LISTED_VOTERS));
+			}
+			line += &format!("\n{}", mentions.join(" "));
+		}
+		line
+	}).collect();
+	let mut details = vec![format!("{} {}", total, if total == 1 { "voter" } else { "voters" })];
+	if poll.multiple {
+		details.push("Vote for as many as you want".to_string());
+	}
+	if poll.anonymous {
+		details.push("Anonymous".to_string());
+	}
+	details.push(format!("ID: {}", poll.id));
+	let mut embed = CreateEmbed::default();
+	embed.title(&poll.question)
+		.description(format!("{}\n\n{} {}", lines.join("\n"), if ended { "Ended" } else { "Ends" }, time::discord_timestamp(poll.ends, "R")))
+		.footer(|f| f.text(details.join(" · ")))
+		.color(chloe.config["embed_color"].as_i64().unwrap() as i32);
+	let mut components = CreateComponents::default();
+	for (row_index, chunk) in poll.choices.chunks(5).enumerate() {
+		let mut row = CreateActionRow::default();
+		for (i, choice) in chunk.iter().enumerate() {
+			let index = row_index * 5 + i;
+			let mut button = CreateButton::default();
+			button.custom_id(format!("{}{}:{}", CUSTOM_ID_PREFIX, poll.id, index))
+				.label(format!("{}. {}", index + 1, choice.chars().take(70).collect::<String>()))
+				.style(ButtonStyle::Primary);
+			row.add_button(button);
+		}
+		components.add_action_row(row);
+	}
+	if ended {
+		disable_all_components(&mut components);
+	}
+	(embed, components)
+}
+
+/// Describes who won, like "**Pizza** won with 3 votes"
+fn winner(poll: &Poll) -> String {
+	let counts = poll.counts();
+	let most = counts.iter().copied().max().unwrap_or(0);
+	if most == 0 {
+		return "Nobody voted".to_string();
+	}
+	let winners: Vec<String> = poll.choices.iter().zip(counts.iter()).filter(|(_, count)| **count == most).map(|(choice, _)| format!("**{}**", choice)).collect();
+	let votes = if most == 1 { "vote" } else { "votes" };
+	match winners.len() {
+		1 => format!("{} won with {} {}", winners[0], most, votes),
+		_ => format!("It's a tie between {}, with {} {} each", winners.join(", "), most, votes)
+	}
+}
+
+/// Ends the poll: its buttons are disabled, its final results are posted
+/// and it's forgotten. Ending a poll early cancels its scheduled end
+pub async fn end(chloe: &ChloeManager<'_>, ctx: &Context, db: &RwLock<Database>, guild_id: GuildId, id: &str) -> Result<Poll, String> {
+	let mut poll = {
+		let mut db = db.write().unwrap();
+		for job in scheduler::list(&db, Some(END_JOB)) {
+			if job.payload["poll_id"].as_str() == Some(id) {
+				scheduler::cancel(&mut db, &job.id)?;
+			}
+		}
+		get(&db, guild_id, id).ok_or("The poll doesn't exist")?
+	};
+	poll.ends = poll.ends.min(time::now());
+	let edited = match poll.message {
+		Some((channel_id, message_id)) => {
+			let (embed, components) = render(chloe, &poll, true);
+			channel_id.edit_message(&ctx.http, message_id, |m| {
+				m.set_embed(embed).set_components(components)
+			}).await.is_ok()
+		},
+		None => false
+	};
+	remove(&mut db.write().unwrap(), guild_id, id)?;
+	// Without the poll's message, there's nowhere to post the results
+	let (channel_id, message_id) = match poll.message {
+		Some(v) if edited => v,
+		_ => return Ok(poll)
+	};
+	let text = format!("The poll **{}** ended. {}", poll.question, winner(&poll));
+	channel_id.send_message(&ctx.http, |m| {
+		m.content(text)
+			.reference_message((channel_id, message_id))
+			.allowed_mentions(|a| a.empty_parse())
+	}).await.map_err(|e| format!("{e}"))?;
+	Ok(poll)
+}
+
+/// Handles a click on a poll's button by changing the member's vote and
+/// updating the results. Returns false if the component isn't a poll's
+pub async fn component(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, component: &MessageComponentInteraction) -> bool {
+	let (poll_id, choice) = match component.data.custom_id.strip_prefix(CUSTOM_ID_PREFIX).and_then(|v| v.split_once(':')) {
+		Some((poll_id, choice)) => (poll_id, choice.parse::<usize>().unwrap_or(usize::MAX)),
+		None => return false
+	};
+	let guild_id = match component.guild_id {
+		Some(v) => v,
+		None => return true
+	};
+	let poll = {
+		let mut db = db.write().unwrap();
+		match get(&db, guild_id, poll_id) {
+			Some(mut poll) if choice < poll.choices.len() && poll.ends > time::now() => {
+				poll.vote(component.user.id, choice);
+				if let Err(e) = save_vote(&mut db, guild_id, &poll, component.user.id) {
+					tracing::warn!(guild = %guild_id, poll = %poll_id, error = %e, "Unable to save a vote");
+				}
+				Some(poll)
+			},
+			_ => None
+		}
+	};
+	let responded = match poll {
+		Some(poll) => {
+			let (embed, components) = render(chloe, &poll, false);
+			component.create_interaction_response(&ctx.http, |r| {
+				r.kind(InteractionResponseType::UpdateMessage).interaction_response_data(|d| {
+					d.set_embed(embed).set_components(components)
+				})
+			}).await
+		},
+		None => component.create_interaction_response(&ctx.http, |r| {
+			r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| {
+				d.content("This poll has ended").ephemeral(true)
+			})
+		}).await
+	};
+	if let Err(e) = responded {
+		tracing::warn!(guild = %guild_id, poll = %poll_id, error = %e, "Unable to update a poll");
+	}
+	true
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn poll(multiple: bool) -> Poll {
+		Poll::new("Lunch?".to_string(), vec!["Pizza".to_string(), "Sushi".to_string(), "Tacos".to_string()], false, multiple, UserId(1), 0)
+	}
+
+	#[test]
+	fn vote_moves_single_choice_votes() {
+		let mut poll = poll(false);
+		poll.vote(UserId(2), 0);
+		poll.vote(UserId(3), 0);
+		assert_eq!(poll.counts(), vec![2, 0, 0]);
+		// Voting for another choice moves the vote there
+		poll.vote(UserId(2), 2);
+		assert_eq!(poll.votes[&UserId(2)], vec![2]);
+		assert_eq!(poll.counts(), vec![1, 0, 1]);
+		// Voting for the same choice again takes the vote back
+		poll.vote(UserId(2), 2);
+		assert!(!poll.votes.contains_key(&UserId(2)));
+		assert_eq!(poll.counts(), vec![1, 0, 0]);
+	}
+
+	#[test]
+	fn vote_toggles_multiple_choice_votes() {
+		let mut poll = poll(true);
+		poll.vote(UserId(2), 2);
+		poll.vote(UserId(2), 0);
+		assert_eq!(poll.votes[&UserId(2)], vec![0, 2]);
+		poll.vote(UserId(3), 2);
+		assert_eq!(poll.counts(), vec![1, 0, 2]);
+		poll.vote(UserId(2), 2);
+		assert_eq!(poll.votes[&UserId(2)], vec![0]);
+		poll.vote(UserId(2), 0);
+		assert!(!poll.votes.contains_key(&UserId(2)));
+		assert_eq!(poll.counts(), vec![0, 0, 1]);
+	}
+
+	#[test]
+	fn polls_round_trip_through_json() {
+		let mut poll = poll(true);
+		poll.vote(UserId(2), 1);
+		poll.vote(UserId(2), 0);
+		poll.message = Some((ChannelId(4), MessageId(5)));
+		let read = Poll::from_value(&poll.id, &poll.to_value()).unwrap();
+		assert_eq!(read.choices, poll.choices);
+		assert_eq!(read.votes, poll.votes);
+		assert_eq!(read.message, poll.message);
+		assert!(read.multiple && !read.anonymous);
+	}
+}
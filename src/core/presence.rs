@@ -0,0 +1,85 @@
+use std::sync::{RwLock, Arc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use serde_json::Value;
+use serenity::model::gateway::Activity;
+use super::db::Database;
+use super::ChloeManager;
+
+/// Position in the config's list of activities
+static ROTATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Makes an activity like "Playing d?help". `kind` is playing, watching,
+/// listening or competing
+pub fn activity(kind: &str, text: &str) -> Option<Activity> {
+	match kind {
+		"playing" => Some(Activity::playing(text)),
+		"watching" => Some(Activity::watching(text)),
+		"listening" => Some(Activity::listening(text)),
+		"competing" => Some(Activity::competing(text)),
+		_ => None
+	}
+}
+
+/// Replaces the placeholders {guilds}, {prefix}, {commands} and {shards}
+pub fn fill(chloe: &ChloeManager, text: &str) -> String {
+	text.replace("{guilds}", &chloe.guild_count().to_string())
+		.replace("{prefix}", chloe.config["prefix"].as_str().unwrap_or_default())
+		.replace("{commands}", &chloe.commands.len().to_string())
+		.replace("{shards}", &chloe.shard_count().to_string())
+}
+
+/// The activity set by the owners, which is shown instead of the config's
+pub fn get(db: &Database) -> Option<(String, String)> {
+	let presence = db.get(&["presence"]);
+	match (presence["type"].as_str(), presence["text"].as_str()) {
+		(Some(kind), Some(text)) => Some((kind.to_string(), text.to_string())),
+		_ => None
+	}
+}
+
+/// Sets the activity that is shown instead of the config's, or goes back to
+/// the config's with None
+pub fn set(db: &mut Database, presence: Option<(&str, &str)>) -> Result<(), String> {
+	db.set(&["presence"], match presence {
+		Some((kind, text)) => serde_json::json!({
+			"type": kind,
+			"text": text
+		}),
+		None => Value::Null
+	})
+}
+
+/// The activity that should be shown right now
+pub fn current(chloe: &ChloeManager, db: &Database) -> Option<Activity> {
+	let (kind, text) = match get(db) {
+		Some(v) => v,
+		None => {
+			let entries = chloe.config["presence"]["activities"].as_array()?;
+			if entries.is_empty() {
+				return None;
+			}
+			let entry = &entries[ROTATION.load(Ordering::Relaxed) % entries.len()];
+			(entry["type"].as_str().unwrap_or("playing").to_string(), entry["text"].as_str()?.to_string())
+		}
+	};
+	let activity = activity(&kind, &fill(chloe, &text));
+	if activity.is_none() {
+		tracing::warn!(kind, "Invalid activity type. It must be one of playing, watching, listening or competing");
+	}
+	activity
+}
+
+/// Moves on to the next activity of the config every `presence.interval`
+/// seconds. The current one is shown again even if there is only one, so
+/// that its placeholders stay up to date
+pub async fn rotate(chloe: &ChloeManager<'_>, db: Arc<RwLock<Database>>) {
+	// Discord doesn't like presences being updated too often
+	let interval = Duration::from_secs(chloe.config["presence"]["interval"].as_u64().unwrap_or(300).max(15));
+	loop {
+		tokio::time::sleep(interval).await;
+		ROTATION.fetch_add(1, Ordering::Relaxed);
+		let activity = current(chloe, &db.read().unwrap());
+		chloe.set_activity(activity).await;
+	}
+}
@@ -0,0 +1,430 @@
+use std::sync::{RwLock, Arc};
+use serde_json::Value;
+use serenity::builder::{CreateActionRow, CreateButton, CreateComponents, CreateEmbed, CreateSelectMenu};
+use serenity::client::Context;
+use serenity::model::application::component::ButtonStyle;
+use serenity::model::application::interaction::InteractionResponseType;
+use serenity::model::application::interaction::message_component::MessageComponentInteraction;
+use serenity::model::channel::{Reaction, ReactionType};
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use super::db::Database;
+use super::{errors, ChloeManager};
+
+/// Start of the custom IDs of panels' components, which are followed by the
+/// panel's ID and, for buttons, the role's ID
+const CUSTOM_ID_PREFIX: &str = "rolepanel:";
+/// Most roles a panel can have, which is what fits in a select menu and
+/// below what fits in reactions
+pub const MAX_ROLES: usize = 20;
+/// Most panels a server can have
+pub const MAX_PANELS: usize = 25;
+
+/// How members pick roles on a panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelStyle {
+	Buttons,
+	Select,
+	Reactions
+}
+
+impl PanelStyle {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"buttons" => Some(PanelStyle::Buttons),
+			"select" => Some(PanelStyle::Select),
+			"reactions" => Some(PanelStyle::Reactions),
+			_ => None
+		}
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			PanelStyle::Buttons => "buttons",
+			PanelStyle::Select => "select",
+			PanelStyle::Reactions => "reactions"
+		}
+	}
+}
+
+/// Which of a panel's roles members can have
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelMode {
+	/// One of them at most
+	Unique,
+	/// Any of them
+	Multiple,
+	/// Any of them, but they're never taken away
+	Verify
+}
+
+impl PanelMode {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"unique" => Some(PanelMode::Unique),
+			"multiple" => Some(PanelMode::Multiple),
+			"verify" => Some(PanelMode::Verify),
+			_ => None
+		}
+	}
+	pub fn name(&self) -> &'static str {
+		match self {
+			PanelMode::Unique => "unique",
+			PanelMode::Multiple => "multiple",
+			PanelMode::Verify => "verify"
+		}
+	}
+}
+
+/// A role on a panel
+pub struct PanelRole {
+	pub role_id: RoleId,
+	/// Text of its button or select menu option
+	pub label: String,
+	/// Emoji as it was written, like "🍎" or "<:name:123>"
+	pub emoji: Option<String>
+}
+
+pub struct Panel {
+	pub id: String,
+	pub style: PanelStyle,
+	pub mode: PanelMode,
+	pub title: String,
+	pub description: Option<String>,
+	pub roles: Vec<PanelRole>,
+	/// Where the panel was posted, if it was
+	pub message: Option<(ChannelId, MessageId)>
+}
+
+impl Panel {
+	pub fn new(style: PanelStyle, mode: PanelMode, title: String) -> Self {
+		Self {
+			id: errors::new_id(),
+			style,
+			mode,
+			title,
+			description: None,
+			roles: Vec::new(),
+			message: None
+		}
+	}
+	fn from_value(id: &str, value: &Value) -> Option<Self> {
+		let message = match (value["channel_id"].as_str(), value["message_id"].as_str()) {
+			(Some(channel_id), Some(message_id)) => Some((ChannelId(channel_id.parse().ok()?), MessageId(message_id.parse().ok()?))),
+			_ => None
+		};
+		Some(Self {
+			id: id.to_string(),
+			style: PanelStyle::from_name(value["style"].as_str()?)?,
+			mode: PanelMode::from_name(value["mode"].as_str()?)?,
+			title: value["title"].as_str()?.to_string(),
+			description: value["description"].as_str().map(|v| v.to_string()),
+			roles: value["roles"].as_array()?.iter().filter_map(|v| Some(PanelRole {
+				role_id: RoleId(v["role_id"].as_str()?.parse().ok()?),
+				label: v["label"].as_str()?.to_string(),
+				emoji: v["emoji"].as_str().map(|v| v.to_string())
+			})).collect(),
+			message
+		})
+	}
+	fn to_value(&self) -> Value {
+		serde_json::json!({
+			"style": self.style.name(),
+			"mode": self.mode.name(),
+			"title": self.title,
+			"description": self.description,
+			"roles": self.roles.iter().map(|v| serde_json::json!({
+				"role_id": v.role_id.to_string(),
+				"label": v.label,
+				"emoji": v.emoji
+			})).collect::<Vec<Value>>(),
+			"channel_id": self.message.map(|v| v.0.to_string()),
+			"message_id": self.message.map(|v| v.1.to_string())
+		})
+	}
+	fn role_ids(&self) -> Vec<RoleId> {
+		self.roles.iter().map(|v| v.role_id).collect()
+	}
+	/// The role that the emoji stands for
+	fn role_of_emoji(&self, emoji: &ReactionType) -> Option<RoleId> {
+		let key = emoji_key(emoji);
+		self.roles.iter()
+			.find(|v| v.emoji.as_deref().and_then(parse_emoji).is_some_and(|e| emoji_key(&e) == key))
+			.map(|v| v.role_id)
+	}
+}
+
+pub fn get(db: &Database, guild_id: GuildId, id: &str) -> Option<Panel> {
+	Panel::from_value(id, db.get(&["guilds", guild_id.to_string().as_str(), "rolepanels", id]))
+}
+
+pub fn list(db: &Database, guild_id: GuildId) -> Vec<Panel> {
+	match db.get(&["guilds", guild_id.to_string().as_str(), "rolepanels"]) {
+		Value::Object(v) => v.iter().filter_map(|(id, v)| Panel::from_value(id, v)).collect(),
+		_ => Vec::new()
+	}
+}
+
+pub fn save(db: &mut Database, guild_id: GuildId, panel: &Panel) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "rolepanels", panel.id.as_str()], panel.to_value())
+}
+
+pub fn remove(db: &mut Database, guild_id: GuildId, id: &str) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "rolepanels", id], Value::Null)
+}
+
+/// Reads an emoji written in a message, which is either a custom emoji or
+/// text without letters (Discord's own emojis are plain unicode)
+pub fn parse_emoji(text: &str) -> Option<ReactionType> {
+	let text = text.trim();
+	match ReactionType::try_from(text) {
+		Ok(emoji @ ReactionType::Custom { .. }) => Some(emoji),
+		Ok(emoji) if text.chars().count() <= 10 && !text.chars().any(|c| c.is_ascii_alphabetic() || c.is_whitespace()) => Some(emoji),
+		_ => None
+	}
+}
+
+/// What identifies an emoji, since custom emojis can be renamed
+fn emoji_key(emoji: &ReactionType) -> String {
+	match emoji {
+		ReactionType::Custom { id, .. } => id.to_string(),
+		ReactionType::Unicode(v) => v.clone(),
+		_ => String::new()
+	}
+}
+
+/// The panel's embed and components
+fn render(chloe: &ChloeManager, panel: &Panel) -> (CreateEmbed, CreateComponents) {
+	let mut embed = CreateEmbed::default();
+	let mut description = panel.description.clone().unwrap_or_default();
+	if panel.style == PanelStyle::Reactions {
+		let lines: Vec<String> = panel.roles.iter().map(|v| format!("{} <@&{}>", v.emoji.as_deref().unwrap_or_default(), v.role_id)).collect();
+		description = format!("{}\n\n{}", description, lines.join("\n")).trim().to_string();
+	}
+	embed.title(&panel.title)
+		.description(description)
+		.color(chloe.config["embed_color"].as_i64().unwrap() as i32)
+		.footer(|f| f.text(match panel.mode {
+			PanelMode::Unique => "You can have one of these roles",
+			PanelMode::Multiple => "You can have any of these roles",
+			PanelMode::Verify => "These roles can't be taken away once you have them"
+		}));
+	let mut components = CreateComponents::default();
+	match panel.style {
+		PanelStyle::Buttons => {
+			for chunk in panel.roles.chunks(5) {
+				let mut row = CreateActionRow::default();
+				for role in chunk {
+					let mut button = CreateButton::default();
+					button.custom_id(format!("{}{}:{}", CUSTOM_ID_PREFIX, panel.id, role.role_id))
+						.label(&role.label)
+						.style(ButtonStyle::Secondary);
+					if let Some(emoji) = role.emoji.as_deref().and_then(parse_emoji) {
+						button.emoji(emoji);
+					}
+					row.add_button(button);
+				}
+				components.add_action_row(row);
+			}
+		},
+		PanelStyle::Select if !panel.roles.is_empty() => {
+			let mut menu = CreateSelectMenu::default();
+			menu.custom_id(format!("{}{}", CUSTOM_ID_PREFIX, panel.id))
+				.placeholder("Choose your roles")
+				.min_values(0)
+				.max_values(if panel.mode == PanelMode::Unique { 1 } else { panel.roles.len() as u64 })
+				.options(|o| {
+					for role in panel.roles.iter() {
+						o.create_option(|o| {
+							o.label(&role.label).value(role.role_id);
+							if let Some(emoji) = role.emoji.as_deref().and_then(parse_emoji) {
+								o.emoji(emoji);
+							}
+							o
+						});
+					}
+					o
+				});
+			let mut row = CreateActionRow::default();
+			row.add_select_menu(menu);
+			components.add_action_row(row);
+		},
+		PanelStyle::Select | PanelStyle::Reactions => ()
+	}
+	(embed, components)
+}
+
+/// Posts the panel in a channel, or updates its message if it's already
+/// there. Returns the message's link
+pub async fn publish(chloe: &ChloeManager<'_>, ctx: &Context, guild_id: GuildId, panel: &mut Panel, channel_id: Option<ChannelId>) -> serenity::Result<String> {
+	let (embed, components) = render(chloe, panel);
+	// Posting it where it already is only updates it
+	let channel_id = channel_id.filter(|v| panel.message.map(|m| m.0) != Some(*v));
+	let (channel_id, message) = match (panel.message, channel_id) {
+		(_, Some(channel_id)) => (channel_id, channel_id.send_message(&ctx.http, |m| {
+			m.set_embed(embed).set_components(components)
+		}).await?),
+		(Some((channel_id, message_id)), None) => (channel_id, channel_id.edit_message(&ctx.http, message_id, |m| {
+			m.set_embed(embed).set_components(components)
+		}).await?),
+		(None, None) => return Err(serenity::Error::Other("The panel wasn't posted"))
+	};
+	if panel.style == PanelStyle::Reactions {
+		for emoji in panel.roles.iter().filter_map(|v| v.emoji.as_deref().and_then(parse_emoji)) {
+			message.react(&ctx.http, emoji).await?;
+		}
+	}
+	panel.message = Some((channel_id, message.id));
+	Ok(message.id.link(channel_id, Some(guild_id)))
+}
+
+/// How a member picked roles on a panel
+enum Pick {
+	/// Clicked a role's button
+	Toggle(RoleId),
+	/// Reacted with a role's emoji
+	Give(RoleId),
+	/// Removed their reaction
+	Take(RoleId),
+	/// Chose these roles in the select menu
+	Set(Vec<RoleId>)
+}
+
+/// Works out which roles to give the member and which to take away
+fn changes(panel: &Panel, current: &[RoleId], pick: Pick) -> (Vec<RoleId>, Vec<RoleId>) {
+	let panel_roles = panel.role_ids();
+	let held: Vec<RoleId> = panel_roles.iter().filter(|v| current.contains(v)).copied().collect();
+	match pick {
+		Pick::Toggle(role_id) => changes(panel, current, match held.contains(&role_id) {
+			true => Pick::Take(role_id),
+			false => Pick::Give(role_id)
+		}),
+		Pick::Give(role_id) => {
+			let add = if held.contains(&role_id) { Vec::new() } else { vec![role_id] };
+			let remove = match panel.mode {
+				PanelMode::Unique => held.into_iter().filter(|v| *v != role_id).collect(),
+				PanelMode::Multiple | PanelMode::Verify => Vec::new()
+			};
+			(add, remove)
+		},
+		Pick::Take(role_id) => match panel.mode {
+			PanelMode::Verify => (Vec::new(), Vec::new()),
+			PanelMode::Unique | PanelMode::Multiple => (Vec::new(), vec![role_id])
+		},
+		Pick::Set(picked) => {
+			let picked: Vec<RoleId> = picked.into_iter().filter(|v| panel_roles.contains(v)).collect();
+			let add = picked.iter().filter(|v| !held.contains(v)).copied().collect();
+			let remove = match panel.mode {
+				PanelMode::Verify => Vec::new(),
+				PanelMode::Unique | PanelMode::Multiple => held.into_iter().filter(|v| !picked.contains(v)).collect()
+			};
+			(add, remove)
+		}
+	}
+}
+
+/// Gives and takes away the member's roles
+async fn apply(ctx: &Context, guild_id: GuildId, user_id: UserId, add: &[RoleId], remove: &[RoleId]) -> serenity::Result<()> {
+	for role_id in add {
+		ctx.http.add_member_role(guild_id.0, user_id.0, role_id.0, Some("Role panel")).await?;
+	}
+	for role_id in remove {
+		ctx.http.remove_member_role(guild_id.0, user_id.0, role_id.0, Some("Role panel")).await?;
+	}
+	Ok(())
+}
+
+/// Handles a click on a panel's button or a choice in its select menu.
+/// Returns false if the component isn't a panel's
+pub async fn component(ctx: &Context, db: Arc<RwLock<Database>>, component: &MessageComponentInteraction) -> bool {
+	let ids = match component.data.custom_id.strip_prefix(CUSTOM_ID_PREFIX) {
+		Some(v) => v,
+		None => return false
+	};
+	let (panel_id, role_id) = match ids.split_once(':') {
+		Some((panel_id, role_id)) => match role_id.parse() {
+			Ok(role_id) => (panel_id, Some(RoleId(role_id))),
+			Err(..) => return true
+		},
+		None => (ids, None)
+	};
+	let (guild_id, member) = match (component.guild_id, &component.member) {
+		(Some(guild_id), Some(member)) => (guild_id, member),
+		_ => return true
+	};
+	// Changing roles can take longer than Discord waits for a response
+	let deferred = component.create_interaction_response(&ctx.http, |r| {
+		r.kind(InteractionResponseType::DeferredChannelMessageWithSource).interaction_response_data(|d| {
+			d.ephemeral(true)
+		})
+	}).await;
+	if deferred.is_err() {
+		return true;
+	}
+	let content = match get(&db.read().unwrap(), guild_id, panel_id) {
+		// Only the panel's own roles can be picked from it
+		Some(panel) => match role_id {
+			Some(role_id) if !panel.role_ids().contains(&role_id) => Err("That role isn't on this panel anymore".to_string()),
+			Some(role_id) => Ok(changes(&panel, &member.roles, Pick::Toggle(role_id))),
+			None => Ok(changes(&panel, &member.roles, Pick::Set(component.data.values.iter().filter_map(|v| v.parse().ok()).map(RoleId).collect())))
+		},
+		None => Err("This panel was deleted".to_string())
+	};
+	let content = match content {
+		Ok((add, remove)) => match apply(ctx, guild_id, member.user.id, &add, &remove).await {
+			Ok(..) => {
+				let mut lines = Vec::new();
+				if !add.is_empty() {
+					lines.push(format!("Gave you {}", add.iter().map(|v| format!("<@&{}>", v)).collect::<Vec<String>>().join(", ")));
+				}
+				if !remove.is_empty() {
+					lines.push(format!("Took away {}", remove.iter().map(|v| format!("<@&{}>", v)).collect::<Vec<String>>().join(", ")));
+				}
+				match lines.is_empty() {
+					true => "Your roles didn't change".to_string(),
+					false => lines.join("\n")
+				}
+			},
+			Err(e) => {
+				tracing::warn!(guild = %guild_id, error = %e, "Unable to change roles from a role panel");
+				"I couldn't change your roles. I need the Manage Roles permission, and my highest role must be above them".to_string()
+			}
+		},
+		Err(e) => e
+	};
+	component.edit_original_interaction_response(&ctx.http, |r| r.content(content)).await.ok();
+	true
+}
+
+/// Gives or takes away a role when a member reacts on a reaction panel
+pub async fn reaction(ctx: &Context, db: Arc<RwLock<Database>>, reaction: &Reaction, added: bool) {
+	let (guild_id, user_id) = match (reaction.guild_id, reaction.user_id) {
+		(Some(guild_id), Some(user_id)) => (guild_id, user_id),
+		_ => return
+	};
+	// Including my own reactions, which are added when the panel is posted
+	if reaction.member.as_ref().is_some_and(|m| m.user.as_ref().is_some_and(|u| u.bot)) {
+		return;
+	}
+	let (add, remove) = {
+		let db = db.read().unwrap();
+		let panel = match list(&db, guild_id).into_iter().find(|v| {
+			v.style == PanelStyle::Reactions && v.message.is_some_and(|m| m.1 == reaction.message_id)
+		}) {
+			Some(v) => v,
+			None => return
+		};
+		let role_id = match panel.role_of_emoji(&reaction.emoji) {
+			Some(v) => v,
+			None => return
+		};
+		// Removed reactions don't say what roles the member has, which doesn't
+		// matter since taking away a role doesn't depend on them
+		let current = reaction.member.as_ref().map(|m| m.roles.clone()).unwrap_or_default();
+		match added {
+			true => changes(&panel, &current, Pick::Give(role_id)),
+			false => changes(&panel, &current, Pick::Take(role_id))
+		}
+	};
+	if let Err(e) = apply(ctx, guild_id, user_id, &add, &remove).await {
+		tracing::warn!(guild = %guild_id, error = %e, "Unable to change roles from a reaction panel");
+	}
+}
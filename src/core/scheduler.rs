@@ -0,0 +1,591 @@
+use std::pin::Pin;
+use std::future::Future;
+use std::collections::HashSet;
+use std::sync::{RwLock, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use serde_json::Value;
+use serenity::builder::CreateMessage;
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, UserId};
+use super::db::Database;
+use super::timezone::TimeZone;
+use super::{ChloeManager, errors, time};
+
+/// Runs a job. Errors are logged, and failed one-shot jobs are tried again a few times
+pub type JobFn = fn(job: JobContext) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+
+/// How many times a one-shot job is tried before giving up on it
+const MAX_ATTEMPTS: u64 = 3;
+/// How many missed runs of a recurring job are run at most with `CatchUp::All`
+const MAX_CATCH_UP_RUNS: usize = 100;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// A 5 field cron expression (minute, hour, day of the month, month and day
+/// of the week), evaluated in UTC unless it has a time zone. Fields can be "*",
+/// numbers, ranges like "1-5", lists like "1,15" and steps like "*/10"
+#[derive(Debug, Clone)]
+pub struct Cron {
+	expr: String,
+	minutes: u64,
+	hours: u64,
+	days: u64,
+	months: u64,
+	weekdays: u64,
+	/// Time zone the expression is evaluated in
+	zone: TimeZone
+}
+
+impl Cron {
+	pub fn parse(expr: &str) -> Result<Self, String> {
+		let fields: Vec<&str> = expr.split_whitespace().collect();
+		if fields.len() != 5 {
+			return Err(format!("\"{expr}\" must have 5 fields: minute, hour, day of the month, month and day of the week"));
+		}
+		let field = |i: usize, name: &str, min: u32, max: u32| match parse_field(fields[i], min, max) {
+			Some(v) => Ok(v),
+			None => Err(format!("Invalid {name} \"{}\" in \"{expr}\". It must be between {min} and {max}", fields[i]))
+		};
+		let mut weekdays = field(4, "day of the week", 0, 7)?;
+		// Both 0 and 7 are Sunday
+		if weekdays & (1 << 7) != 0 {
+			weekdays |= 1;
+		}
+		Ok(Self {
+			expr: fields.join(" "),
+			minutes: field(0, "minute", 0, 59)?,
+			hours: field(1, "hour", 0, 23)?,
+			days: field(2, "day of the month", 1, 31)?,
+			months: field(3, "month", 1, 12)?,
+			weekdays,
+			zone: TimeZone::UTC
+		})
+	}
+	/// Evaluates the expression in a time zone instead of in UTC, so runs
+	/// follow its daylight saving time
+	pub fn in_zone(mut self, zone: TimeZone) -> Self {
+		self.zone = zone;
+		self
+	}
+	pub fn expr(&self) -> &str {
+		&self.expr
+	}
+	pub fn zone(&self) -> &TimeZone {
+		&self.zone
+	}
+	fn matches_day(&self, timestamp: u64) -> bool {
+		let (_, month, day) = time::civil(timestamp);
+		if self.months & (1 << month) == 0 {
+			return false;
+		}
+		let day_matches = self.days & (1 << day) != 0;
+		let weekday_matches = self.weekdays & (1 << time::weekday(timestamp)) != 0;
+		// Like in cron, a day matches either field when both are restricted
+		match (self.days == mask(1, 31), self.weekdays & mask(0, 6) == mask(0, 6)) {
+			(false, false) => day_matches || weekday_matches,
+			_ => day_matches && weekday_matches
+		}
+	}
+	/// The first time after `timestamp` that matches
+	pub fn next_after(&self, timestamp: u64) -> Option<u64> {
+		let mut local = u64::try_from(self.zone.to_local(timestamp)).ok()?;
+		// Times that happen twice when the clocks go back only match the first
+		// time, which can be before `timestamp`
+		loop {
+			local = self.next_after_local(local)?;
+			let next = self.zone.to_utc(local as i64)?;
+			if next > timestamp {
+				return Some(next);
+			}
+		}
+	}
+	fn next_after_local(&self, timestamp: u64) -> Option<u64> {
+		let start = (timestamp / 60 + 1) * 60;
+		// Long enough to reach any day that exists, like February 29th on a Monday
+		for day in 0..366 * 30 {
+			let day_start = (start / 86400 + day) * 86400;
+			if !self.matches_day(day_start) {
+				continue;
+			}
+			for hour in (0..24).filter(|v| self.hours & (1 << v) != 0) {
+				for minute in (0..60).filter(|v| self.minutes & (1 << v) != 0) {
+					let t = day_start + hour * 3600 + minute * 60;
+					if t >= start {
+						return Some(t);
+					}
+				}
+			}
+		}
+		None
+	}
+}
+
+fn mask(min: u32, max: u32) -> u64 {
+	(min..=max).fold(0, |mask, v| mask | 1 << v)
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+	let mut bits = 0;
+	for part in field.split(',') {
+		let (range, step) = match part.split_once('/') {
+			Some((range, step)) => (range, step.parse().ok().filter(|v| *v > 0)?),
+			None => (part, 1)
+		};
+		let (first, last) = match range {
+			"*" => (min, max),
+			_ => match range.split_once('-') {
+				Some((first, last)) => (first.parse().ok()?, last.parse().ok()?),
+				None => {
+					let v = range.parse().ok()?;
+					// "5/15" means from 5 to the end, every 15
+					(v, if step > 1 { max } else { v })
+				}
+			}
+		};
+		if first < min || last > max || first > last {
+			return None;
+		}
+		for v in (first..=last).step_by(step as usize) {
+			bits |= 1 << v;
+		}
+	}
+	Some(bits)
+}
+
+#[derive(Debug, Clone)]
+pub enum Schedule {
+	/// Runs once, then the job is deleted
+	Once,
+	/// Runs every this many seconds
+	Every(u64),
+	Cron(Cron)
+}
+
+impl Schedule {
+	/// The next run after `timestamp`, or None if there are no more
+	pub fn next_after(&self, timestamp: u64) -> Option<u64> {
+		match self {
+			Schedule::Once => None,
+			Schedule::Every(secs) => Some(timestamp + secs),
+			Schedule::Cron(cron) => cron.next_after(timestamp)
+		}
+	}
+	fn to_value(&self) -> Value {
+		match self {
+			Schedule::Once => Value::Null,
+			Schedule::Every(secs) => serde_json::json!({ "every": secs }),
+			Schedule::Cron(cron) => serde_json::json!({ "cron": cron.expr(), "timezone": cron.zone().name() })
+		}
+	}
+	fn from_value(value: &Value) -> Option<Self> {
+		if value.is_null() {
+			return Some(Schedule::Once);
+		}
+		if let Some(secs) = value["every"].as_u64() {
+			return Some(Schedule::Every(secs.max(1)));
+		}
+		let cron = Cron::parse(value["cron"].as_str()?).ok()?;
+		// Schedules used to be stored with a UTC offset instead
+		let zone = match value["timezone"].as_str() {
+			Some(name) => TimeZone::parse(name)?,
+			None => TimeZone::Fixed(value["offset"].as_i64().unwrap_or(0))
+		};
+		Some(Schedule::Cron(cron.in_zone(zone)))
+	}
+}
+
+/// What to do with the runs that were missed while the bot was offline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchUp {
+	/// Forget about them
+	Skip,
+	/// Run once for all of them
+	Once,
+	/// Run once for each of them
+	All
+}
+
+impl CatchUp {
+	pub fn name(&self) -> &'static str {
+		match self {
+			CatchUp::Skip => "skip",
+			CatchUp::Once => "once",
+			CatchUp::All => "all"
+		}
+	}
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"skip" => Some(CatchUp::Skip),
+			"once" => Some(CatchUp::Once),
+			"all" => Some(CatchUp::All),
+			_ => None
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Job {
+	pub id: String,
+	/// Name of the handler that runs the job, as registered with `ChloeManager::job`
+	pub kind: String,
+	/// Whatever the handler needs to know
+	pub payload: Value,
+	/// UNIX timestamp of the next run
+	pub next_run: u64,
+	pub schedule: Schedule,
+	pub catch_up: CatchUp,
+	/// How many times the current run has failed
+	attempts: u64
+}
+
+impl Job {
+	/// A job that runs once at `timestamp`
+	pub fn once(kind: &str, payload: Value, timestamp: u64) -> Self {
+		Self {
+			id: errors::new_id(),
+			kind: kind.to_string(),
+			payload,
+			next_run: timestamp,
+			schedule: Schedule::Once,
+			catch_up: CatchUp::Once,
+			attempts: 0
+		}
+	}
+	/// A job that runs on a schedule, starting with its next run from now
+	pub fn recurring(kind: &str, payload: Value, schedule: Schedule) -> Result<Self, String> {
+		let next_run = match schedule.next_after(time::now()) {
+			Some(v) => v,
+			None => return Err("The schedule never runs".to_string())
+		};
+		Ok(Self {
+			next_run,
+			schedule,
+			..Self::once(kind, payload, 0)
+		})
+	}
+	pub fn catch_up(mut self, catch_up: CatchUp) -> Self {
+		self.catch_up = catch_up;
+		self
+	}
+	fn to_value(&self) -> Value {
+		serde_json::json!({
+			"kind": self.kind,
+			"payload": self.payload,
+			"next_run": self.next_run,
+			"schedule": self.schedule.to_value(),
+			"catch_up": self.catch_up.name(),
+			"attempts": self.attempts
+		})
+	}
+	fn from_value(id: &str, value: &Value) -> Option<Self> {
+		Some(Self {
+			id: id.to_string(),
+			kind: value["kind"].as_str()?.to_string(),
+			payload: value["payload"].clone(),
+			next_run: value["next_run"].as_u64()?,
+			schedule: Schedule::from_value(&value["schedule"])?,
+			catch_up: value["catch_up"].as_str().and_then(CatchUp::from_name).unwrap_or(CatchUp::Once),
+			attempts: value["attempts"].as_u64().unwrap_or(0)
+		})
+	}
+}
+
+/// What a job's handler gets to work with
+pub struct JobContext {
+	pub id: String,
+	pub kind: String,
+	pub payload: Value,
+	/// When this run was supposed to happen, which is in the past when catching up
+	pub scheduled_for: u64,
+	pub ctx: serenity::client::Context,
+	pub db: Arc<RwLock<Database>>
+}
+
+impl JobContext {
+	pub async fn send_message(&self, channel_id: ChannelId, message: CreateMessage<'_>) -> serenity::Result<Message> {
+		channel_id.send_message(self.ctx.http.as_ref(), |m| { *m = message; m }).await
+	}
+	pub async fn send_dm(&self, user_id: UserId, message: CreateMessage<'_>) -> serenity::Result<Message> {
+		let channel = user_id.create_dm_channel(self.ctx.http.as_ref()).await?;
+		self.send_message(channel.id, message).await
+	}
+}
+
+/// Stores the job, replacing any job with the same ID
+pub fn schedule(db: &mut Database, job: &Job) -> Result<(), String> {
+	db.set(&["jobs", job.id.as_str()], job.to_value())
+}
+
+/// Stores the job along with other changes, all in one write, so that the
+/// job isn't stored without them or the other way around
+pub fn schedule_with(db: &mut Database, job: &Job, also: &[(&[&str], Value)]) -> Result<(), String> {
+	let path = ["jobs", job.id.as_str()];
+	let mut changes: Vec<(&[&str], Value)> = vec![(&path, job.to_value())];
+	changes.extend(also.iter().cloned());
+	db.set_all(&changes)
+}
+
+/// Deletes the job. Returns whether it existed
+pub fn cancel(db: &mut Database, id: &str) -> Result<bool, String> {
+	if db.get(&["jobs", id]).is_null() {
+		return Ok(false);
+	}
+	db.set(&["jobs", id], Value::Null)?;
+	Ok(true)
+}
+
+pub fn get(db: &Database, id: &str) -> Option<Job> {
+	Job::from_value(id, db.get(&["jobs", id]))
+}
+
+/// Jobs of a kind (or all of them with None), soonest first
+pub fn list(db: &Database, kind: Option<&str>) -> Vec<Job> {
+	let mut jobs: Vec<Job> = match db.get(&["jobs"]) {
+		Value::Object(v) => v.iter()
+			.filter_map(|(id, job)| Job::from_value(id, job))
+			.filter(|job| kind.is_none() || kind == Some(job.kind.as_str()))
+			.collect(),
+		_ => Vec::new()
+	};
+	jobs.sort_by_key(|job| job.next_run);
+	jobs
+}
+
+/// Runs jobs when they are due. Only one scheduler runs per process, and it
+/// should only be started on shard 0 so that jobs don't run more than once.
+/// One-shot jobs are only deleted once they're done, so if I stop right after
+/// one ran, it runs again when I start
+pub async fn run(chloe: &'static ChloeManager<'static>, ctx: serenity::client::Context, db: Arc<RwLock<Database>>) {
+	if STARTED.swap(true, Ordering::SeqCst) {
+		return;
+	}
+	let started = time::now();
+	// One-shot jobs that are running, which stay stored until they're done
+	let running: Arc<Mutex<HashSet<String>>> = Arc::default();
+	tracing::info!("Starting the scheduler");
+	let mut interval = tokio::time::interval(Duration::from_secs(1));
+	loop {
+		interval.tick().await;
+		let now = time::now();
+		let due: Vec<Job> = match db.read().unwrap().get(&["jobs"]) {
+			Value::Object(v) => v.iter()
+				.filter(|(_, job)| job["next_run"].as_u64().is_some_and(|v| v <= now))
+				.filter_map(|(id, job)| match Job::from_value(id, job) {
+					Some(v) => Some(v),
+					None => {
+						tracing::warn!(id, "Ignoring an invalid job");
+						None
+					}
+				})
+				.collect(),
+			_ => continue
+		};
+		for job in due {
+			if running.lock().unwrap().contains(&job.id) {
+				continue;
+			}
+			let handler = match chloe.job_handler(&job.kind) {
+				Some(v) => v,
+				None => {
+					tracing::warn!(id = %job.id, kind = %job.kind, "Deleting a job that nothing can run");
+					if let Err(e) = cancel(&mut db.write().unwrap(), &job.id) {
+						tracing::error!(error = %e, "Unable to delete the job");
+					}
+					continue;
+				}
+			};
+			// Runs that were due before I started were missed while I was offline
+			let runs = match job.next_run < started {
+				true => missed_runs(&job, started),
+				false => vec![job.next_run]
+			};
+			// Taken before the job is touched, so that shutting down leaves it as it is
+			let in_flight = match chloe.shutdown.start_command() {
+				Some(v) => v,
+				None => return
+			};
+			// Recurring jobs are moved on before they run, so that they run at
+			// most once per run even if they take longer than a tick
+			let once = match job.schedule.next_after(now.max(job.next_run)) {
+				Some(next_run) => {
+					let mut next = job.clone();
+					next.attempts = 0;
+					next.next_run = next_run;
+					if let Err(e) = schedule(&mut db.write().unwrap(), &next) {
+						tracing::error!(id = %job.id, error = %e, "Unable to reschedule the job");
+						continue;
+					}
+					false
+				},
+				None => {
+					running.lock().unwrap().insert(job.id.clone());
+					true
+				}
+			};
+			let ctx = ctx.clone();
+			let db = db.clone();
+			let running = running.clone();
+			tokio::spawn(async move {
+				let _in_flight = in_flight;
+				let mut failed = false;
+				for scheduled_for in runs {
+					let result = handler(JobContext {
+						id: job.id.clone(),
+						kind: job.kind.clone(),
+						payload: job.payload.clone(),
+						scheduled_for,
+						ctx: ctx.clone(),
+						db: db.clone()
+					}).await;
+					if let Err(e) = result {
+						tracing::error!(id = %job.id, kind = %job.kind, error = %e, "Job failed");
+						failed = true;
+					}
+				}
+				if once {
+					finish(&job, failed, &mut db.write().unwrap());
+					running.lock().unwrap().remove(&job.id);
+				}
+			});
+		}
+	}
+}
+
+/// The runs of a job that were missed while I was offline, according to its catch-up policy
+fn missed_runs(job: &Job, started: u64) -> Vec<u64> {
+	if job.catch_up == CatchUp::Skip {
+		return Vec::new();
+	}
+	let mut runs = vec![job.next_run];
+	if job.catch_up == CatchUp::All {
+		while let Some(next_run) = job.schedule.next_after(*runs.last().unwrap()) {
+			if next_run >= started || runs.len() >= MAX_CATCH_UP_RUNS {
+				break;
+			}
+			runs.push(next_run);
+		}
+	}
+	tracing::info!(id = %job.id, kind = %job.kind, runs = runs.len(), "Catching up on missed runs");
+	runs
+}
+
+/// Deletes a one-shot job that ran, or schedules it to be tried again a bit
+/// later if it failed. Jobs that were cancelled or replaced meanwhile are left
+/// alone
+fn finish(job: &Job, failed: bool, db: &mut Database) {
+	if !get(db, &job.id).is_some_and(|v| v.next_run == job.next_run && v.attempts == job.attempts) {
+		return;
+	}
+	let result = match failed && job.attempts + 1 < MAX_ATTEMPTS {
+		true => {
+			let mut job = job.clone();
+			job.attempts += 1;
+			job.next_run = time::now() + 60 * job.attempts;
+			schedule(db, &job)
+		},
+		false => cancel(db, &job.id).map(|_| ())
+	};
+	if let Err(e) = result {
+		tracing::error!(id = %job.id, error = %e, "Unable to finish the job");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::core::timezone::Zone;
+
+	/// UNIX timestamp of a UTC date and time
+	fn at(year: i64, month: u32, day: u32, hour: u64, minute: u64) -> u64 {
+		time::days_from_civil(year, month, day) as u64 * 86400 + hour * 3600 + minute * 60
+	}
+
+	#[test]
+	fn cron_parses_fields() {
+		let cron = Cron::parse("*/15 9-17 1,15 * 1-5").unwrap();
+		assert_eq!(cron.minutes, 1 << 0 | 1 << 15 | 1 << 30 | 1 << 45);
+		assert_eq!(cron.hours, mask(9, 17));
+		assert_eq!(cron.days, 1 << 1 | 1 << 15);
+		assert_eq!(cron.months, mask(1, 12));
+		assert_eq!(cron.weekdays, mask(1, 5));
+		// From 5 to the end, every 20
+		assert_eq!(Cron::parse("5/20 * * * *").unwrap().minutes, 1 << 5 | 1 << 25 | 1 << 45);
+		assert_eq!(Cron::parse("10-30/10 * * * *").unwrap().minutes, 1 << 10 | 1 << 20 | 1 << 30);
+		// Both 0 and 7 are Sunday
+		assert_eq!(Cron::parse("0 0 * * 7").unwrap().weekdays & 1, 1);
+		assert_eq!(Cron::parse("0  0 *  * *").unwrap().expr(), "0 0 * * *");
+	}
+
+	#[test]
+	fn cron_rejects_invalid_fields() {
+		for expr in ["* * * *", "* * * * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "* * * 13 *", "* * * * 8", "5-1 * * * *", "*/0 * * * *", "a * * * *", "1-2-3 * * * *"] {
+			assert!(Cron::parse(expr).is_err(), "{expr}");
+		}
+	}
+
+	#[test]
+	fn cron_next_after() {
+		let cron = Cron::parse("*/15 9-17 * * 1-5").unwrap();
+		// 2024-01-05 was a Friday
+		assert_eq!(cron.next_after(at(2024, 1, 5, 9, 0)), Some(at(2024, 1, 5, 9, 15)));
+		assert_eq!(cron.next_after(at(2024, 1, 5, 9, 7)), Some(at(2024, 1, 5, 9, 15)));
+		assert_eq!(cron.next_after(at(2024, 1, 5, 17, 45)), Some(at(2024, 1, 8, 9, 0)));
+		// Every day at midnight, across the end of a year
+		let cron = Cron::parse("0 0 * * *").unwrap();
+		assert_eq!(cron.next_after(at(2024, 12, 31, 12, 0)), Some(at(2025, 1, 1, 0, 0)));
+		// February 29th only comes every 4 years
+		let cron = Cron::parse("0 12 29 2 *").unwrap();
+		assert_eq!(cron.next_after(at(2024, 3, 1, 0, 0)), Some(at(2028, 2, 29, 12, 0)));
+	}
+
+	#[test]
+	fn cron_matches_either_day_field_when_both_are_restricted() {
+		// The 13th, and every Friday
+		let cron = Cron::parse("0 0 13 * 5").unwrap();
+		// 2024-09-06 was a Friday, and 2024-09-13 both
+		assert_eq!(cron.next_after(at(2024, 9, 1, 0, 0)), Some(at(2024, 9, 6, 0, 0)));
+		assert_eq!(cron.next_after(at(2024, 9, 10, 0, 0)), Some(at(2024, 9, 13, 0, 0)));
+		// Only the 13th
+		let cron = Cron::parse("0 0 13 * *").unwrap();
+		assert_eq!(cron.next_after(at(2024, 9, 1, 0, 0)), Some(at(2024, 9, 13, 0, 0)));
+	}
+
+	#[test]
+	fn cron_with_offset_crosses_day_boundaries() {
+		// Mondays at 1:00 in UTC+2 are Sundays at 23:00 in UTC. 2024-01-07 was a Sunday
+		let cron = Cron::parse("0 1 * * 1").unwrap().in_zone(TimeZone::Fixed(2 * 3600));
+		assert_eq!(cron.next_after(at(2024, 1, 7, 12, 0)), Some(at(2024, 1, 7, 23, 0)));
+		assert_eq!(cron.next_after(at(2024, 1, 7, 23, 0)), Some(at(2024, 1, 14, 23, 0)));
+		// 23:30 in UTC-5 is 4:30 in UTC on the next day
+		let cron = Cron::parse("30 23 * * *").unwrap().in_zone(TimeZone::Fixed(-5 * 3600));
+		assert_eq!(cron.next_after(at(2024, 12, 31, 12, 0)), Some(at(2025, 1, 1, 4, 30)));
+	}
+
+	#[test]
+	fn cron_in_zone_follows_daylight_saving_time() {
+		let zone = TimeZone::Zone(Arc::new(Zone::from_rule("Europe/Berlin", "CET-1CEST,M3.5.0,M10.5.0/3").unwrap()));
+		let cron = Cron::parse("0 9 * * *").unwrap().in_zone(zone.clone());
+		// The clocks went forward on 2024-03-31
+		assert_eq!(cron.next_after(at(2024, 3, 30, 12, 0)), Some(at(2024, 3, 31, 7, 0)));
+		assert_eq!(cron.next_after(at(2024, 3, 29, 12, 0)), Some(at(2024, 3, 30, 8, 0)));
+		// 2:30 was skipped that day, so it's at 3:30 instead
+		let cron = Cron::parse("30 2 * * *").unwrap().in_zone(zone.clone());
+		assert_eq!(cron.next_after(at(2024, 3, 30, 12, 0)), Some(at(2024, 3, 31, 1, 30)));
+		// They went back on 2024-10-27, when 2:30 happened twice, and it only runs the first time
+		assert_eq!(cron.next_after(at(2024, 10, 26, 12, 0)), Some(at(2024, 10, 27, 0, 30)));
+		assert_eq!(cron.next_after(at(2024, 10, 27, 0, 30)), Some(at(2024, 10, 28, 1, 30)));
+	}
+
+	#[test]
+	fn schedule_round_trips_through_json() {
+		let schedule = Schedule::Cron(Cron::parse("0 9 * * 1").unwrap().in_zone(TimeZone::Fixed(3600)));
+		let schedule = Schedule::from_value(&schedule.to_value()).unwrap();
+		assert_eq!(schedule.next_after(at(2024, 1, 7, 0, 0)), Some(at(2024, 1, 8, 8, 0)));
+		// Schedules that were stored with a UTC offset
+		let schedule = Schedule::from_value(&serde_json::json!({ "cron": "0 9 * * 1", "offset": -3600 })).unwrap();
+		assert_eq!(schedule.next_after(at(2024, 1, 7, 0, 0)), Some(at(2024, 1, 8, 10, 0)));
+		assert!(matches!(Schedule::from_value(&Value::Null), Some(Schedule::Once)));
+		assert_eq!(Schedule::from_value(&serde_json::json!({ "every": 60 })).unwrap().next_after(100), Some(160));
+	}
+}
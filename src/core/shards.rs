@@ -0,0 +1,39 @@
+use std::time::Duration;
+use serenity::client::bridge::gateway::ShardManager;
+use serenity::gateway::ConnectionStage;
+use serenity::model::gateway::Activity;
+use serenity::model::id::GuildId;
+use serenity::prelude::Mutex;
+
+pub struct ShardStatus {
+	pub id: u64,
+	pub stage: ConnectionStage,
+	/// How long the last heartbeat took to be acknowledged
+	pub latency: Option<Duration>
+}
+
+/// The shard that receives a guild's events
+pub fn shard_of(guild_id: GuildId, shard_count: u64) -> u64 {
+	(guild_id.0 >> 22) % shard_count.max(1)
+}
+
+/// Status of the shards that this process runs, by shard ID
+pub async fn statuses(shard_manager: &Mutex<ShardManager>) -> Vec<ShardStatus> {
+	let shard_manager = shard_manager.lock().await;
+	let runners = shard_manager.runners.lock().await;
+	let mut shards: Vec<ShardStatus> = runners.iter().map(|(id, info)| ShardStatus {
+		id: id.0,
+		stage: info.stage,
+		latency: info.latency
+	}).collect();
+	shards.sort_by_key(|v| v.id);
+	shards
+}
+
+/// Shows the activity on every shard that this process runs
+pub async fn set_activity(shard_manager: &Mutex<ShardManager>, activity: Option<Activity>) {
+	let shard_manager = shard_manager.lock().await;
+	for info in shard_manager.runners.lock().await.values() {
+		info.runner_tx.set_activity(activity.clone());
+	}
+}
@@ -0,0 +1,97 @@
+use std::future::Future;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use super::CommErr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownKind {
+	Shutdown,
+	Restart
+}
+
+/// Keeps track of whether the bot is shutting down, and of the commands that
+/// are still running
+#[derive(Default)]
+pub struct Shutdown {
+	kind: Mutex<Option<ShutdownKind>>,
+	notify: Notify,
+	in_flight: AtomicUsize
+}
+
+/// Marks a command as running until it's dropped
+pub struct InFlight<'a>(&'a Shutdown);
+
+impl Drop for InFlight<'_> {
+	fn drop(&mut self) {
+		self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+	}
+}
+
+impl Shutdown {
+	/// Starts shutting down (or restarting). Only the first request counts
+	pub fn request(&self, kind: ShutdownKind) {
+		let mut current = self.kind.lock().unwrap();
+		if current.is_none() {
+			*current = Some(kind);
+			self.notify.notify_waiters();
+		}
+	}
+	pub fn kind(&self) -> Option<ShutdownKind> {
+		*self.kind.lock().unwrap()
+	}
+	pub fn is_requested(&self) -> bool {
+		self.kind().is_some()
+	}
+	/// Waits until shutting down is requested
+	pub async fn requested(&self) -> ShutdownKind {
+		loop {
+			// Waiting starts before checking, so that a request in between isn't missed
+			let notified = self.notify.notified();
+			tokio::pin!(notified);
+			notified.as_mut().enable();
+			if let Some(kind) = self.kind() {
+				return kind;
+			}
+			notified.await;
+		}
+	}
+	/// Waits for the future, unless shutting down is requested first, in which
+	/// case it's dropped and None is returned. Commands wait for users with
+	/// this, so that shutting down doesn't wait for them
+	pub async fn unless_requested<F: Future>(&self, future: F) -> Option<F::Output> {
+		tokio::select! {
+			v = future => Some(v),
+			_ = self.requested() => None
+		}
+	}
+	/// Marks a command as running, unless the bot is shutting down
+	pub fn start_command(&self) -> Option<InFlight<'_>> {
+		self.in_flight.fetch_add(1, Ordering::SeqCst);
+		let in_flight = InFlight(self);
+		match self.is_requested() {
+			true => None,
+			false => Some(in_flight)
+		}
+	}
+	/// What users are told when they use a command while the bot is shutting down
+	pub fn error(&self) -> CommErr {
+		CommErr::Error(match self.kind() {
+			Some(ShutdownKind::Restart) => "I'm restarting, please try again in a moment",
+			_ => "I'm shutting down, so I can't run commands right now"
+		}.to_string(), String::new())
+	}
+	/// Waits for the running commands to finish. Returns how many are still
+	/// running when `timeout` runs out
+	pub async fn wait_for_commands(&self, timeout: Duration) -> usize {
+		let start = Instant::now();
+		loop {
+			let in_flight = self.in_flight.load(Ordering::SeqCst);
+			if in_flight == 0 || start.elapsed() >= timeout {
+				return in_flight;
+			}
+			tokio::time::sleep(Duration::from_millis(50)).await;
+		}
+	}
+}
@@ -0,0 +1,126 @@
+use std::pin::Pin;
+use std::future::Future;
+use std::time::Duration;
+use serde_json::Value;
+use super::db::Database;
+use super::hooks::CommandInfo;
+use super::{ChloeManager, CommRes, CommErr, time};
+
+/// Usage numbers of a command, or of every command together
+#[derive(Default, Clone)]
+pub struct Usage {
+	pub runs: u64,
+	pub errors: u64,
+	/// Sum of how long every run took, in milliseconds
+	pub total_ms: u64
+}
+
+impl Usage {
+	fn from_value(value: &Value) -> Self {
+		Self {
+			runs: value["runs"].as_u64().unwrap_or(0),
+			errors: value["errors"].as_u64().unwrap_or(0),
+			total_ms: value["total_ms"].as_u64().unwrap_or(0)
+		}
+	}
+	fn to_value(&self) -> Value {
+		serde_json::json!({
+			"runs": self.runs,
+			"errors": self.errors,
+			"total_ms": self.total_ms
+		})
+	}
+	fn add(&mut self, other: &Usage) {
+		self.runs += other.runs;
+		self.errors += other.errors;
+		self.total_ms += other.total_ms;
+	}
+	/// Percentage of runs that didn't end in an error
+	pub fn success_rate(&self) -> f64 {
+		match self.runs {
+			0 => 100.0,
+			runs => (runs - self.errors) as f64 * 100.0 / runs as f64
+		}
+	}
+	pub fn avg_ms(&self) -> u64 {
+		match self.runs {
+			0 => 0,
+			runs => self.total_ms / runs
+		}
+	}
+}
+
+fn record(db: &mut Database, path: &[&str], run: &Usage) -> Result<(), String> {
+	let mut usage = Usage::from_value(db.get(path));
+	usage.add(run);
+	db.set(path, usage.to_value())
+}
+
+/// Counts every command run, per command, per guild and per day
+pub fn record_stats<'a, 'b>(_chloe: &'b ChloeManager<'a>, info: &'b CommandInfo, result: &'b Result<CommRes<'a>, CommErr>, elapsed: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + 'b>> {
+	Box::pin(async move {
+		let run = Usage {
+			runs: 1,
+			errors: result.is_err() as u64,
+			total_ms: elapsed.as_millis() as u64
+		};
+		let day = time::date(time::now());
+		let mut db = info.db.write().unwrap();
+		let mut recorded = record(&mut db, &["stats", "commands", info.name.as_str()], &run)
+			.and_then(|_| record(&mut db, &["stats", "days", day.as_str()], &run));
+		if let Some(guild_id) = info.guild_id {
+			recorded = recorded.and_then(|_| record(&mut db, &["stats", "guilds", guild_id.to_string().as_str(), info.name.as_str()], &run));
+		}
+		if let Err(e) = recorded {
+			tracing::error!(error = %e, "Unable to record the command's stats");
+		}
+	})
+}
+
+/// Usage of every command, most used first. With a guild ID, only runs in that guild count
+pub fn command_usage(db: &Database, guild_id: Option<&str>) -> Vec<(String, Usage)> {
+	let commands = match guild_id {
+		Some(guild_id) => db.get(&["stats", "guilds", guild_id]),
+		None => db.get(&["stats", "commands"])
+	};
+	let mut usage: Vec<(String, Usage)> = match commands {
+		Value::Object(v) => v.iter().map(|(name, usage)| (name.clone(), Usage::from_value(usage))).collect(),
+		_ => Vec::new()
+	};
+	usage.sort_by(|a, b| b.1.runs.cmp(&a.1.runs).then(a.0.cmp(&b.0)));
+	usage
+}
+
+pub fn total(usage: &[(String, Usage)]) -> Usage {
+	let mut total = Usage::default();
+	for (_, v) in usage.iter() {
+		total.add(v);
+	}
+	total
+}
+
+/// Usage during the last `days` days, including today
+pub fn recent_usage(db: &Database, days: u64) -> Usage {
+	let mut total = Usage::default();
+	let now = time::now();
+	for i in 0..days {
+		let day = time::date(now - i * 86400);
+		total.add(&Usage::from_value(db.get(&["stats", "days", day.as_str()])));
+	}
+	total
+}
+
+/// Formats a size in bytes, like "12.3 KB"
+pub fn format_bytes(bytes: u64) -> String {
+	let mut size = bytes as f64;
+	for unit in ["B", "KB", "MB", "GB"] {
+		if size < 1024.0 || unit == "GB" {
+			return match unit {
+				"B" => format!("{} B", bytes),
+				_ => format!("{:.1} {}", size, unit)
+			};
+		}
+		size /= 1024.0;
+	}
+	unreachable!()
+}
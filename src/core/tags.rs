@@ -0,0 +1,129 @@
+use serde_json::Value;
+use serenity::builder::CreateMessage;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use super::db::Database;
+use super::{time, ChloeManager};
+
+/// Most tags a server can have
+pub const MAX_TAGS: usize = 200;
+/// Longest content of a tag, which leaves room for the placeholders to grow
+pub const MAX_CONTENT: usize = 1800;
+/// Longest text a message can have
+const MAX_MESSAGE: usize = 2000;
+
+/// A server's own text command
+pub struct Tag {
+	/// Name, in lowercase
+	pub name: String,
+	/// Text with placeholders
+	pub content: String,
+	/// Whether it's sent as an embed
+	pub embed: bool,
+	pub author_id: UserId,
+	/// UNIX timestamp of when it was created
+	pub created: u64,
+	/// UNIX timestamp of when it was last edited, if it was
+	pub edited: Option<u64>,
+	/// How many times it was used
+	pub uses: u64
+}
+
+impl Tag {
+	pub fn new(name: &str, content: String, author_id: UserId) -> Self {
+		Self {
+			name: name.to_lowercase(),
+			content,
+			embed: false,
+			author_id,
+			created: time::now(),
+			edited: None,
+			uses: 0
+		}
+	}
+	fn from_value(name: &str, value: &Value) -> Option<Self> {
+		Some(Self {
+			name: name.to_string(),
+			content: value["content"].as_str()?.to_string(),
+			embed: value["embed"].as_bool().unwrap_or(false),
+			author_id: UserId(value["author_id"].as_str()?.parse().ok()?),
+			created: value["created"].as_u64()?,
+			edited: value["edited"].as_u64(),
+			uses: value["uses"].as_u64().unwrap_or(0)
+		})
+	}
+	fn to_value(&self) -> Value {
+		serde_json::json!({
+			"content": self.content,
+			"embed": self.embed,
+			"author_id": self.author_id.to_string(),
+			"created": self.created,
+			"edited": self.edited,
+			"uses": self.uses
+		})
+	}
+	/// Replaces the placeholders {user}, {args} and {channel}. The args are
+	/// cut short to keep the text within a message's length
+	pub fn render(&self, user_id: UserId, channel_id: ChannelId, args: &str) -> String {
+		let text = self.content.replace("{user}", &format!("<@{}>", user_id))
+			.replace("{channel}", &format!("<#{}>", channel_id));
+		let count = text.matches("{args}").count();
+		let room = MAX_MESSAGE.saturating_sub(text.chars().count() - count * "{args}".len());
+		let args: String = args.trim().chars().take(room / count.max(1)).collect();
+		text.replace("{args}", &args).chars().take(MAX_MESSAGE).collect()
+	}
+	/// Builds the tag's message. Tags never ping anyone, since anyone can put
+	/// anything in {args}
+	pub fn message<'a>(&self, chloe: &ChloeManager, user_id: UserId, channel_id: ChannelId, args: &str) -> CreateMessage<'a> {
+		let text = self.render(user_id, channel_id, args);
+		let mut message = CreateMessage::default();
+		message.allowed_mentions(|a| a.empty_parse());
+		match self.embed {
+			true => message.add_embed(|e| {
+				e.description(text)
+					.color(chloe.config["embed_color"].as_i64().unwrap() as i32)
+			}),
+			false => message.content(text)
+		};
+		message
+	}
+}
+
+/// Whether the name can be a tag's, which is one word like a command's
+pub fn is_valid_name(name: &str) -> bool {
+	(1..=32).contains(&name.chars().count()) && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+pub fn get(db: &Database, guild_id: GuildId, name: &str) -> Option<Tag> {
+	let name = name.to_lowercase();
+	Tag::from_value(&name, db.get(&["guilds", guild_id.to_string().as_str(), "tags", name.as_str()]))
+}
+
+/// The server's tags, by name
+pub fn list(db: &Database, guild_id: GuildId) -> Vec<Tag> {
+	let mut tags: Vec<Tag> = match db.get(&["guilds", guild_id.to_string().as_str(), "tags"]) {
+		Value::Object(v) => v.iter().filter_map(|(name, v)| Tag::from_value(name, v)).collect(),
+		_ => Vec::new()
+	};
+	tags.sort_by(|a, b| a.name.cmp(&b.name));
+	tags
+}
+
+pub fn save(db: &mut Database, guild_id: GuildId, tag: &Tag) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "tags", tag.name.as_str()], tag.to_value())
+}
+
+pub fn remove(db: &mut Database, guild_id: GuildId, name: &str) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "tags", name.to_lowercase().as_str()], Value::Null)
+}
+
+/// Finds the tag and counts the use. Returns None if the server has no tag
+/// with that name
+pub fn use_tag(db: &mut Database, guild_id: GuildId, name: &str) -> Option<Tag> {
+	let mut tag = get(db, guild_id, name)?;
+	tag.uses += 1;
+	// Only the count, so nothing else the tag had is written back
+	if let Err(e) = db.set(&["guilds", guild_id.to_string().as_str(), "tags", tag.name.as_str(), "uses"], tag.uses.into()) {
+		tracing::warn!(guild = %guild_id, tag = %tag.name, error = %e, "Unable to count the use of a tag");
+	}
+	Some(tag)
+}
@@ -0,0 +1,192 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Current UNIX timestamp, in seconds
+pub fn now() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Parses durations like "30s", "2h30m" or "1w 2d"
+pub fn parse_duration(s: &str) -> Option<Duration> {
+	let mut total = 0;
+	let mut number = String::new();
+	let mut found_unit = false;
+	for c in s.chars().filter(|c| !c.is_whitespace()) {
+		if c.is_ascii_digit() {
+			number.push(c);
+			continue;
+		}
+		let unit = match c.to_ascii_lowercase() {
+			's' => 1,
+			'm' => 60,
+			'h' => 60 * 60,
+			'd' => 60 * 60 * 24,
+			'w' => 60 * 60 * 24 * 7,
+			_ => return None
+		};
+		let value: u64 = number.parse().ok()?;
+		total = value.checked_mul(unit).and_then(|v| v.checked_add(total))?;
+		number.clear();
+		found_unit = true;
+	}
+	match number.is_empty() && found_unit {
+		true => Some(Duration::from_secs(total)),
+		false => None
+	}
+}
+
+/// Formats a duration like "1d 2h 30m"
+pub fn format_duration(d: Duration) -> String {
+	let mut secs = d.as_secs();
+	let mut parts = Vec::new();
+	for (unit, name) in [(60 * 60 * 24, "d"), (60 * 60, "h"), (60, "m"), (1, "s")] {
+		if secs >= unit {
+			parts.push(format!("{}{}", secs / unit, name));
+			secs %= unit;
+		}
+	}
+	match parts.is_empty() {
+		true => "0s".to_string(),
+		false => parts.join(" ")
+	}
+}
+
+/// Discord's markdown for showing a timestamp in the user's timezone.
+/// `style` is one of Discord's timestamp styles, like "R" for relative time
+pub fn discord_timestamp(timestamp: u64, style: &str) -> String {
+	format!("<t:{}:{}>", timestamp, style)
+}
+
+/// The UTC year, month and day of a UNIX timestamp
+pub fn civil(timestamp: u64) -> (i64, u32, u32) {
+	// Howard Hinnant's days-to-civil algorithm
+	let z = (timestamp / 86400) as i64 + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+	(year, month as u32, day as u32)
+}
+
+/// The UTC day of the week of a UNIX timestamp, from 0 (Sunday) to 6 (Saturday)
+pub fn weekday(timestamp: u64) -> u32 {
+	// 1970-01-01 was a Thursday
+	((timestamp / 86400 + 4) % 7) as u32
+}
+
+/// The UTC date of a UNIX timestamp, like "2022-07-25"
+pub fn date(timestamp: u64) -> String {
+	let (year, month, day) = civil(timestamp);
+	format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Days between 1970-01-01 and a date, which is negative for earlier dates
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+	// Howard Hinnant's days-from-civil algorithm
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let yoe = year.rem_euclid(400);
+	let mp = (month as i64 + 9) % 12;
+	let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+	era * 146097 + doe - 719468
+}
+
+/// Parses UTC offsets like "UTC+2", "-03:30", "+0530" or "UTC", in seconds.
+/// Offsets are fixed, so they don't follow daylight saving time. Time zones
+/// that do are in `timezone`
+pub fn parse_utc_offset(s: &str) -> Option<i64> {
+	let s = s.trim().to_lowercase();
+	let s = s.strip_prefix("utc").or_else(|| s.strip_prefix("gmt")).unwrap_or(&s).trim();
+	if s.is_empty() || s == "z" {
+		return Some(0);
+	}
+	let (sign, s) = match (s.strip_prefix('+'), s.strip_prefix('-')) {
+		(Some(rest), _) => (1, rest),
+		(_, Some(rest)) => (-1, rest),
+		_ => (1, s)
+	};
+	// Only digits, so that the sign can't be given twice like in "+-5"
+	if !s.chars().all(|c| c.is_ascii_digit() || c == ':') {
+		return None;
+	}
+	let (hours, minutes): (i64, i64) = match s.split_once(':') {
+		Some((hours, minutes)) => (hours.parse().ok()?, minutes.parse().ok()?),
+		None if s.len() > 2 => (s[..s.len() - 2].parse().ok()?, s[s.len() - 2..].parse().ok()?),
+		None => (s.parse().ok()?, 0)
+	};
+	let offset = sign * (hours * 3600 + minutes * 60);
+	match minutes < 60 && (-12 * 3600..=14 * 3600).contains(&offset) {
+		true => Some(offset),
+		false => None
+	}
+}
+
+/// Formats a UTC offset in seconds, like "UTC+2" or "UTC-3:30"
+pub fn format_utc_offset(offset: i64) -> String {
+	let sign = if offset < 0 { "-" } else { "+" };
+	let (hours, minutes) = (offset.abs() / 3600, offset.abs() % 3600 / 60);
+	match (offset, minutes) {
+		(0, _) => "UTC".to_string(),
+		(_, 0) => format!("UTC{}{}", sign, hours),
+		_ => format!("UTC{}{}:{:02}", sign, hours, minutes)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_duration_adds_units() {
+		assert_eq!(parse_duration("30s"), Some(Duration::from_secs(30)));
+		assert_eq!(parse_duration("2h30m"), Some(Duration::from_secs(2 * 3600 + 30 * 60)));
+		assert_eq!(parse_duration("1w 2d"), Some(Duration::from_secs(9 * 86400)));
+		assert_eq!(parse_duration("10M"), Some(Duration::from_secs(600)));
+	}
+
+	#[test]
+	fn parse_duration_rejects_invalid_text() {
+		assert_eq!(parse_duration(""), None);
+		assert_eq!(parse_duration("30"), None);
+		assert_eq!(parse_duration("1h30"), None);
+		assert_eq!(parse_duration("h"), None);
+		assert_eq!(parse_duration("5y"), None);
+	}
+
+	#[test]
+	fn parse_duration_rejects_overflows() {
+		assert_eq!(parse_duration("18446744073709551615s"), Some(Duration::from_secs(u64::MAX)));
+		assert_eq!(parse_duration("18446744073709551615s 1s"), None);
+		assert_eq!(parse_duration("18446744073709551615m"), None);
+		assert_eq!(parse_duration("99999999999999999999s"), None);
+	}
+
+	#[test]
+	fn parse_utc_offset_reads_offsets() {
+		assert_eq!(parse_utc_offset("UTC"), Some(0));
+		assert_eq!(parse_utc_offset("z"), Some(0));
+		assert_eq!(parse_utc_offset("UTC+2"), Some(2 * 3600));
+		assert_eq!(parse_utc_offset("gmt-5"), Some(-5 * 3600));
+		assert_eq!(parse_utc_offset("-03:30"), Some(-(3 * 3600 + 30 * 60)));
+		assert_eq!(parse_utc_offset("+0530"), Some(5 * 3600 + 30 * 60));
+		assert_eq!(parse_utc_offset("14"), Some(14 * 3600));
+	}
+
+	#[test]
+	fn parse_utc_offset_rejects_invalid_offsets() {
+		for offset in ["+-5", "-+5", "--5", "+5:-30", "UTC+15", "-13", "+2:60", "+5:", "Europe/Berlin", "5h"] {
+			assert_eq!(parse_utc_offset(offset), None, "{offset}");
+		}
+	}
+
+	#[test]
+	fn format_utc_offset_reads_back() {
+		for offset in [0, 2 * 3600, -5 * 3600, 5 * 3600 + 30 * 60, -(3 * 3600 + 30 * 60)] {
+			assert_eq!(parse_utc_offset(&format_utc_offset(offset)), Some(offset));
+		}
+	}
+}
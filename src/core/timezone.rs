@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use lazy_static::lazy_static;
+use super::time;
+
+lazy_static! {
+	/// Zones that were already read, by the name they were asked for in lowercase
+	static ref ZONES: Mutex<HashMap<String, Arc<Zone>>> = Mutex::default();
+}
+
+/// A time zone, which is either a fixed UTC offset or a zone of the IANA time
+/// zone database, like "Europe/Berlin", which follows daylight saving time
+#[derive(Debug, Clone)]
+pub enum TimeZone {
+	/// UTC offset, in seconds
+	Fixed(i64),
+	Zone(Arc<Zone>)
+}
+
+impl TimeZone {
+	pub const UTC: TimeZone = TimeZone::Fixed(0);
+
+	/// Parses UTC offsets like "UTC+2" or "-5:30", and zone names like
+	/// "Europe/Berlin", which are read from the system's time zone database
+	pub fn parse(s: &str) -> Option<Self> {
+		match time::parse_utc_offset(s) {
+			Some(offset) => Some(TimeZone::Fixed(offset)),
+			None => Zone::load(s.trim()).map(TimeZone::Zone)
+		}
+	}
+	/// What `parse` reads back, like "UTC+2" or "Europe/Berlin"
+	pub fn name(&self) -> String {
+		match self {
+			TimeZone::Fixed(offset) => time::format_utc_offset(*offset),
+			TimeZone::Zone(zone) => zone.name.clone()
+		}
+	}
+	/// The UTC offset at a UNIX timestamp, in seconds
+	pub fn offset_at(&self, timestamp: u64) -> i64 {
+		match self {
+			TimeZone::Fixed(offset) => *offset,
+			TimeZone::Zone(zone) => zone.offset_at(timestamp as i64)
+		}
+	}
+	/// The local time at a UNIX timestamp, in seconds since 1970-01-01 00:00
+	/// in the zone
+	pub fn to_local(&self, timestamp: u64) -> i64 {
+		timestamp as i64 + self.offset_at(timestamp)
+	}
+	/// The UNIX timestamp of a local time. Times that happen twice when the
+	/// clocks go back are the first one, and times that are skipped when they
+	/// go forward are moved forward by as much
+	pub fn to_utc(&self, local: i64) -> Option<u64> {
+		let zone = match self {
+			TimeZone::Fixed(offset) => return u64::try_from(local - offset).ok(),
+			TimeZone::Zone(zone) => zone
+		};
+		// Offsets don't change more than once a day
+		let before = zone.offset_at(local - 86400);
+		let after = zone.offset_at(local + 86400);
+		let utc = [local - before, local - after].into_iter()
+			.filter(|utc| utc + zone.offset_at(*utc) == local)
+			.min()
+			.unwrap_or(local - before);
+		u64::try_from(utc).ok()
+	}
+}
+
+/// A zone of the IANA time zone database
+#[derive(Debug)]
+pub struct Zone {
+	name: String,
+	/// UNIX timestamps at which the offset changed, and the offset from then
+	/// on, in order
+	transitions: Vec<(i64, i64)>,
+	/// Offset before the first transition
+	initial: i64,
+	/// Offsets after the last transition
+	rule: Option<Rule>
+}
+
+impl Zone {
+	/// Reads a zone from the time zone database in TZDIR or /usr/share/zoneinfo.
+	/// The name doesn't have to be in the right case
+	pub fn load(name: &str) -> Option<Arc<Zone>> {
+		let key = name.to_lowercase();
+		if let Some(zone) = ZONES.lock().unwrap().get(&key) {
+			return Some(zone.clone());
+		}
+		if name.is_empty() || name.split('/').any(|v| v.is_empty() || v.starts_with('.')) {
+			return None;
+		}
+		let mut path = PathBuf::from(std::env::var("TZDIR").unwrap_or_else(|_| "/usr/share/zoneinfo".to_string()));
+		let mut parts = Vec::new();
+		for part in name.split('/') {
+			let entry = fs::read_dir(&path).ok()?
+				.filter_map(|entry| entry.ok())
+				.find(|entry| entry.file_name().to_str().is_some_and(|v| v.eq_ignore_ascii_case(part)))?;
+			parts.push(entry.file_name().to_str()?.to_string());
+			path.push(entry.file_name());
+		}
+		let zone = Arc::new(Zone::from_tzif(&parts.join("/"), &fs::read(&path).ok()?)?);
+		ZONES.lock().unwrap().insert(key, zone.clone());
+		Some(zone)
+	}
+	/// Reads a zone from a TZif file, as described in RFC 8536
+	pub fn from_tzif(name: &str, data: &[u8]) -> Option<Zone> {
+		let (version, counts) = tzif_header(data)?;
+		let (data, counts, time_size) = match version {
+			0 => (data, counts, 4),
+			// The 32-bit data is followed by a second header and the 64-bit data
+			_ => {
+				let data = data.get(tzif_len(counts, 4)..)?;
+				(data, tzif_header(data)?.1, 8)
+			}
+		};
+		let [_, _, _, timecnt, typecnt, _] = counts;
+		let body = data.get(44..tzif_len(counts, time_size))?;
+		let (times, body) = body.split_at(timecnt * time_size);
+		let (indices, body) = body.split_at(timecnt);
+		let types = &body[..typecnt * 6];
+		let offsets: Vec<i64> = types.chunks(6)
+			.map(|v| i32::from_be_bytes([v[0], v[1], v[2], v[3]]) as i64)
+			.collect();
+		let transitions = times.chunks(time_size).zip(indices)
+			.map(|(time, index)| {
+				let time = match time_size {
+					4 => i32::from_be_bytes(time.try_into().unwrap()) as i64,
+					_ => i64::from_be_bytes(time.try_into().unwrap())
+				};
+				Some((time, *offsets.get(*index as usize)?))
+			})
+			.collect::<Option<Vec<(i64, i64)>>>()?;
+		let footer = match version {
+			0 => "",
+			_ => {
+				let footer = &data[tzif_len(counts, time_size)..];
+				std::str::from_utf8(footer).ok()?.trim_matches('\n')
+			}
+		};
+		Some(Zone {
+			name: name.to_string(),
+			transitions,
+			initial: *offsets.first()?,
+			rule: match footer {
+				"" => None,
+				footer => Some(Rule::parse(footer)?)
+			}
+		})
+	}
+	/// A zone that only follows a POSIX TZ rule, like "CET-1CEST,M3.5.0,M10.5.0/3"
+	pub fn from_rule(name: &str, rule: &str) -> Option<Zone> {
+		let rule = Rule::parse(rule)?;
+		Some(Zone {
+			name: name.to_string(),
+			transitions: Vec::new(),
+			initial: rule.std,
+			rule: Some(rule)
+		})
+	}
+	fn offset_at(&self, timestamp: i64) -> i64 {
+		let i = self.transitions.partition_point(|(t, _)| *t <= timestamp);
+		match (i, &self.rule) {
+			(i, Some(rule)) if i == self.transitions.len() => rule.offset_at(timestamp),
+			(0, _) => self.initial,
+			(i, _) => self.transitions[i - 1].1
+		}
+	}
+}
+
+/// The version and the counts of a TZif header, which is 44 bytes long
+fn tzif_header(data: &[u8]) -> Option<(u8, [usize; 6])> {
+	if data.len() < 44 || &data[..4] != b"TZif" {
+		return None;
+	}
+	let mut counts = [0; 6];
+	for (i, count) in counts.iter_mut().enumerate() {
+		let at = 20 + i * 4;
+		*count = u32::from_be_bytes(data[at..at + 4].try_into().unwrap()) as usize;
+	}
+	Some((data[4].saturating_sub(b'0'), counts))
+}
+
+/// Length of a TZif header and the data after it, with times of `time_size` bytes
+fn tzif_len([isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt]: [usize; 6], time_size: usize) -> usize {
+	44 + timecnt * (time_size + 1) + typecnt * 6 + charcnt + leapcnt * (time_size + 4) + isstdcnt + isutcnt
+}
+
+/// A POSIX TZ rule, like "CET-1CEST,M3.5.0,M10.5.0/3"
+#[derive(Debug)]
+struct Rule {
+	/// Standard time's UTC offset, in seconds
+	std: i64,
+	dst: Option<Dst>
+}
+
+#[derive(Debug)]
+struct Dst {
+	/// Daylight saving time's UTC offset, in seconds
+	offset: i64,
+	/// When it starts, in standard time
+	start: (RuleDate, i64),
+	/// When it ends, in daylight saving time
+	end: (RuleDate, i64)
+}
+
+#[derive(Debug)]
+enum RuleDate {
+	/// "Jn", a day of the year from 1 to 365 that skips February 29th
+	Julian(i64),
+	/// "n", a day of the year from 0 to 365
+	Day(i64),
+	/// "Mm.w.d", day d (0 is Sunday) of week w (5 is the last one) of month m
+	Month(u32, i64, i64)
+}
+
+impl Rule {
+	fn parse(s: &str) -> Option<Rule> {
+		let s = skip_name(s)?;
+		let (std, s) = split_hms(s);
+		// POSIX offsets are west of UTC
+		let std = -parse_hms(std)?;
+		if s.is_empty() {
+			return Some(Rule { std, dst: None });
+		}
+		let s = skip_name(s)?;
+		let (offset, s) = split_hms(s);
+		let offset = match offset {
+			"" => std + 3600,
+			offset => -parse_hms(offset)?
+		};
+		let (start, end) = s.strip_prefix(',')?.split_once(',')?;
+		Some(Rule {
+			std,
+			dst: Some(Dst {
+				offset,
+				start: RuleDate::parse(start)?,
+				end: RuleDate::parse(end)?
+			})
+		})
+	}
+	fn offset_at(&self, timestamp: i64) -> i64 {
+		let dst = match &self.dst {
+			Some(v) => v,
+			None => return self.std
+		};
+		let (year, _, _) = time::civil((timestamp + self.std).max(0) as u64);
+		let start = dst.start.0.day(year) * 86400 + dst.start.1 - self.std;
+		let end = dst.end.0.day(year) * 86400 + dst.end.1 - dst.offset;
+		// Daylight saving time goes over the new year in the southern hemisphere
+		let in_dst = match start < end {
+			true => start <= timestamp && timestamp < end,
+			false => timestamp < end || start <= timestamp
+		};
+		if in_dst { dst.offset } else { self.std }
+	}
+}
+
+impl RuleDate {
+	/// Parses dates like "M3.5.0/3", with the time they change at, which is
+	/// 2:00 by default
+	fn parse(s: &str) -> Option<(RuleDate, i64)> {
+		let (date, time) = match s.split_once('/') {
+			Some((date, time)) => (date, parse_hms(time)?),
+			None => (s, 2 * 3600)
+		};
+		let date = match (date.strip_prefix('J'), date.strip_prefix('M')) {
+			(Some(day), _) => RuleDate::Julian(day.parse().ok().filter(|v| (1..=365).contains(v))?),
+			(_, Some(date)) => {
+				let parts: Vec<&str> = date.split('.').collect();
+				let (month, week, weekday) = match parts.as_slice() {
+					[month, week, weekday] => (month.parse().ok()?, week.parse().ok()?, weekday.parse().ok()?),
+					_ => return None
+				};
+				match (1..=12).contains(&month) && (1..=5).contains(&week) && (0..=6).contains(&weekday) {
+					true => RuleDate::Month(month, week, weekday),
+					false => return None
+				}
+			},
+			_ => RuleDate::Day(date.parse().ok().filter(|v| (0..=365).contains(v))?)
+		};
+		Some((date, time))
+	}
+	/// The day of the year it's on, in days since 1970-01-01
+	fn day(&self, year: i64) -> i64 {
+		let first = time::days_from_civil(year, 1, 1);
+		let leap = time::days_from_civil(year + 1, 1, 1) - first == 366;
+		match *self {
+			RuleDate::Julian(day) => first + day - 1 + if leap && day >= 60 { 1 } else { 0 },
+			RuleDate::Day(day) => first + day,
+			RuleDate::Month(month, week, weekday) => {
+				let first = time::days_from_civil(year, month, 1);
+				let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+				let len = time::days_from_civil(next_year, next_month, 1) - first;
+				// 1970-01-01 was a Thursday
+				let day = (weekday - (first + 4)).rem_euclid(7) + (week - 1) * 7;
+				first + if day >= len { day - 7 } else { day }
+			}
+		}
+	}
+}
+
+/// Skips a zone abbreviation like "CET" or "<+0530>"
+fn skip_name(s: &str) -> Option<&str> {
+	match s.strip_prefix('<') {
+		Some(s) => s.split_once('>').map(|(_, s)| s),
+		None => {
+			let len = s.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(s.len());
+			if len < 3 { None } else { Some(&s[len..]) }
+		}
+	}
+}
+
+/// Splits a time like "-5:30" off the start
+fn split_hms(s: &str) -> (&str, &str) {
+	s.split_at(s.find(|c: char| !matches!(c, '0'..='9' | ':' | '+' | '-')).unwrap_or(s.len()))
+}
+
+/// Parses times like "2", "-1" or "+5:30:15", in seconds
+fn parse_hms(s: &str) -> Option<i64> {
+	let (sign, s) = match (s.strip_prefix('+'), s.strip_prefix('-')) {
+		(Some(rest), _) => (1, rest),
+		(_, Some(rest)) => (-1, rest),
+		_ => (1, s)
+	};
+	let mut secs = 0;
+	for (i, part) in s.split(':').enumerate() {
+		if i > 2 || part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+			return None;
+		}
+		secs += part.parse::<i64>().ok()? * [3600, 60, 1][i];
+	}
+	Some(sign * secs)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// UNIX timestamp of a UTC date and time
+	fn at(year: i64, month: u32, day: u32, hour: i64, minute: i64) -> i64 {
+		time::days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60
+	}
+
+	fn zone(rule: &str) -> TimeZone {
+		TimeZone::Zone(Arc::new(Zone::from_rule("Test", rule).unwrap()))
+	}
+
+	#[test]
+	fn rules_follow_daylight_saving_time() {
+		let berlin = zone("CET-1CEST,M3.5.0,M10.5.0/3");
+		// It started on 2024-03-31 at 2:00 and ended on 2024-10-27 at 3:00, local time
+		assert_eq!(berlin.offset_at(at(2024, 3, 31, 0, 59) as u64), 3600);
+		assert_eq!(berlin.offset_at(at(2024, 3, 31, 1, 0) as u64), 7200);
+		assert_eq!(berlin.offset_at(at(2024, 10, 27, 0, 59) as u64), 7200);
+		assert_eq!(berlin.offset_at(at(2024, 10, 27, 1, 0) as u64), 3600);
+		// Daylight saving time goes over the new year in the southern hemisphere
+		let sydney = zone("AEST-10AEDT,M10.1.0,M4.1.0/3");
+		assert_eq!(sydney.offset_at(at(2024, 1, 1, 0, 0) as u64), 11 * 3600);
+		assert_eq!(sydney.offset_at(at(2024, 7, 1, 0, 0) as u64), 10 * 3600);
+		// Names in angle brackets, and zones without daylight saving time
+		assert_eq!(zone("<+0530>-5:30").offset_at(0), 5 * 3600 + 30 * 60);
+		assert_eq!(zone("EST5").offset_at(0), -5 * 3600);
+		// Day of the year, without and with February 29th
+		let julian = zone("AAA0BBB,J60/0,300/0");
+		assert_eq!(julian.offset_at(at(2024, 2, 29, 12, 0) as u64), 0);
+		assert_eq!(julian.offset_at(at(2024, 3, 1, 12, 0) as u64), 3600);
+		assert_eq!(julian.offset_at(at(2024, 10, 26, 12, 0) as u64), 3600);
+		assert_eq!(julian.offset_at(at(2024, 10, 27, 12, 0) as u64), 0);
+	}
+
+	#[test]
+	fn rules_reject_invalid_text() {
+		for rule in ["", "C1", "CET", "CET-1CEST", "CET-1CEST,M3.5.0", "CET-1CEST,M13.5.0,M10.5.0", "CET-1CEST,M3.6.0,M10.5.0", "CET-1CEST,J0,J365", "CET-1:-30"] {
+			assert!(Zone::from_rule("Test", rule).is_none(), "{rule}");
+		}
+	}
+
+	#[test]
+	fn local_times_convert_to_utc() {
+		let berlin = zone("CET-1CEST,M3.5.0,M10.5.0/3");
+		assert_eq!(berlin.to_utc(at(2024, 7, 1, 12, 0)), Some(at(2024, 7, 1, 10, 0) as u64));
+		assert_eq!(berlin.to_local(at(2024, 7, 1, 10, 0) as u64), at(2024, 7, 1, 12, 0));
+		// 2:30 was skipped when the clocks went forward, so it's 3:30
+		assert_eq!(berlin.to_utc(at(2024, 3, 31, 2, 30)), Some(at(2024, 3, 31, 1, 30) as u64));
+		// 2:30 happened twice when they went back, and it's the first one
+		assert_eq!(berlin.to_utc(at(2024, 10, 27, 2, 30)), Some(at(2024, 10, 27, 0, 30) as u64));
+		assert_eq!(TimeZone::Fixed(-5 * 3600).to_utc(at(2024, 1, 1, 0, 0)), Some(at(2024, 1, 1, 5, 0) as u64));
+	}
+
+	#[test]
+	fn tzif_files_are_read() {
+		let header = |counts: [u32; 6]| {
+			let mut data = b"TZif2".to_vec();
+			data.extend([0; 15]);
+			data.extend(counts.iter().flat_map(|v| v.to_be_bytes()));
+			data
+		};
+		let mut data = header([0, 0, 0, 0, 1, 4]);
+		data.extend([0, 0, 0, 0, 0, 0]);
+		data.extend(b"UTC\0");
+		data.extend(header([0, 0, 0, 2, 2, 10]));
+		data.extend(at(2024, 3, 31, 1, 0).to_be_bytes());
+		data.extend(at(2024, 10, 27, 1, 0).to_be_bytes());
+		data.extend([1, 0]);
+		data.extend([0, 0, 14, 16, 0, 0]);
+		data.extend([0, 0, 28, 32, 1, 4]);
+		data.extend(b"CET\0CEST\0\0");
+		data.extend(b"\nCET-1CEST,M3.5.0,M10.5.0/3\n");
+		let zone = TimeZone::Zone(Arc::new(Zone::from_tzif("Europe/Berlin", &data).unwrap()));
+		assert_eq!(zone.name(), "Europe/Berlin");
+		// Before, between and after the transitions
+		assert_eq!(zone.offset_at(at(2024, 1, 1, 0, 0) as u64), 3600);
+		assert_eq!(zone.offset_at(at(2024, 7, 1, 0, 0) as u64), 7200);
+		assert_eq!(zone.offset_at(at(2025, 1, 1, 0, 0) as u64), 3600);
+		assert_eq!(zone.offset_at(at(2025, 7, 1, 0, 0) as u64), 7200);
+		assert!(Zone::from_tzif("Europe/Berlin", &data[..data.len() / 2]).is_none());
+		assert!(Zone::from_tzif("Europe/Berlin", b"not a TZif file").is_none());
+	}
+
+	#[test]
+	fn parse_reads_offsets_before_names() {
+		assert!(matches!(TimeZone::parse("UTC+2"), Some(TimeZone::Fixed(7200))));
+		assert!(matches!(TimeZone::parse("utc"), Some(TimeZone::Fixed(0))));
+		assert_eq!(TimeZone::parse("-5:30").unwrap().name(), "UTC-5:30");
+		for name in ["+-5", "../etc/passwd", "Europe/../../etc", "/etc/passwd"] {
+			assert!(TimeZone::parse(name).is_none(), "{name}");
+		}
+	}
+}
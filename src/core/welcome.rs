@@ -0,0 +1,169 @@
+use std::sync::{RwLock, Arc};
+use serde_json::Value;
+use serenity::builder::CreateMessage;
+use serenity::client::Context;
+use serenity::model::guild::{Member, PartialGuild};
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+use serenity::model::user::User;
+use super::db::Database;
+use super::{antiraid, ChloeManager};
+
+/// Most roles that members can get when they join
+pub const MAX_JOIN_ROLES: usize = 10;
+
+/// A message that's sent when a member joins or leaves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Greeting {
+	Welcome,
+	Goodbye
+}
+
+impl Greeting {
+	/// Key of the greeting in the database, which is also the command's name
+	pub fn key(&self) -> &'static str {
+		match self {
+			Greeting::Welcome => "welcome",
+			Greeting::Goodbye => "goodbye"
+		}
+	}
+	fn default_template(&self) -> &'static str {
+		match self {
+			Greeting::Welcome => "Welcome to **{server}**, {mention}!",
+			Greeting::Goodbye => "**{user}** left the server"
+		}
+	}
+}
+
+/// How a server sends a greeting
+pub struct Settings {
+	/// Channel it's sent in
+	pub channel: Option<ChannelId>,
+	/// Whether it's sent to the member by DM
+	pub dm: bool,
+	/// Text with placeholders, or None for the default one
+	pub template: Option<String>,
+	/// Whether it's sent as an embed
+	pub embed: bool,
+	/// Color of the embed, or None for the default one
+	pub color: Option<u32>
+}
+
+impl Settings {
+	pub fn is_enabled(&self) -> bool {
+		self.channel.is_some() || self.dm
+	}
+	pub fn template(&self, greeting: Greeting) -> &str {
+		self.template.as_deref().unwrap_or(greeting.default_template())
+	}
+}
+
+pub fn settings(db: &Database, guild_id: GuildId, greeting: Greeting) -> Settings {
+	let value = db.get(&["guilds", guild_id.to_string().as_str(), "settings", greeting.key()]);
+	Settings {
+		channel: value["channel"].as_str().and_then(|v| v.parse().ok()).map(ChannelId),
+		dm: value["dm"].as_bool().unwrap_or(false),
+		template: value["template"].as_str().map(|v| v.to_string()),
+		embed: value["embed"].as_bool().unwrap_or(false),
+		color: value["color"].as_u64().map(|v| v as u32)
+	}
+}
+
+pub fn set_settings(db: &mut Database, guild_id: GuildId, greeting: Greeting, settings: &Settings) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "settings", greeting.key()], serde_json::json!({
+		"channel": settings.channel.map(|v| v.to_string()),
+		"dm": settings.dm,
+		"template": settings.template,
+		"embed": settings.embed,
+		"color": settings.color
+	}))
+}
+
+/// Roles that members get when they join
+pub fn join_roles(db: &Database, guild_id: GuildId) -> Vec<RoleId> {
+	db.get(&["guilds", guild_id.to_string().as_str(), "settings", "join_roles"])
+		.as_array()
+		.map(|v| v.iter().filter_map(|v| v.as_str()?.parse().ok()).map(RoleId).collect())
+		.unwrap_or_default()
+}
+
+pub fn set_join_roles(db: &mut Database, guild_id: GuildId, roles: &[RoleId]) -> Result<(), String> {
+	db.set(&["guilds", guild_id.to_string().as_str(), "settings", "join_roles"], Value::Array(
+		roles.iter().map(|v| Value::String(v.to_string())).collect()
+	))
+}
+
+/// Replaces the placeholders {user}, {mention}, {server} and {member_count}
+pub fn render(template: &str, user: &User, guild: &PartialGuild) -> String {
+	template.replace("{user}", &user.tag())
+		.replace("{mention}", &format!("<@{}>", user.id))
+		.replace("{server}", &guild.name)
+		.replace("{member_count}", &guild.approximate_member_count.map(|v| v.to_string()).unwrap_or("?".to_string()))
+}
+
+/// Builds the greeting for a user, only letting it ping them
+pub fn message<'a>(chloe: &ChloeManager, settings: &Settings, greeting: Greeting, user: &User, guild: &PartialGuild) -> CreateMessage<'a> {
+	let text = render(settings.template(greeting), user, guild);
+	let mut message = CreateMessage::default();
+	message.allowed_mentions(|a| a.users(vec![user.id]));
+	match settings.embed {
+		true => message.add_embed(|e| {
+			e.description(text)
+				.thumbnail(user.face())
+				.color(settings.color.map(|v| v as i32).unwrap_or(chloe.config["embed_color"].as_i64().unwrap() as i32))
+		}),
+		false => message.content(text)
+	};
+	message
+}
+
+/// Sends the server's greeting for a user wherever it goes
+async fn send(chloe: &ChloeManager<'_>, ctx: &Context, settings: &Settings, greeting: Greeting, guild_id: GuildId, user: &User) {
+	let guild = match ctx.http.get_guild_with_counts(guild_id.0).await {
+		Ok(v) => v,
+		Err(e) => {
+			tracing::warn!(guild = %guild_id, error = %e, "Unable to get a server for its greeting");
+			return;
+		}
+	};
+	if let Some(channel_id) = settings.channel {
+		let message = message(chloe, settings, greeting, user, &guild);
+		if let Err(e) = channel_id.send_message(&ctx.http, |m| { *m = message; m }).await {
+			tracing::warn!(guild = %guild_id, channel = %channel_id, error = %e, "Unable to send a {} message", greeting.key());
+		}
+	}
+	if settings.dm {
+		let message = message(chloe, settings, greeting, user, &guild);
+		// Members can have their DMs closed, which is nothing to worry about
+		if let Err(e) = user.direct_message(&ctx.http, |m| { *m = message; m }).await {
+			tracing::debug!(guild = %guild_id, user = %user.id, error = %e, "Unable to send a {} DM", greeting.key());
+		}
+	}
+}
+
+/// Gives the member their join roles and welcomes them. Nobody is welcomed
+/// during a raid
+pub async fn member_joined(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, member: &Member) {
+	let guild_id = member.guild_id;
+	let (roles, settings) = {
+		let db = db.read().unwrap();
+		if member.user.bot || antiraid::raid(&db, guild_id).is_some() {
+			return;
+		}
+		(join_roles(&db, guild_id), settings(&db, guild_id, Greeting::Welcome))
+	};
+	for role_id in roles {
+		if let Err(e) = ctx.http.add_member_role(guild_id.0, member.user.id.0, role_id.0, Some("Join role")).await {
+			tracing::warn!(guild = %guild_id, role = %role_id, error = %e, "Unable to give a join role");
+		}
+	}
+	if settings.is_enabled() {
+		send(chloe, ctx, &settings, Greeting::Welcome, guild_id, &member.user).await;
+	}
+}
+
+pub async fn member_left(chloe: &ChloeManager<'_>, ctx: &Context, db: Arc<RwLock<Database>>, guild_id: GuildId, user: &User) {
+	let settings = settings(&db.read().unwrap(), guild_id, Greeting::Goodbye);
+	if !user.bot && settings.is_enabled() {
+		send(chloe, ctx, &settings, Greeting::Goodbye, guild_id, user).await;
+	}
+}
@@ -1,54 +1,312 @@
 use db::Database;
 use std::env;
 use std::sync::{RwLock, Arc};
+use std::time::Duration;
 use serenity::async_trait;
 use serenity::prelude::*;
-use serenity::model::channel::Message;
+use serenity::model::channel::{Message, Reaction};
 use serenity::model::gateway::Ready;
-use serenity::model::application::interaction::Interaction;
+use serenity::model::user::OnlineStatus;
+use serenity::model::guild::{Guild, Member, UnavailableGuild};
+use serenity::model::user::User;
+use serenity::model::event::{GuildMemberUpdateEvent, MessageUpdateEvent};
+use serenity::client::bridge::gateway::event::ShardStageUpdateEvent;
+use serenity::client::bridge::gateway::ShardManager;
+use serenity::gateway::ConnectionStage;
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+use serenity::model::application::interaction::{Interaction, InteractionResponseType};
 use lazy_static::lazy_static;
 use serde_json::Value;
 mod commands;
 pub mod core;
 use crate::core::*;
+use crate::core::blocklist::{Block, BlockKind};
+use crate::core::shutdown::ShutdownKind;
 
 lazy_static! {
-	pub static ref CHLOE: ChloeManager<'static> = ChloeManager::new(
-		Some("config.json5"),
-		commands::commands()
-	).unwrap();
+	pub static ref CHLOE: ChloeManager<'static> = {
+		let mut chloe = ChloeManager::new(
+			Some("config.json5"),
+			commands::commands()
+		).unwrap()
+			.before(hooks::check_owner)
+			.before(hooks::check_permissions)
+			.before(hooks::check_cooldown)
+			.after(hooks::record_cooldown)
+			.after(hooks::log_errors)
+			.after(hooks::report_errors)
+			.after(stats::record_stats)
+			.after(metrics::record_metrics);
+		for (kind, handler) in commands::jobs() {
+			chloe = chloe.job(kind, handler);
+		}
+		chloe
+	};
 	static ref DB: Arc<RwLock<Database>> = Arc::new(RwLock::new(Database::open("db").unwrap()));
 }
 
+fn prefix() -> &'static str {
+	match &CHLOE.config["prefix"] {
+		Value::String(v) => v.as_str(),
+		Value::Null => panic!("The entry \"prefix\" doesn't exist in the config"),
+		_ => panic!("The entry \"prefix\" in the config must be a string")
+	}
+}
+
+fn is_blocked(user_id: UserId, guild_id: Option<GuildId>, channel_id: ChannelId) -> Option<Block> {
+	if CHLOE.is_owner(user_id) {
+		return None;
+	}
+	blocklist::check(&DB.read().unwrap(), user_id, guild_id, channel_id)
+}
+
+/// Starts shutting down on SIGINT or SIGTERM. Another signal while shutting
+/// down exits right away
+async fn handle_signals() {
+	#[cfg(unix)]
+	let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).unwrap();
+	loop {
+		#[cfg(unix)]
+		tokio::select! {
+			_ = tokio::signal::ctrl_c() => (),
+			_ = sigterm.recv() => ()
+		}
+		#[cfg(not(unix))]
+		tokio::signal::ctrl_c().await.ok();
+		if CHLOE.shutdown.is_requested() {
+			tracing::warn!("Exiting without waiting for the shutdown to finish");
+			std::process::exit(1);
+		}
+		tracing::info!("Received a signal to shut down");
+		CHLOE.shutdown.request(ShutdownKind::Shutdown);
+	}
+}
+
+/// Waits until shutting down is requested, then lets the running commands
+/// finish, saves the database and disconnects
+async fn shut_down(shard_manager: Arc<Mutex<ShardManager>>) {
+	let kind = CHLOE.shutdown.requested().await;
+	tracing::info!(?kind, "Shutting down");
+	let timeout = Duration::from_secs(CHLOE.config["shutdown_timeout"].as_u64().unwrap_or(30));
+	let in_flight = CHLOE.shutdown.wait_for_commands(timeout).await;
+	if in_flight > 0 {
+		tracing::warn!(in_flight, "Gave up waiting for the running commands to finish");
+	}
+	if let Err(e) = DB.write().unwrap().compact() {
+		tracing::error!(error = %e, "Unable to save the database");
+	}
+	shard_manager.lock().await.shutdown_all().await;
+}
+
+/// Replaces the process with a new one, started the same way
+fn restart() {
+	let exe = match env::current_exe() {
+		Ok(v) => v,
+		Err(e) => {
+			tracing::error!(error = %e, "Unable to find my executable to restart");
+			return;
+		}
+	};
+	let mut command = std::process::Command::new(exe);
+	command.args(env::args_os().skip(1));
+	#[cfg(unix)]
+	{
+		use std::os::unix::process::CommandExt;
+		let e = command.exec();
+		tracing::error!(error = %e, "Unable to restart");
+	}
+	#[cfg(not(unix))]
+	if let Err(e) = command.spawn() {
+		tracing::error!(error = %e, "Unable to restart");
+	}
+}
+
 struct Handler;
 
+impl Handler {
+	/// Whether the event came through shard 0. Things that must only happen
+	/// once for the whole bot, like registering commands and running scheduled
+	/// jobs, happen on shard 0, even when the shards are split between several processes
+	fn is_main_shard(ctx: &Context) -> bool {
+		ctx.shard_id == 0
+	}
+}
+
+/// Starts the shards set in the "shards" entry of the config
+async fn start(client: &mut Client) -> serenity::Result<()> {
+	let config = &CHLOE.config["shards"];
+	let count = match &config["count"] {
+		Value::Null => None,
+		Value::String(v) if v == "auto" => None,
+		Value::Number(v) => match v.as_u64() {
+			Some(v) if v > 0 => Some(v),
+			_ => panic!("The entry \"shards.count\" in the config must be a positive number or \"auto\"")
+		},
+		_ => panic!("The entry \"shards.count\" in the config must be a positive number or \"auto\"")
+	};
+	let range = match &config["range"] {
+		Value::Null => None,
+		Value::Array(v) if v.len() == 2 && v.iter().all(|v| v.is_u64()) => Some([v[0].as_u64().unwrap(), v[1].as_u64().unwrap()]),
+		_ => panic!("The entry \"shards.range\" in the config must be null or an array with the first and the last shard, like [0, 3]")
+	};
+	match (count, range) {
+		(None, None) => client.start_autosharded().await,
+		(Some(count), None) => client.start_shards(count).await,
+		(count, Some(range)) => {
+			let count = match count {
+				Some(v) => v,
+				None => client.cache_and_http.http.get_bot_gateway().await?.shards
+			};
+			if range[0] > range[1] || range[1] >= count {
+				panic!("The shard range {:?} in the config doesn't fit in {} shards", range, count);
+			}
+			tracing::info!(first = range[0], last = range[1], shards = count, "Starting a range of shards");
+			client.start_shard_range(range, count).await
+		}
+	}
+}
+
 #[async_trait]
 impl EventHandler for Handler {
 	async fn message(&self, ctx: Context, msg: Message) {
-		if msg.author.bot {
+		CHLOE.modlog.cache_message(&CHLOE, &DB.read().unwrap(), &msg);
+		if automod::check(&CHLOE, &ctx, DB.clone(), &msg).await {
+			return;
+		}
+		if msg.author.bot || is_blocked(msg.author.id, msg.guild_id, msg.channel_id).is_some() {
 			return;
 		}
+		// Level-ups make HTTP requests, which commands shouldn't wait on
+		let (levels_ctx, levels_msg) = (ctx.clone(), msg.clone());
+		tokio::spawn(async move {
+			levels::message(&CHLOE, &levels_ctx, DB.clone(), &levels_msg).await;
+		});
 		let db = DB.clone();
-		let prefix = match &CHLOE.config["prefix"] {
-			Value::String(v) => v.as_str(),
-			Value::Null => panic!("The entry \"prefix\" doesn't exist in the config"),
-			_ => panic!("The entry \"prefix\" in the config must be a string")
-		};
-		CHLOE.process_msg(msg.clone(), ctx.clone(), db, prefix).await;
+		CHLOE.process_msg(msg.clone(), ctx.clone(), db, prefix()).await;
+	}
+
+	async fn message_update(&self, ctx: Context, event: MessageUpdateEvent) {
+		modlog::message_edited(&CHLOE, &ctx, DB.clone(), &event).await;
+		if let Some(author) = &event.author {
+			if author.bot || is_blocked(author.id, event.guild_id, event.channel_id).is_some() {
+				return;
+			}
+		}
+		CHLOE.process_msg_edit(event, ctx, DB.clone(), prefix()).await;
+	}
+
+	async fn message_delete(&self, ctx: Context, channel_id: ChannelId, deleted_message_id: MessageId, guild_id: Option<GuildId>) {
+		modlog::message_deleted(&CHLOE, &ctx, DB.clone(), channel_id, deleted_message_id, guild_id).await;
+		CHLOE.process_msg_delete(channel_id, deleted_message_id, ctx).await;
+	}
+
+	async fn message_delete_bulk(&self, ctx: Context, channel_id: ChannelId, deleted_message_ids: Vec<MessageId>, guild_id: Option<GuildId>) {
+		modlog::messages_deleted(&CHLOE, &ctx, DB.clone(), channel_id, &deleted_message_ids, guild_id).await;
+	}
+
+	async fn guild_member_addition(&self, ctx: Context, member: Member) {
+		modlog::member_joined(&CHLOE, &ctx, DB.clone(), &member).await;
+		antiraid::member_joined(&CHLOE, &ctx, DB.clone(), &member).await;
+		welcome::member_joined(&CHLOE, &ctx, DB.clone(), &member).await;
+	}
+
+	async fn guild_member_removal(&self, ctx: Context, guild_id: GuildId, user: User) {
+		modlog::member_left(&CHLOE, &ctx, DB.clone(), guild_id, &user).await;
+		welcome::member_left(&CHLOE, &ctx, DB.clone(), guild_id, &user).await;
+	}
+
+	async fn guild_member_update(&self, ctx: Context, event: GuildMemberUpdateEvent) {
+		modlog::member_updated(&CHLOE, &ctx, DB.clone(), &event).await;
+	}
+
+	async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+		rolepanels::reaction(&ctx, DB.clone(), &reaction, true).await;
+	}
+
+	async fn reaction_remove(&self, ctx: Context, reaction: Reaction) {
+		rolepanels::reaction(&ctx, DB.clone(), &reaction, false).await;
+	}
+
+	async fn guild_ban_addition(&self, ctx: Context, guild_id: GuildId, user: User) {
+		modlog::ban_changed(&CHLOE, &ctx, DB.clone(), guild_id, &user, true).await;
+	}
+
+	async fn guild_ban_removal(&self, ctx: Context, guild_id: GuildId, user: User) {
+		modlog::ban_changed(&CHLOE, &ctx, DB.clone(), guild_id, &user, false).await;
 	}
 
 	async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-		if let Interaction::ApplicationCommand(command) = interaction {
-			CHLOE.process_inter(command, ctx, DB.clone()).await;
+		match interaction {
+			Interaction::ApplicationCommand(command) => {
+				if let Some(block) = is_blocked(command.user.id, command.guild_id, command.channel_id) {
+					command.create_interaction_response(&ctx.http, |r| {
+						r.kind(InteractionResponseType::ChannelMessageWithSource).interaction_response_data(|d| {
+							let mut content = format!("You can't use my commands in this {}", match block.kind {
+								BlockKind::User => "or any other place",
+								BlockKind::Guild => "server",
+								BlockKind::Channel => "channel"
+							});
+							if let Some(reason) = &block.reason {
+								content += &format!("\nReason: {}", reason);
+							}
+							if let Some(expires) = block.expires {
+								content += &format!("\nUntil: {}", time::discord_timestamp(expires, "f"));
+							}
+							d.content(content).ephemeral(true)
+						})
+					}).await.ok();
+					return;
+				}
+				CHLOE.process_inter(command, ctx, DB.clone()).await;
+			},
+			Interaction::Autocomplete(autocomplete) => {
+				CHLOE.process_autocomplete(autocomplete, ctx, DB.clone()).await;
+			},
+			// Only role panels' and polls' components, since the rest are
+			// handled by whatever sent them
+			Interaction::MessageComponent(component) => {
+				rolepanels::component(&ctx, DB.clone(), &component).await;
+				polls::component(&CHLOE, &ctx, DB.clone(), &component).await;
+			},
+			_ => ()
+		}
+	}
+
+	async fn guild_create(&self, _ctx: Context, guild: Guild) {
+		CHLOE.add_guild(guild.id);
+		CHLOE.modlog.cache_members(&CHLOE, &DB.read().unwrap(), guild.id, guild.members.values());
+	}
+
+	async fn guild_delete(&self, _ctx: Context, incomplete: UnavailableGuild) {
+		// Guilds also get "deleted" when they become unavailable during outages
+		if !incomplete.unavailable {
+			CHLOE.remove_guild(incomplete.id);
 		}
 	}
 
+	async fn shard_stage_update(&self, _ctx: Context, event: ShardStageUpdateEvent) {
+		CHLOE.metrics.set_shard_connected(event.shard_id.0, event.new == ConnectionStage::Connected);
+	}
+
 	async fn ready(&self, ctx: Context, ready: Ready) {
+		let shard = ready.shard.unwrap_or([0, 1]);
+		CHLOE.set_guilds(shard, ready.guilds.iter().map(|g| g.id));
+		CHLOE.metrics.set_ready();
+		let activity = presence::current(&CHLOE, &DB.read().unwrap());
+		ctx.set_presence(activity, OnlineStatus::Online).await;
+		tracing::info!(shard = shard[0], shards = shard[1], guilds = ready.guilds.len(), "{} is connected!", ready.user.name);
+		if !Handler::is_main_shard(&ctx) {
+			return;
+		}
+		tokio::spawn(scheduler::run(&CHLOE, ctx.clone(), DB.clone()));
 		let commands = &CHLOE.commands;
 		serenity::model::application::command::Command::set_global_application_commands(&ctx.http, |new_commands| {
 			for command in commands.iter() {
 				new_commands.create_application_command(|new_command| {
 					new_command.name(command.names[0].clone()).description(command.desc.clone());
+					if !command.perms.is_empty() {
+						new_command.default_member_permissions(command.perms);
+					}
 					for option in command.options.iter() {
 						new_command.create_option(option);
 					}
@@ -57,26 +315,53 @@ impl EventHandler for Handler {
 			}
 			new_commands
 		}).await.unwrap();
-		println!("{} is connected!", ready.user.name);
 	}
 }
 
 #[tokio::main]
 async fn main() {
+	let log_guard = logging::init(&CHLOE.config["log"], metrics::HttpTimer(&CHLOE.metrics)).unwrap();
 	{
-		let _ = &CHLOE.config;
 		let _ = DB.clone();
 	}
+	if let Some(address) = CHLOE.config["metrics_address"].as_str() {
+		tokio::spawn(async move {
+			if let Err(e) = metrics::serve(address, &CHLOE, DB.clone()).await {
+				tracing::error!(error = %e, address, "Unable to serve metrics");
+			}
+		});
+	}
 	let token = env::var("DISCORD_TOKEN").expect("Expected a discord token in the environment variable DISCORD_TOKEN");
 
-	let intents = GatewayIntents::GUILD_MESSAGES
+	// GUILD_MEMBERS is privileged, so it must also be enabled in the
+	// developer portal
+	let intents = GatewayIntents::GUILDS
+		| GatewayIntents::GUILD_MEMBERS
+		| GatewayIntents::GUILD_BANS
+		| GatewayIntents::GUILD_MESSAGES
+		| GatewayIntents::GUILD_MESSAGE_REACTIONS
 		| GatewayIntents::DIRECT_MESSAGES
 		| GatewayIntents::MESSAGE_CONTENT;
 
 	let mut client =
 		Client::builder(&token, intents).event_handler(Handler).await.expect("Err creating client");
 
-	if let Err(why) = client.start().await {
-		println!("Client error: {:?}", why);
+	tokio::spawn(handle_signals());
+	tokio::spawn(presence::rotate(&CHLOE, DB.clone()));
+	let shard_manager = client.shard_manager.clone();
+	CHLOE.set_shard_manager(shard_manager.clone());
+	// Shutting down doesn't always make start() return, like when no shard
+	// has connected yet, so whichever finishes first ends the bot
+	tokio::select! {
+		result = start(&mut client) => if let Err(why) = result {
+			tracing::error!("Client error: {:?}", why);
+		},
+		_ = shut_down(shard_manager) => ()
+	}
+	if CHLOE.shutdown.kind() == Some(ShutdownKind::Restart) {
+		tracing::info!("Restarting");
+		// Flushes the log file, since exec doesn't run destructors
+		drop(log_guard);
+		restart();
 	}
 }